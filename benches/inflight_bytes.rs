@@ -0,0 +1,89 @@
+// peak memory and time of a scan decoding a few huge images among many small ones, with and without the byte budget,
+// e.g. `cargo bench --bench inflight_bytes`
+use std::{alloc::{GlobalAlloc, Layout, System}, path::Path, sync::atomic::{AtomicUsize, Ordering}, time::Instant};
+use futures::StreamExt;
+use image::{ImageBuffer, Rgb};
+use make_xnview_slideshow::{cache::{CacheKey, CacheOptions, set_cache_parent_dir}, config::SlideshowConfig};
+use serde_json::json;
+
+struct CountingAllocator;
+
+static CURRENT_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current_bytes = CURRENT_BYTES.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK_BYTES.fetch_max(current_bytes, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const N_HUGE: usize = 4;
+const N_SMALL: usize = 400;
+
+// noisy, so that the sizes of the files are of real photos rather than of flat colors
+fn write_jpeg(path: &Path, width: u32, height: u32, seed: u32) {
+    let mut state = seed.wrapping_mul(2654435761).max(1);
+    let pixels: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::from_fn(width, height, |x, y| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        Rgb([(x / 16) as u8 ^ (state as u8 & 0x1f), (y / 16) as u8, (state >> 8) as u8])
+    });
+    pixels.save(path).expect("writable");
+}
+
+async fn scan(dir: &Path, max_inflight_bytes: Option<u64>) -> (usize, f64, usize) {
+    let slideshow: SlideshowConfig = serde_json::from_value(json!({
+        "path": dir.join("bench.sld"),
+        "image_dirs": [dir],
+        // decoded, as the pixels are what the huge ones cost
+        "verify_decodable": true,
+    })).expect("valid config");
+    let cache_options = CacheOptions::new(true, false, None, CacheKey::Path);
+    let mut scan_options = slideshow.scan_options(num_cpus::get(), &cache_options).expect("valid options");
+    scan_options.max_inflight_bytes = max_inflight_bytes;
+    let dir_filters = slideshow.dir_filters().expect("valid filters");
+    PEAK_BYTES.store(CURRENT_BYTES.load(Ordering::Relaxed), Ordering::Relaxed);
+    let baseline_bytes = CURRENT_BYTES.load(Ordering::Relaxed);
+    let started = Instant::now();
+    let n_images = make_xnview_slideshow::scan::scan_images(slideshow.image_dir_paths(), scan_options, dir_filters)
+        .filter(|image_info| futures::future::ready(image_info.is_ok()))
+        .count()
+        .await;
+    (n_images, started.elapsed().as_secs_f64(), PEAK_BYTES.load(Ordering::Relaxed) - baseline_bytes)
+}
+
+#[tokio::main]
+async fn main() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    set_cache_parent_dir(dir.path().join("cache"));
+    let image_dir = dir.path().join("images");
+    std::fs::create_dir_all(&image_dir).expect("writable");
+    for i in 0..N_HUGE {
+        write_jpeg(&image_dir.join(format!("huge-{}.jpg", i)), 8000, 6000, i as u32);
+    }
+    for i in 0..N_SMALL {
+        write_jpeg(&image_dir.join(format!("small-{}.jpg", i)), 640, 480, (N_HUGE + i) as u32);
+    }
+    let huge_bytes = std::fs::metadata(image_dir.join("huge-0.jpg")).expect("written").len();
+    println!("{} huge images of {} bytes, {} small ones, {} threads", N_HUGE, huge_bytes, N_SMALL, num_cpus::get());
+    // a budget of about one huge file, so that the huge ones are decoded one at a time
+    for max_inflight_bytes in [None, Some(huge_bytes + 1024 * 1024)] {
+        let (n_images, secs, peak_bytes) = scan(&image_dir, max_inflight_bytes).await;
+        let label = max_inflight_bytes.map_or("without --max-inflight-bytes".to_string(), |max_inflight_bytes| format!("--max-inflight-bytes {}", max_inflight_bytes));
+        println!("{}: {} images in {:.2}s, peak {:.1} MiB", label, n_images, secs, peak_bytes as f64 / 1024.0 / 1024.0);
+    }
+}
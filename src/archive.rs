@@ -0,0 +1,133 @@
+use std::{collections::{HashMap, HashSet}, fs::File, io::Read, path::{Path, PathBuf}, sync::{Mutex, OnceLock}, time::SystemTime};
+use chrono::{Local, NaiveDate, TimeZone};
+use anyhow::Result;
+use tokio::task;
+use tracing::debug;
+use zip::ZipArchive;
+use crate::{Error, cache::cache_parent_dir, remote::{is_media_path, remove_unlisted}};
+
+// as of the remote heads, enough for the exif, the xmp and the size in the headers
+const HEAD_BYTES: u64 = 256 * 1024;
+
+// of the dirs of the heads to their archives, so that the scanned paths are mapped back
+static ARCHIVE_MIRRORS: OnceLock<Mutex<HashMap<PathBuf, PathBuf>>> = OnceLock::new();
+
+pub fn is_archive_path(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+}
+
+// the images in the archive are written to a dir of the cache under their paths in the archive, so that the scan
+// reads them as local files, where only the heads are unless whole, e.g. for the analysis decoding the pixels,
+// and only the changed ones are written again by the modified times
+// the paths of the written ones are returned with the sizes in the archive
+pub async fn extract_archive_heads(archive_path: &Path, whole: bool) -> Result<Vec<(PathBuf, u64)>> {
+    let mirror_dir = cache_parent_dir().await?.join("archives").join(archive_key(archive_path));
+    tokio::fs::create_dir_all(&mirror_dir).await?;
+    let blocking_archive_path = archive_path.to_path_buf();
+    let blocking_mirror_dir = mirror_dir.clone();
+    let head_paths = task::spawn_blocking(move || extract_heads(&blocking_archive_path, &blocking_mirror_dir, whole)).await??;
+    let listed_paths: HashSet<PathBuf> = head_paths.iter().map(|(head_path, _)| head_path.clone()).collect();
+    remove_unlisted(&mirror_dir, &listed_paths).await?;
+    ARCHIVE_MIRRORS.get_or_init(Default::default).lock().expect("not poisoned").insert(mirror_dir, archive_path.to_path_buf());
+    Ok(head_paths)
+}
+
+fn extract_heads(archive_path: &Path, mirror_dir: &Path, whole: bool) -> Result<Vec<(PathBuf, u64)>> {
+    let mut archive = ZipArchive::new(File::open(archive_path)?).map_err(|e| Error::ArchiveError(archive_path.to_path_buf(), e.to_string()))?;
+    let mut head_paths = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(|e| Error::ArchiveError(archive_path.to_path_buf(), e.to_string()))?;
+        // none for the ones escaping the dir, e.g. "../a.jpg"
+        let Some(entry_path) = entry.enclosed_name() else {
+            debug!("skip unsafe entry: {}: {}", archive_path.display(), entry.name());
+            continue;
+        };
+        if !entry.is_file() || !is_media_path(&entry_path) {
+            continue;
+        }
+        let head_path = mirror_dir.join(&entry_path);
+        let size = entry.size();
+        let head_len = if whole { size } else { size.min(HEAD_BYTES) };
+        let modified = entry.last_modified().and_then(zip_modified);
+        if is_extracted(&head_path, head_len, modified) {
+            head_paths.push((head_path, size));
+            continue;
+        }
+        debug!("extract: {}: {}", archive_path.display(), entry_path.display());
+        let mut head = Vec::new();
+        entry.by_ref().take(head_len).read_to_end(&mut head)?;
+        if let Some(parent) = head_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&head_path, &head)?;
+        // of the entry, for the next run and the file-time dates
+        if let Some(modified) = modified {
+            File::options().write(true).open(&head_path)?.set_modified(modified)?;
+        }
+        head_paths.push((head_path, size));
+    }
+    Ok(head_paths)
+}
+
+// the heads of a former run are reused, unless the whole entries are needed this time
+fn is_extracted(path: &Path, len: u64, modified: Option<SystemTime>) -> bool {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return false;
+    };
+    metadata.len() == len && modified.is_some() && metadata.modified().ok() == modified
+}
+
+// in the local time, as zip has no time zone
+fn zip_modified(date_time: zip::DateTime) -> Option<SystemTime> {
+    let date = NaiveDate::from_ymd_opt(date_time.year() as i32, date_time.month() as u32, date_time.day() as u32)?;
+    let date_time = date.and_hms_opt(date_time.hour() as u32, date_time.minute() as u32, date_time.second() as u32)?;
+    Local.from_local_datetime(&date_time).earliest().map(SystemTime::from)
+}
+
+fn archive_key(archive_path: &Path) -> String {
+    format!("{:016x}", xxhash_rust::xxh3::xxh3_64(archive_path.to_string_lossy().as_bytes()))
+}
+
+// the path in the archive as under it, e.g. "/photos/2012-trip.zip/DSC_0001.jpg", none unless of an archive
+pub fn archive_entry_path(head_path: &Path) -> Option<PathBuf> {
+    let archive_mirrors = ARCHIVE_MIRRORS.get()?.lock().expect("not poisoned");
+    head_path.ancestors().find_map(|mirror_dir| {
+        let archive_path = archive_mirrors.get(mirror_dir)?;
+        let entry_path = head_path.strip_prefix(mirror_dir).ok()?;
+        Some(archive_path.join(entry_path))
+    })
+}
+
+// the whole file in a dir of the cache, as the viewers can't open the paths in the archives,
+// none unless the path is of an archive
+pub async fn unpack_archive_entry(path: &Path) -> Result<Option<PathBuf>> {
+    let Some(archive_path) = path.ancestors().skip(1).find(|ancestor| is_archive_path(ancestor) && ancestor.is_file()) else {
+        return Ok(None);
+    };
+    let entry_path = path.strip_prefix(archive_path)?.to_path_buf();
+    let unpacked_path = cache_parent_dir().await?.join("unpacked").join(archive_key(archive_path)).join(&entry_path);
+    let archive_path = archive_path.to_path_buf();
+    let blocking_unpacked_path = unpacked_path.clone();
+    task::spawn_blocking(move || unpack_entry(&archive_path, &entry_path, &blocking_unpacked_path)).await??;
+    Ok(Some(unpacked_path))
+}
+
+fn unpack_entry(archive_path: &Path, entry_path: &Path, unpacked_path: &Path) -> Result<()> {
+    let mut archive = ZipArchive::new(File::open(archive_path)?).map_err(|e| Error::ArchiveError(archive_path.to_path_buf(), e.to_string()))?;
+    // the names in the archive are with slashes regardless of the os
+    let name = entry_path.components().map(|component| component.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+    let mut entry = archive.by_name(&name).map_err(|e| Error::ArchiveError(archive_path.to_path_buf(), format!("{}: {}", name, e)))?;
+    let modified = entry.last_modified().and_then(zip_modified);
+    if is_extracted(unpacked_path, entry.size(), modified) {
+        return Ok(());
+    }
+    debug!("unpack: {}: {}", archive_path.display(), name);
+    if let Some(parent) = unpacked_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::io::copy(&mut entry, &mut File::create(unpacked_path)?)?;
+    if let Some(modified) = modified {
+        File::options().write(true).open(unpacked_path)?.set_modified(modified)?;
+    }
+    Ok(())
+}
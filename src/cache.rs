@@ -0,0 +1,448 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::{Arc, Mutex, OnceLock}, time::{Duration, SystemTime}};
+use serde::{Serialize, Deserialize};
+use clap::crate_name;
+use dirs::cache_dir;
+use md5;
+use anyhow::Result;
+use rusqlite::{Connection, OptionalExtension, TransactionBehavior, params};
+use tokio::{io::AsyncReadExt, sync::OnceCell, task};
+use xxhash_rust::xxh3::xxh3_64;
+use tracing::{debug, warn};
+use crate::{Error, image_info::ImageInfo, scan::ScanMemo};
+
+// bump when the tables change, with the step from the previous version in SCHEMA_MIGRATIONS,
+// the databases older than the steps are recreated
+const CACHE_SCHEMA_VERSION: i64 = 16;
+
+// (from version, the statements to the next version)
+const SCHEMA_MIGRATIONS: &[(i64, &str)] = &[
+    (15, "ALTER TABLE image_infos ADD COLUMN version INTEGER NOT NULL DEFAULT 0;"),
+];
+
+// of the ImageInfo json of the entries, a new field with a serde default needs no bump,
+// but one whose meaning changes does, with its step in migrate_entry
+const IMAGE_INFO_VERSION: i64 = 1;
+
+// pending writes are committed in a transaction once this many
+const WRITE_BATCH_SIZE: usize = 256;
+
+static CACHE_DB: OnceCell<Arc<Mutex<Connection>>> = OnceCell::const_new();
+static PENDING_WRITES: Mutex<Vec<(Vec<u8>, String, String)>> = Mutex::new(Vec::new());
+// (json, written_at, version) by the key
+static CACHE_INDEX: OnceCell<Mutex<HashMap<Vec<u8>, (String, i64, i64)>>> = OnceCell::const_new();
+// instead of the user's cache dir, e.g. a temp dir of the tests
+static CACHE_PARENT_DIR: OnceLock<PathBuf> = OnceLock::new();
+// keys of the entries read, their hit_at is updated together with the writes
+static PENDING_HITS: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheKey {
+    #[default]
+    Path,
+    // md5 of the file content, so that the same file anywhere shares the cache
+    Content,
+    // path relative to the image dir, so that the same layout on another mount point or machine shares the cache
+    Relative,
+    // xxh3 of the head of the file and the size, so that a moved or renamed file keeps its entry without reading it whole
+    Fingerprint,
+}
+
+// enough for the exif and the start of the pixels
+const FINGERPRINT_BYTES: u64 = 64 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct CacheOptions {
+    pub read: bool,
+    pub write: bool,
+    pub ttl: Option<Duration>,
+    pub key: CacheKey,
+    // needed by the relative key
+    pub image_dirs: Arc<Vec<PathBuf>>,
+    // in memory for the run, shared by the slideshows
+    pub memo: Arc<ScanMemo>,
+    // with memory_budget_mb, the entries are queried one by one instead of loading the whole table,
+    // and the memo keeps no image infos, so that the memory doesn't grow with the library
+    pub bounded_memory: bool,
+}
+
+impl CacheOptions {
+    pub fn new(no_cache: bool, refresh_cache: bool, ttl_days: Option<u64>, key: CacheKey) -> Self {
+        // --no-cache wins over everything else
+        Self {
+            read: !no_cache && !refresh_cache,
+            write: !no_cache,
+            ttl: ttl_days.map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+            key,
+            image_dirs: Arc::new(vec![]),
+            memo: Arc::new(ScanMemo::default()),
+            bounded_memory: false,
+        }
+    }
+
+    pub fn with_bounded_memory(self, bounded_memory: bool) -> Self {
+        Self { bounded_memory, ..self }
+    }
+
+    pub fn with_image_dirs(&self, image_dirs: Vec<PathBuf>) -> Self {
+        Self {
+            image_dirs: Arc::new(image_dirs),
+            ..self.clone()
+        }
+    }
+}
+
+#[tracing::instrument(name = "cache", skip_all)]
+pub async fn cached_image_info(path: impl AsRef<Path>, cache_options: &CacheOptions) -> Option<ImageInfo> {
+    let key = match cache_key(path, cache_options).await {
+        Ok(key) => key,
+        Err(_) => return None,
+    };
+    let entry = if cache_options.bounded_memory {
+        query_entry(key.clone()).await
+    } else {
+        // cloned, so that the other lookups don't wait for the parse
+        cache_index().await.map(|cache_index| cache_index.lock().expect("not poisoned").get(&key).cloned())
+    };
+    let entry = match entry {
+        Ok(entry) => entry,
+        Err(e) => {
+            warn!("Failed to read cache: {:?}", e);
+            return None;
+        }
+    };
+    let Some((json, written_at, version)) = entry else {
+        debug!("cache miss");
+        return None;
+    };
+    if let Some(ttl) = cache_options.ttl {
+        if is_cache_expired(written_at, ttl) {
+            debug!("cache expired");
+            return None;
+        }
+    }
+    // of a newer build, whose fields may mean otherwise
+    if version > IMAGE_INFO_VERSION {
+        debug!("cache entry of a newer version {}", version);
+        return None;
+    }
+    let is_migrated = version < IMAGE_INFO_VERSION;
+    let json = if is_migrated {
+        match migrate_entry(version, &json) {
+            Some(json) => json,
+            None => {
+                // overwritten by the fresh one
+                debug!("cache entry of an old version {}", version);
+                return None;
+            }
+        }
+    } else {
+        json
+    };
+    match serde_json::from_str::<ImageInfo>(&json) {
+        Ok(image_info) => {
+            if is_migrated {
+                // written back with flush_cache, so that it's migrated once
+                if let Some(cache_index) = CACHE_INDEX.get() {
+                    cache_index.lock().expect("not poisoned").insert(key.clone(), (json.clone(), written_at, IMAGE_INFO_VERSION));
+                }
+                if cache_options.write {
+                    PENDING_WRITES.lock().expect("not poisoned").push((key, image_info.path.to_string_lossy().to_string(), json));
+                }
+            } else {
+                PENDING_HITS.lock().expect("not poisoned").push(key);
+            }
+            Some(image_info)
+        }
+        Err(e) => {
+            warn!("Failed to parse cache entry, remove it: {:?}", e);
+            if let Some(cache_index) = CACHE_INDEX.get() {
+                cache_index.lock().expect("not poisoned").remove(&key);
+            }
+            // self-heal, the next run writes a fresh one
+            let result = with_cache_db(move |db| Ok(db.execute("DELETE FROM image_infos WHERE key = ?1", params![key])?)).await;
+            if let Err(e) = result {
+                warn!("Failed to remove cache entry: {:?}", e);
+            }
+            None
+        }
+    }
+}
+
+// upgrades an entry a version at a time, none when the image needs parsing again, e.g. a field computed otherwise,
+// so that only the entries of the images affected are invalidated instead of the whole cache
+fn migrate_entry(mut version: i64, json: &str) -> Option<String> {
+    let image_info: serde_json::Value = serde_json::from_str(json).ok()?;
+    while version < IMAGE_INFO_VERSION {
+        match version {
+            // written before the versions, the same as 1
+            0 => {}
+            _ => return None,
+        }
+        version += 1;
+    }
+    serde_json::to_string(&image_info).ok()
+}
+
+// the whole table, loaded by the first lookup, so that a run with 100k images doesn't query per image
+async fn cache_index() -> Result<&'static Mutex<HashMap<Vec<u8>, (String, i64, i64)>>> {
+    CACHE_INDEX.get_or_try_init(|| async {
+        let entries = with_cache_db(|db| {
+            let mut statement = db.prepare("SELECT key, image_info, written_at, version FROM image_infos")?;
+            let entries = statement.query_map([], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?, row.get(3)?))))?.collect::<Result<_, _>>()?;
+            Ok(entries)
+        }).await?;
+        Ok::<_, anyhow::Error>(Mutex::new(entries))
+    }).await
+}
+
+// (json, written_at, version) of the key, of the pending write first as it's not in the table yet
+async fn query_entry(key: Vec<u8>) -> Result<Option<(String, i64, i64)>> {
+    let pending_json = PENDING_WRITES.lock().expect("not poisoned").iter().rev().find(|(pending_key, _, _)| *pending_key == key).map(|(_, _, json)| json.clone());
+    if let Some(json) = pending_json {
+        return Ok(Some((json, unix_time_now(), IMAGE_INFO_VERSION)));
+    }
+    with_cache_db(move |db| {
+        let mut statement = db.prepare_cached("SELECT image_info, written_at, version FROM image_infos WHERE key = ?1")?;
+        Ok(statement.query_row(params![key], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).optional()?)
+    }).await
+}
+
+fn is_cache_expired(written_at: i64, ttl: Duration) -> bool {
+    // written in the future is just trusted
+    unix_time_now() - written_at > ttl.as_secs() as i64
+}
+
+fn unix_time_now() -> i64 {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |duration| duration.as_secs() as i64)
+}
+
+// the write is batched, call flush_cache at the end
+pub async fn cache_image_info(image_info: &ImageInfo, cache_options: &CacheOptions) -> Result<()> {
+    let key = cache_key(&image_info.path, cache_options).await?;
+    let json = serde_json::to_string(image_info)?;
+    let path = image_info.path.to_string_lossy().to_string();
+    // e.g. watch reads it again in the same process
+    if let Some(cache_index) = CACHE_INDEX.get() {
+        cache_index.lock().expect("not poisoned").insert(key.clone(), (json.clone(), unix_time_now(), IMAGE_INFO_VERSION));
+    }
+    let batch = {
+        let mut pending_writes = PENDING_WRITES.lock().expect("not poisoned");
+        pending_writes.push((key, path, json));
+        if pending_writes.len() >= WRITE_BATCH_SIZE {
+            std::mem::take(&mut *pending_writes)
+        } else {
+            vec![]
+        }
+    };
+    if !batch.is_empty() {
+        write_batch(batch).await?;
+    }
+    Ok(())
+}
+
+pub async fn flush_cache() -> Result<()> {
+    let batch = std::mem::take(&mut *PENDING_WRITES.lock().expect("not poisoned"));
+    if !batch.is_empty() || !PENDING_HITS.lock().expect("not poisoned").is_empty() {
+        write_batch(batch).await?;
+    }
+    Ok(())
+}
+
+async fn write_batch(batch: Vec<(Vec<u8>, String, String)>) -> Result<()> {
+    let hits = std::mem::take(&mut *PENDING_HITS.lock().expect("not poisoned"));
+    with_cache_db(move |db| {
+        let written_at = unix_time_now();
+        let transaction = db.transaction()?;
+        {
+            let mut statement = transaction.prepare_cached("INSERT OR REPLACE INTO image_infos (key, path, image_info, written_at, hit_at, version) VALUES (?1, ?2, ?3, ?4, ?4, ?5)")?;
+            for (key, path, json) in &batch {
+                statement.execute(params![key, path, json, written_at, IMAGE_INFO_VERSION])?;
+            }
+            let mut statement = transaction.prepare_cached("UPDATE image_infos SET hit_at = ?1 WHERE key = ?2")?;
+            for key in &hits {
+                statement.execute(params![written_at, key])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }).await
+}
+
+// runs on a blocking thread, as sqlite calls block
+async fn with_cache_db<T: Send + 'static>(f: impl FnOnce(&mut Connection) -> Result<T> + Send + 'static) -> Result<T> {
+    let db = CACHE_DB.get_or_try_init(|| async {
+        let db_path = cache_db_path().await?;
+        let db = task::spawn_blocking(move || open_cache_db(&db_path)).await??;
+        Ok::<_, anyhow::Error>(Arc::new(Mutex::new(db)))
+    }).await?.clone();
+    task::spawn_blocking(move || {
+        let mut db = db.lock().expect("not poisoned");
+        f(&mut db)
+    }).await?
+}
+
+fn open_cache_db(db_path: &Path) -> Result<Connection> {
+    let mut db = Connection::open(db_path)?;
+    // other runs may use the same database at the same time
+    db.busy_timeout(Duration::from_secs(30))?;
+    db.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+    // a step per transaction, where the version is read again as another run may have migrated it meanwhile
+    loop {
+        let transaction = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let schema_version: i64 = transaction.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if schema_version == CACHE_SCHEMA_VERSION {
+            break;
+        }
+        match SCHEMA_MIGRATIONS.iter().find(|(from_version, _)| *from_version == schema_version) {
+            Some((_, migration)) => {
+                transaction.execute_batch(migration)?;
+                transaction.pragma_update(None, "user_version", schema_version + 1)?;
+            }
+            None => {
+                // too old or of a newer build, the cache can be rebuilt anyway
+                transaction.execute_batch("
+                    DROP TABLE IF EXISTS image_infos;
+                    CREATE TABLE image_infos (
+                        key BLOB PRIMARY KEY,
+                        path TEXT NOT NULL,
+                        image_info TEXT NOT NULL,
+                        written_at INTEGER NOT NULL,
+                        hit_at INTEGER NOT NULL,
+                        version INTEGER NOT NULL DEFAULT 0
+                    );
+                ")?;
+                transaction.pragma_update(None, "user_version", CACHE_SCHEMA_VERSION)?;
+            }
+        }
+        transaction.commit()?;
+    }
+    Ok(db)
+}
+
+pub async fn cache_key(path: impl AsRef<Path>, cache_options: &CacheOptions) -> Result<Vec<u8>> {
+    let path = path.as_ref();
+    let key = match cache_options.key {
+        CacheKey::Path => {
+            // the same file via another relative path or symlink shares the entry
+            let path = tokio::fs::canonicalize(path).await.unwrap_or_else(|_| path.to_path_buf());
+            path.as_os_str().as_encoded_bytes().to_vec()
+        }
+        CacheKey::Content => format!("content:{:x}", md5::compute(tokio::fs::read(path).await?)).into_bytes(),
+        CacheKey::Relative => format!("relative:{}", relative_cache_key(path, &cache_options.image_dirs)).into_bytes(),
+        CacheKey::Fingerprint => {
+            let mut file = tokio::fs::File::open(path).await?;
+            let size = file.metadata().await?.len();
+            let mut head = Vec::new();
+            (&mut file).take(FINGERPRINT_BYTES).read_to_end(&mut head).await?;
+            format!("fingerprint:{:016x}:{}", xxh3_64(&head), size).into_bytes()
+        }
+    };
+    Ok(key)
+}
+
+// separators are normalized, so that windows and unix machines share the cache too
+fn relative_cache_key(path: &Path, image_dirs: &[PathBuf]) -> String {
+    let relative_path = image_dirs.iter()
+        .filter_map(|image_dir| path.strip_prefix(image_dir).ok())
+        .min_by_key(|relative_path| relative_path.components().count())
+        .unwrap_or(path);
+    relative_path.components().map(|component| component.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")
+}
+
+pub async fn cache_db_path() -> Result<PathBuf> {
+    Ok(cache_parent_dir().await?.join("cache.sqlite3"))
+}
+
+// only before the cache is first used, false when already set
+pub fn set_cache_parent_dir(cache_parent_dir: PathBuf) -> bool {
+    CACHE_PARENT_DIR.set(cache_parent_dir).is_ok()
+}
+
+pub async fn cache_parent_dir() -> Result<PathBuf> {
+    let cache_parent_dir = match CACHE_PARENT_DIR.get() {
+        Some(cache_parent_dir) => cache_parent_dir.clone(),
+        None => cache_dir().ok_or(Error::CacheDirError)?.join(crate_name!()),
+    };
+    if !cache_parent_dir.exists() {
+        tokio::fs::create_dir_all(&cache_parent_dir).await?;
+    }
+    Ok(cache_parent_dir)
+}
+
+#[derive(Debug, Default)]
+pub struct CacheStats {
+    pub n_entries: usize,
+    pub total_bytes: u64,
+    // only counted with a ttl
+    pub n_expired: usize,
+}
+
+pub async fn cache_stats(ttl: Option<Duration>) -> Result<CacheStats> {
+    with_cache_db(move |db| {
+        let (n_entries, total_bytes): (i64, i64) = db.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(LENGTH(key) + LENGTH(image_info)), 0) FROM image_infos",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let n_expired: i64 = match ttl {
+            Some(ttl) => db.query_row(
+                "SELECT COUNT(*) FROM image_infos WHERE written_at < ?1",
+                params![unix_time_now() - ttl.as_secs() as i64],
+                |row| row.get(0),
+            )?,
+            None => 0,
+        };
+        Ok(CacheStats {
+            n_entries: n_entries as usize,
+            total_bytes: total_bytes as u64,
+            n_expired: n_expired as usize,
+        })
+    }).await
+}
+
+// the entries of the deleted files, and the ones not read for unused_days when given,
+// returns the number of removed entries
+pub async fn prune_cache(unused_days: Option<u64>) -> Result<usize> {
+    flush_cache().await?;
+    with_cache_db(move |db| {
+        let unused_since = unused_days.map(|days| unix_time_now() - (days * 24 * 60 * 60) as i64);
+        let mut stale_keys: Vec<Vec<u8>> = Vec::new();
+        {
+            let mut statement = db.prepare("SELECT key, path, hit_at FROM image_infos")?;
+            let mut rows = statement.query([])?;
+            while let Some(row) = rows.next()? {
+                let key: Vec<u8> = row.get(0)?;
+                let path: String = row.get(1)?;
+                let hit_at: i64 = row.get(2)?;
+                let is_unused = unused_since.is_some_and(|unused_since| hit_at < unused_since);
+                if is_unused || !Path::new(&path).exists() {
+                    stale_keys.push(key);
+                }
+            }
+        }
+        let transaction = db.transaction()?;
+        {
+            let mut statement = transaction.prepare("DELETE FROM image_infos WHERE key = ?1")?;
+            for key in &stale_keys {
+                statement.execute(params![key])?;
+            }
+        }
+        transaction.commit()?;
+        if let Some(cache_index) = CACHE_INDEX.get() {
+            let mut cache_index = cache_index.lock().expect("not poisoned");
+            for key in &stale_keys {
+                cache_index.remove(key);
+            }
+        }
+        Ok(stale_keys.len())
+    }).await
+}
+
+// returns the number of removed entries
+pub async fn clear_cache() -> Result<usize> {
+    if let Some(cache_index) = CACHE_INDEX.get() {
+        cache_index.lock().expect("not poisoned").clear();
+    }
+    with_cache_db(|db| Ok(db.execute("DELETE FROM image_infos", [])?)).await
+}
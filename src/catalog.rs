@@ -0,0 +1,135 @@
+use std::{collections::HashMap, path::{Path, PathBuf}};
+use chrono::{DateTime, Local, NaiveDateTime};
+use anyhow::Result;
+use rusqlite::{Connection, OpenFlags, types::Value};
+use tokio::task;
+use tracing::warn;
+
+// what XnView MP knows about an image, winning over the xmp
+#[derive(Debug, Clone, Default)]
+pub struct CatalogEntry {
+    pub rating: Option<i32>,
+    pub color_label: Option<String>,
+    pub categories: Vec<String>,
+    // of the exif as XnView read it, so that the image can be listed without opening the file
+    pub capture_date_time: Option<NaiveDateTime>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+// of the Label column, in the order of the menu of XnView MP
+const COLOR_LABELS: [&str; 8] = ["Red", "Orange", "Yellow", "Green", "Blue", "Purple", "Pink", "Gray"];
+
+// the images of XnView.db under the image dirs, so that the dirs don't need to be walked
+#[derive(Debug, Default)]
+pub struct Catalog {
+    pub entries: HashMap<PathBuf, CatalogEntry>,
+}
+
+impl Catalog {
+    // the tables of XnView MP 1.x: Folders, Images, TagTree and TagsImages
+    pub async fn open(catalog_path: impl Into<PathBuf>, image_dirs: Vec<PathBuf>) -> Result<Self> {
+        let catalog_path = catalog_path.into();
+        task::spawn_blocking(move || {
+            // read only, as XnView may be running
+            let db = Connection::open_with_flags(&catalog_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+            let mut paths_by_id: HashMap<i64, PathBuf> = HashMap::new();
+            let mut entries: HashMap<PathBuf, CatalogEntry> = HashMap::new();
+            {
+                let mut statement = db.prepare("SELECT i.ImageID, f.Pathname, i.Filename, i.Rating FROM Images i JOIN Folders f ON i.FolderID = f.FolderID")?;
+                let mut rows = statement.query([])?;
+                while let Some(row) = rows.next()? {
+                    let image_id: i64 = row.get(0)?;
+                    let dir: String = row.get(1)?;
+                    let file_name: String = row.get(2)?;
+                    let rating: Option<i32> = row.get(3)?;
+                    let path = Path::new(&dir).join(file_name);
+                    if !image_dirs.iter().any(|image_dir| path.starts_with(image_dir)) {
+                        continue;
+                    }
+                    paths_by_id.insert(image_id, path.clone());
+                    entries.insert(path, CatalogEntry {
+                        // 0 is unrated
+                        rating: rating.filter(|rating| *rating > 0),
+                        ..Default::default()
+                    });
+                }
+            }
+            match read_color_labels(&db) {
+                Ok(color_labels) => {
+                    for (image_id, color_label) in color_labels {
+                        let Some(entry) = paths_by_id.get(&image_id).and_then(|path| entries.get_mut(path)) else {
+                            continue;
+                        };
+                        entry.color_label = Some(color_label);
+                    }
+                }
+                Err(e) => warn!("Failed to read color labels of the catalog, ignore them: {:?}", e),
+            }
+            match read_capture_infos(&db) {
+                Ok(capture_infos) => {
+                    for (image_id, capture_date_time, width, height) in capture_infos {
+                        let Some(entry) = paths_by_id.get(&image_id).and_then(|path| entries.get_mut(path)) else {
+                            continue;
+                        };
+                        entry.capture_date_time = capture_date_time;
+                        entry.width = width;
+                        entry.height = height;
+                    }
+                }
+                // the images are opened for them instead
+                Err(e) => warn!("Failed to read capture dates of the catalog, ignore them: {:?}", e),
+            }
+            match read_categories(&db) {
+                Ok(categories) => {
+                    for (image_id, category) in categories {
+                        let Some(entry) = paths_by_id.get(&image_id).and_then(|path| entries.get_mut(path)) else {
+                            continue;
+                        };
+                        entry.categories.push(category);
+                    }
+                }
+                // older catalogs may lack the tables
+                Err(e) => warn!("Failed to read categories of the catalog, ignore them: {:?}", e),
+            }
+            Ok(Self { entries })
+        }).await?
+    }
+}
+
+// 0 is none, and the others are 1 to 8
+fn read_color_labels(db: &Connection) -> Result<Vec<(i64, String)>> {
+    let mut statement = db.prepare("SELECT ImageID, Label FROM Images WHERE Label > 0")?;
+    let labels: Vec<(i64, i64)> = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?.collect::<Result<_, _>>()?;
+    let color_labels = labels.into_iter()
+        .filter_map(|(image_id, label)| COLOR_LABELS.get(label as usize - 1).map(|color_label| (image_id, color_label.to_string())))
+        .collect();
+    Ok(color_labels)
+}
+
+// the sizes and the dates are 0 until XnView has read the image
+fn read_capture_infos(db: &Connection) -> Result<Vec<(i64, Option<NaiveDateTime>, Option<u32>, Option<u32>)>> {
+    let mut statement = db.prepare("SELECT ImageID, TakenDate, Width, Height FROM Images")?;
+    let capture_infos = statement.query_map([], |row| {
+        let width: Option<u32> = row.get(2)?;
+        let height: Option<u32> = row.get(3)?;
+        Ok((row.get(0)?, capture_date_time(row.get(1)?), width.filter(|width| *width > 0), height.filter(|height| *height > 0)))
+    })?.collect::<Result<_, _>>()?;
+    Ok(capture_infos)
+}
+
+// either the text of the exif or seconds since the epoch, in the local time as the exif has no time zone
+fn capture_date_time(value: Value) -> Option<NaiveDateTime> {
+    match value {
+        Value::Text(text) => ["%Y-%m-%d %H:%M:%S", "%Y:%m:%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"].iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(text.trim(), format).ok()),
+        Value::Integer(secs) if secs > 0 => DateTime::from_timestamp(secs, 0).map(|date_time| date_time.with_timezone(&Local).naive_local()),
+        _ => None,
+    }
+}
+
+fn read_categories(db: &Connection) -> Result<Vec<(i64, String)>> {
+    let mut statement = db.prepare("SELECT ti.ImageID, t.Label FROM TagsImages ti JOIN TagTree t ON ti.TagID = t.TagID")?;
+    let categories = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?.collect::<Result<_, _>>()?;
+    Ok(categories)
+}
@@ -0,0 +1,99 @@
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use chrono::{Datelike, NaiveDateTime};
+use ab_glyph::{FontVec, PxScale};
+use image::{Rgba, RgbaImage};
+use tokio::task;
+use anyhow::Result;
+use crate::{cache::cache_parent_dir, slideshow::Color};
+
+const MONTH_NAMES: [&str; 12] = ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"];
+
+// a title slide before each run of images with the same title, so only for the chronological sorts
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChapterConfig {
+    // with year, season, month and quarter, e.g. "{month} {year}" for monthly chapters
+    #[serde(default = "default_title")]
+    pub title: String,
+    // a ttf or otf file, e.g. "C:/Windows/Fonts/arial.ttf"
+    pub font: PathBuf,
+    // of the height of the screen
+    #[serde(default = "default_font_size_ratio")]
+    pub font_size_ratio: f32,
+}
+
+fn default_title() -> String {
+    "{season} {year}".to_string()
+}
+
+fn default_font_size_ratio() -> f32 {
+    0.1
+}
+
+impl ChapterConfig {
+    // e.g. "Summer 2019"
+    pub fn title(&self, date_time: NaiveDateTime) -> String {
+        let month = date_time.month();
+        let season = match month {
+            3..=5 => "Spring",
+            6..=8 => "Summer",
+            9..=11 => "Autumn",
+            _ => "Winter",
+        };
+        self.title
+            .replace("{year}", &date_time.year().to_string())
+            .replace("{season}", season)
+            .replace("{month}", MONTH_NAMES[month as usize - 1])
+            .replace("{quarter}", &((month - 1) / 3 + 1).to_string())
+    }
+
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if !self.font.is_file() {
+            problems.push(format!("font of chapters not found: {}", self.font.display()));
+        }
+        if !(self.font_size_ratio > 0.0 && self.font_size_ratio <= 1.0) {
+            problems.push(format!("font_size_ratio {} of chapters is not in (0, 1]", self.font_size_ratio));
+        }
+        problems
+    }
+}
+
+// how the title slides look, of the screen and the colors of the header
+#[derive(Debug, Clone)]
+pub struct TitleStyle {
+    pub width: u32,
+    pub height: u32,
+    pub background_color: Color,
+    pub text_color: Color,
+    pub font: PathBuf,
+    pub font_size_ratio: f32,
+}
+
+// the slide is kept in the cache dir by the text and the style, as the slideshow refers to it
+pub async fn render_title(text: &str, style: &TitleStyle) -> Result<PathBuf> {
+    let key = format!("{}\0{:?}", text, style);
+    let titles_dir = cache_parent_dir().await?.join("titles");
+    let path = titles_dir.join(format!("{:x}.png", md5::compute(key.as_bytes())));
+    if path.exists() {
+        return Ok(path);
+    }
+    tokio::fs::create_dir_all(&titles_dir).await?;
+    let font_data = tokio::fs::read(&style.font).await?;
+    let (text, style, title_path) = (text.to_string(), style.clone(), path.clone());
+    task::spawn_blocking(move || draw_title(&text, &style, font_data, &title_path)).await??;
+    Ok(path)
+}
+
+// centered on the background
+fn draw_title(text: &str, style: &TitleStyle, font_data: Vec<u8>, path: &Path) -> Result<()> {
+    let font = FontVec::try_from_vec(font_data)?;
+    let mut img = RgbaImage::from_pixel(style.width, style.height, Rgba(style.background_color.0));
+    let scale = PxScale::from(style.height as f32 * style.font_size_ratio);
+    let (text_width, text_height) = imageproc::drawing::text_size(scale, &font, text);
+    let x = (style.width as i32 - text_width as i32) / 2;
+    let y = (style.height as i32 - text_height as i32) / 2;
+    imageproc::drawing::draw_text_mut(&mut img, Rgba(style.text_color.0), x, y, scale, &font, text);
+    img.save(path)?;
+    Ok(())
+}
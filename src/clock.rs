@@ -0,0 +1,43 @@
+use std::sync::{Arc, RwLock};
+use chrono::{DateTime, Local, NaiveDate};
+
+// the time the relative dates like last_n_days and on_this_day are of, so that they can be tested on a fixed day
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Local>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock(pub DateTime<Local>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Local> {
+        self.0
+    }
+}
+
+// the system clock unless set
+static CLOCK: RwLock<Option<Arc<dyn Clock>>> = RwLock::new(None);
+
+pub fn set_clock(clock: Arc<dyn Clock>) {
+    *CLOCK.write().expect("not poisoned") = Some(clock);
+}
+
+pub fn now() -> DateTime<Local> {
+    match &*CLOCK.read().expect("not poisoned") {
+        Some(clock) => clock.now(),
+        None => SystemClock.now(),
+    }
+}
+
+pub fn today() -> NaiveDate {
+    now().date_naive()
+}
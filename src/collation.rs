@@ -0,0 +1,115 @@
+use std::{cmp::Ordering, path::Path};
+use serde::{Serialize, Deserialize};
+use icu_collator::{Collator, CollatorOptions, Numeric};
+use icu_locid::Locale;
+use anyhow::Result;
+use crate::Error;
+
+// how the paths are ordered, for sort = "path" and the ties of the others
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PathCollation {
+    // by the bytes, IMG_10.jpg before IMG_9.jpg
+    #[default]
+    Bytewise,
+    // the runs of digits by their values, so IMG_9.jpg before IMG_10.jpg, case-insensitive otherwise
+    Natural,
+    // by the collation of collation_locale, e.g. "ja" orders kana by the gojūon, with the digits by their values
+    Locale,
+}
+
+pub enum PathComparator {
+    Bytewise,
+    Natural,
+    Locale(Box<Collator>),
+}
+
+impl PathComparator {
+    // the root collation of unicode when no locale is given
+    pub fn new(path_collation: PathCollation, collation_locale: Option<&str>) -> Result<Self> {
+        let comparator = match path_collation {
+            PathCollation::Bytewise => PathComparator::Bytewise,
+            PathCollation::Natural => PathComparator::Natural,
+            PathCollation::Locale => {
+                let locale: Locale = match collation_locale {
+                    Some(collation_locale) => collation_locale.parse().map_err(|_| Error::LocaleError(collation_locale.to_string()))?,
+                    None => Locale::UND,
+                };
+                let mut options = CollatorOptions::new();
+                options.numeric = Some(Numeric::On);
+                let collator = Collator::try_new(&locale.into(), options).map_err(|e| Error::LocaleError(e.to_string()))?;
+                PathComparator::Locale(Box::new(collator))
+            }
+        };
+        Ok(comparator)
+    }
+
+    // by the components, so that a dir comes before the ones with the same prefix, e.g. "2019/" before "2019 Summer/",
+    // and by the bytes when equal, so that the order is total
+    pub fn compare(&self, a: &Path, b: &Path) -> Ordering {
+        if matches!(self, PathComparator::Bytewise) {
+            return a.cmp(b);
+        }
+        let a_components = a.components().map(|component| component.as_os_str().to_string_lossy());
+        let b_components = b.components().map(|component| component.as_os_str().to_string_lossy());
+        a_components.zip(b_components)
+            .map(|(a_component, b_component)| match self {
+                PathComparator::Bytewise => a_component.cmp(&b_component),
+                PathComparator::Natural => natural_compare(&a_component, &b_component),
+                PathComparator::Locale(collator) => collator.compare(&a_component, &b_component),
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a.components().count().cmp(&b.components().count()))
+            .then_with(|| a.cmp(b))
+    }
+}
+
+// the full-width digits of japanese names too
+fn digit_value(c: char) -> Option<u32> {
+    match c {
+        '0'..='9' => Some(c as u32 - '0' as u32),
+        '０'..='９' => Some(c as u32 - '０' as u32),
+        _ => None,
+    }
+}
+
+fn natural_compare(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_char), Some(b_char)) => {
+                if digit_value(a_char).is_some() && digit_value(b_char).is_some() {
+                    let a_digits = take_digits(&mut a_chars);
+                    let b_digits = take_digits(&mut b_chars);
+                    // without the leading zeros, the longer one is the larger
+                    let a_value: Vec<u32> = a_digits.iter().copied().skip_while(|digit| *digit == 0).collect();
+                    let b_value: Vec<u32> = b_digits.iter().copied().skip_while(|digit| *digit == 0).collect();
+                    let ordering = a_value.len().cmp(&b_value.len()).then_with(|| a_value.cmp(&b_value));
+                    if ordering.is_ne() {
+                        return ordering;
+                    }
+                } else {
+                    let ordering = a_char.to_lowercase().cmp(b_char.to_lowercase());
+                    if ordering.is_ne() {
+                        return ordering;
+                    }
+                    a_chars.next();
+                    b_chars.next();
+                }
+            }
+        }
+    }
+}
+
+fn take_digits(chars: &mut std::iter::Peekable<std::str::Chars>) -> Vec<u32> {
+    let mut digits = Vec::new();
+    while let Some(digit) = chars.peek().copied().and_then(digit_value) {
+        digits.push(digit);
+        chars.next();
+    }
+    digits
+}
@@ -0,0 +1,811 @@
+use std::{collections::HashSet, path::{Path, PathBuf}, sync::Arc};
+use serde::{Serialize, Deserialize};
+use rand::{SeedableRng, rngs::StdRng};
+use anyhow::Result;
+use chrono::NaiveDate;
+use regex::{Captures, Regex};
+use crate::{Error, cache::{CacheKey, CacheOptions}, chapters::ChapterConfig, collation::{PathCollation, PathComparator}, filter::{DirFilters, FileSize, FilterOverrides, ImageFilter}, date::{DateOptions, DatePick}, duration::DurationRule, entry_options::EntryRule, image_info::{AnalysisOptions, DateSource}, junk::JunkRules, live_photo::LivePhotoPairing, monitors::Monitor, notify::Notifier, output::OutputFormat, output_path::{has_placeholders, resolve_path_template}, raw::RawPairing, remote::{check_remote_url, is_remote}, split::SplitBy, scan::{ScanOptions, ScanStats, WalkOptions, build_glob_set}, schedule::Schedule, selection::{Freshness, SampleStrategy, SortOrder}, slideshow::{Color, OutputEncoding, SlideshowHeader, UnencodablePaths}, variants::{PreferVariant, variant_suffix_regex}};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Config {
+    pub slideshows: Vec<SlideshowConfig>,
+    // in days, cache entries written before that are ignored
+    #[serde(default)]
+    pub cache_ttl: Option<u64>,
+    // an image written by a slideshow is skipped by the later ones, in the config order
+    #[serde(default)]
+    pub exclusive: bool,
+    // the names of the slideshows sharing no image, e.g. [["best-of", "2022"]], where the earlier ones of a group
+    // keep the images, and are written first regardless of the config order
+    #[serde(default)]
+    pub exclusive_groups: Vec<Vec<String>>,
+    #[serde(default)]
+    pub cache_key: CacheKey,
+    // images parsed at once, the number of cpus by default
+    #[serde(default)]
+    pub parse_concurrency: Option<usize>,
+    // the slideshows of disjoint image dirs are generated at once, sharing parse_concurrency,
+    // false for e.g. the dirs of a single spinning disk
+    #[serde(default = "default_true")]
+    pub parallel_slideshows: bool,
+    // throttles the parsing, e.g. for a nas
+    #[serde(default)]
+    pub max_files_per_sec: Option<f64>,
+    #[serde(default)]
+    pub max_bytes_per_sec: Option<u64>,
+    // fail on the first unreadable image instead of skipping it
+    #[serde(default)]
+    pub strict: bool,
+    // the scheduled runs of the daemon are delayed randomly up to this, so that they don't hit a nas at once
+    #[serde(default)]
+    pub schedule_jitter_secs: u64,
+    // of the previews made for the slideshows with thumbnails, in the cache dir when omitted
+    #[serde(default)]
+    pub thumbnail_dir: Option<PathBuf>,
+    // the longer side of the previews
+    #[serde(default = "default_thumbnail_size")]
+    pub thumbnail_size: u32,
+    // an UltraFace onnx model (version-RFB-320.onnx) for contains_faces, with the faces feature
+    #[serde(default)]
+    pub face_model: Option<PathBuf>,
+    // of the matched images kept in memory while sorting, the rest is sorted on disk in the cache dir,
+    // and with it, the cache is queried per image instead of loaded whole, and the parsed images are not memoized across the slideshows
+    #[serde(default)]
+    pub memory_budget_mb: Option<usize>,
+    // the .scr set by --install-screensaver, the one of XnView Classic in program files when omitted
+    #[serde(default)]
+    pub screensaver_path: Option<PathBuf>,
+    // of the slideshows without their own
+    #[serde(default)]
+    pub junk: JunkRules,
+    // told the results after each run, e.g. [{ type = "desktop" }, { type = "webhook", url = "https://...", only_errors = true }]
+    #[serde(default)]
+    pub notifiers: Vec<Notifier>,
+}
+
+// a path, or a table like {"path": "~/Pictures/Family", "weight": 0.7, "min_rating": 5},
+// where the path may be a url like {"path": "webdav://nas.local/photos", "mapped_path": "Z:\\photos"}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(from = "ImageDirEntry")]
+pub struct ImageDir {
+    pub path: PathBuf,
+    // the share of the slides from this dir among the weighted ones, 1 when only the others have one
+    pub weight: Option<f64>,
+    // of a remote dir, where the machine showing the slideshow sees it, e.g. a mounted share, written into the output
+    pub mapped_path: Option<PathBuf>,
+    #[serde(flatten)]
+    pub filter_overrides: FilterOverrides,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ImageDirEntry {
+    Path(PathBuf),
+    Table {
+        path: PathBuf,
+        #[serde(default)]
+        weight: Option<f64>,
+        #[serde(default)]
+        mapped_path: Option<PathBuf>,
+        #[serde(flatten)]
+        filter_overrides: FilterOverrides,
+    },
+}
+
+impl From<ImageDirEntry> for ImageDir {
+    fn from(entry: ImageDirEntry) -> Self {
+        match entry {
+            ImageDirEntry::Path(path) => Self { path, weight: None, mapped_path: None, filter_overrides: FilterOverrides::default() },
+            ImageDirEntry::Table { path, weight, mapped_path, filter_overrides } => Self { path, weight, mapped_path, filter_overrides },
+        }
+    }
+}
+
+impl ImageDir {
+    pub fn is_remote(&self) -> bool {
+        is_remote(&self.path)
+    }
+
+    // as the images are written, the mapped path of a remote dir
+    pub fn local_path(&self) -> &Path {
+        match &self.mapped_path {
+            Some(mapped_path) if self.is_remote() => mapped_path,
+            _ => &self.path,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SlideshowConfig {
+    // may have {today}, {year}, {month} and {name}, resolved on each run, e.g. "D:/slides/{name}-{today}.sld"
+    pub path: PathBuf,
+    // the path as written when it has the placeholders, and then the path is the resolved one
+    #[serde(skip)]
+    pub path_template: Option<PathBuf>,
+    // in days, the outputs of the other days of path_template older than this are removed after generating
+    #[serde(default)]
+    pub retention_days: Option<u64>,
+    // for --only and --skip, the file stem of the path when omitted
+    #[serde(default)]
+    pub name: Option<String>,
+    // a disabled one is left out of the config as if it were not there
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    // of the screen, full hd when omitted
+    #[serde(default = "default_width")]
+    pub width: u32,
+    #[serde(default = "default_height")]
+    pub height: u32,
+    #[serde(flatten)]
+    pub filter: ImageFilter,
+    pub image_dirs: Vec<ImageDir>,
+    // read the json sidecars of Google Takeout for the dates, the descriptions and the locations
+    #[serde(default)]
+    pub takeout: bool,
+    // XnView.db of XnView MP, the images under image_dirs are read from it instead of walking them
+    #[serde(default)]
+    pub catalog: Option<PathBuf>,
+    #[serde(default = "default_true")]
+    pub skip_junk: bool,
+    // the junk of the config when omitted
+    #[serde(default)]
+    pub junk: Option<JunkRules>,
+    #[serde(default)]
+    pub include_hidden: bool,
+    // symlinked dirs are skipped unless this
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    #[serde(default)]
+    pub include_videos: bool,
+    // cr2, nef, arw, raf and so on, sized from the exif or the embedded preview instead of decoded
+    #[serde(default)]
+    pub include_raw: bool,
+    // the images in the zip files under the image dirs, listed as e.g. "/photos/2012-trip.zip/DSC_0001.jpg"
+    #[serde(default)]
+    pub include_archives: bool,
+    // the matched images in the zip files are unpacked to the cache and listed there, as the viewers can't open them in the archives
+    #[serde(default)]
+    pub unpack_archives: bool,
+    // same as raw_pairing = "prefer_jpeg"
+    #[serde(default)]
+    pub prefer_sibling_jpeg: bool,
+    #[serde(default)]
+    pub raw_pairing: RawPairing,
+    // "still" or "motion" lists only one of a Live Photo, with include_videos
+    #[serde(default)]
+    pub live_photos: LivePhotoPairing,
+    // matched against the whole path, e.g. "**/thumbnails" or "*_edited.*"
+    #[serde(default)]
+    pub exclude_globs: Vec<String>,
+    #[serde(default)]
+    pub include_globs: Vec<String>,
+    // paths or globs matched against the whole path, e.g. the favorites shown regardless of the filters, the thinning
+    // and the sampling, and the ones never shown, though the walk options still apply, e.g. exclude_globs
+    #[serde(default)]
+    pub always_include: Vec<String>,
+    #[serde(default)]
+    pub never_include: Vec<String>,
+    // compared case-insensitively without the dot, before guessing the type by the extension, e.g. ["svg", "ico"] out,
+    // and the listed ones are taken as images even when unknown to the guess, empty means all
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub excluded_extensions: Vec<String>,
+    // checked in the walk before parsing, e.g. to skip the thumbnails and the huge tiff scans
+    #[serde(default)]
+    pub min_file_size: Option<FileSize>,
+    #[serde(default)]
+    pub max_file_size: Option<FileSize>,
+    // dirs read at once, raise it for network shares
+    #[serde(default = "default_walk_concurrency")]
+    pub walk_concurrency: usize,
+    #[serde(default)]
+    pub output_format: OutputFormat,
+    // keep the previous output as "<path>.bak" when replacing it
+    #[serde(default)]
+    pub backup: bool,
+    // relative to the dir of the output, e.g. for a slideshow on a usb stick along with the images
+    #[serde(default)]
+    pub relative_paths: bool,
+    // the images are center-cropped to the screen into jpegs here and the copies are listed instead,
+    // so that looser aspect ratios still fill the screen, videos are listed as they are
+    #[serde(default)]
+    pub export_dir: Option<PathBuf>,
+    // previews of the matched images are made in thumbnail_dir, so that the first show on a slow nas starts at once,
+    // not with --fast
+    #[serde(default)]
+    pub thumbnails: bool,
+    // one output per bucket instead of the path, named by split_path_template
+    #[serde(default)]
+    pub split_by: Option<SplitBy>,
+    // e.g. "{name}-{year}-{month}.sld", with name, ext, year, month and quarter
+    #[serde(default)]
+    pub split_path_template: Option<String>,
+    // title slides between the date-grouped sections, e.g. { title = "{season} {year}", font = "C:/Windows/Fonts/arial.ttf" }
+    #[serde(default)]
+    pub chapters: Option<ChapterConfig>,
+    // one output per monitor instead of the path, with the images split between them
+    #[serde(default)]
+    pub monitors: Vec<Monitor>,
+    // for ffconcat, the crossfade is only in the rendered video
+    #[serde(default = "default_slide_duration_secs")]
+    pub slide_duration_secs: f64,
+    // per-image durations, e.g. longer for the panoramas, the timer of the header or slide_duration_secs otherwise
+    #[serde(default)]
+    pub durations: Vec<DurationRule>,
+    // per-image effect and stretch of the sld, e.g. no crop for the panoramas
+    #[serde(default)]
+    pub entry_rules: Vec<EntryRule>,
+    #[serde(default)]
+    pub crossfade_secs: Option<f64>,
+    #[serde(default)]
+    pub video_path: Option<PathBuf>,
+    // only for sld, m3u8 is always utf-8
+    #[serde(default)]
+    pub encoding: OutputEncoding,
+    // of sld and m3u8, for the paths the encoding can't hold
+    #[serde(default)]
+    pub unencodable_paths: UnencodablePaths,
+    #[serde(default)]
+    pub header: SlideshowHeader,
+    // per-image info, e.g. "{date} {folder} {camera}", with date, time, year, filename, folder, camera, lens,
+    // rating, keywords and description, instead of the info of the header with XnView's own placeholders
+    #[serde(default)]
+    pub info_template: Option<String>,
+    // same as info_template = "{description}", the captions of the xmp, the iptc or the takeout
+    #[serde(default)]
+    pub captions: bool,
+    // shorthands of the ones in the header, win over them when given
+    #[serde(default)]
+    pub background_color: Option<Color>,
+    #[serde(default)]
+    pub text_color: Option<Color>,
+    #[serde(default, alias = "dedup")]
+    pub dedupe_similar: bool,
+    #[serde(default = "default_hamming_threshold")]
+    pub hamming_threshold: u32,
+    // only the images taken within this many seconds of each other are compared, e.g. bursts
+    #[serde(default)]
+    pub dedupe_time_window_secs: Option<u64>,
+    // a cheaper way for the bursts than dedupe_similar, only by the creation dates
+    #[serde(default)]
+    pub min_seconds_between_shots: Option<u64>,
+    // skip the partially copied images, which XnView shows as gray frames, checked once and cached
+    #[serde(default)]
+    pub verify_decodable: bool,
+    // of the creation dates, e.g. 20 a day for the trips of thousands of frames
+    #[serde(default)]
+    pub max_per_day: Option<usize>,
+    #[serde(default)]
+    pub max_per_month: Option<usize>,
+    // stem suffixes of the edited ones and the copies, e.g. ["-edited", "_v\\d+", " \\(\\d+\\)"], only one of each shot is kept
+    #[serde(default)]
+    pub variant_suffixes: Vec<String>,
+    #[serde(default)]
+    pub prefer_variant: PreferVariant,
+    // max number of images to pick from the matched ones, also accepted as max_images
+    #[serde(default, alias = "max_images")]
+    pub sample: Option<usize>,
+    // also accepted as sampling
+    #[serde(default, alias = "sampling")]
+    pub sample_strategy: SampleStrategy,
+    // the ratio of the recent photos to the archive, sampled with sample_strategy
+    #[serde(default)]
+    pub freshness: Option<Freshness>,
+    // also accepted as order, e.g. order = "shuffle"
+    #[serde(default, alias = "order")]
+    pub sort: SortOrder,
+    // same as sort = "random"
+    #[serde(default)]
+    pub shuffle: bool,
+    // of sort = "path" and the ties of the others, e.g. "natural" for IMG_9.jpg before IMG_10.jpg
+    #[serde(default)]
+    pub path_collation: PathCollation,
+    // for path_collation = "locale", e.g. "ja", the root collation of unicode when omitted
+    #[serde(default)]
+    pub collation_locale: Option<String>,
+    // for sort = "interleave_events", a longer gap between photos starts a new event
+    #[serde(default = "default_event_gap_hours")]
+    pub event_gap_hours: u64,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    // dates in the file name or the dir names, e.g. "IMG_20190714_183000.jpg" or "2004-08-Summer/"
+    #[serde(default)]
+    pub path_dates: bool,
+    // regexes with the named groups year, month, and optionally day, hour, minute and second
+    #[serde(default)]
+    pub path_date_patterns: Option<Vec<String>>,
+    #[serde(default)]
+    pub path_date_outranks_file_times: bool,
+    // only these sources in this order, e.g. ["exif", "filename", "mtime"], where "exif" covers the track dates of videos
+    #[serde(default)]
+    pub date_sources: Option<Vec<DateSource>>,
+    #[serde(default)]
+    pub date_pick: DatePick,
+    // the matched images whose exif none of the parsers could read, and without an xmp date, one path per line
+    #[serde(default)]
+    pub exif_failure_report: Option<PathBuf>,
+    // e.g. "Asia/Tokyo", the calendar day of each image is of this timezone instead of where it was taken
+    #[serde(default)]
+    pub timezone: Option<String>,
+    // regenerated by the daemon on this, an interval like "6h" or a cron expression like "0 0 3 * * *"
+    #[serde(default)]
+    pub schedule: Option<String>,
+    // shell commands run before scanning and after writing, see run_hook for the environment
+    #[serde(default)]
+    pub pre_command: Option<String>,
+    #[serde(default)]
+    pub post_command: Option<String>,
+}
+
+pub fn expand_path(path: &Path, base_dir: Option<&Path>) -> Result<PathBuf> {
+    let path = path.to_string_lossy();
+    // $$ for a literal $, e.g. "D:/$$RECYCLE.BIN"
+    let env_var_pattern = Regex::new(r"\$(?:\$|\{(\w+)\}|(\w+))").expect("valid regex");
+    let mut unknown_var = None;
+    let path = env_var_pattern.replace_all(&path, |captures: &Captures| {
+        let Some(name) = captures.get(1).or(captures.get(2)).map(|name| name.as_str()) else {
+            return "$".to_string();
+        };
+        std::env::var(name).unwrap_or_else(|_| {
+            unknown_var = Some(name.to_string());
+            String::new()
+        })
+    });
+    if let Some(unknown_var) = unknown_var {
+        return Err(Error::EnvVarError(unknown_var).into());
+    }
+    let path = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') || rest.starts_with('\\') => {
+            let home_dir = dirs::home_dir().ok_or(Error::HomeDirError)?;
+            home_dir.join(rest.trim_start_matches(['/', '\\']))
+        }
+        _ => PathBuf::from(path.into_owned()),
+    };
+    match base_dir {
+        Some(base_dir) if path.is_relative() => Ok(base_dir.join(path)),
+        _ => Ok(path),
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_event_gap_hours() -> u64 {
+    6
+}
+
+fn default_thumbnail_size() -> u32 {
+    256
+}
+
+fn default_width() -> u32 {
+    1920
+}
+
+fn default_height() -> u32 {
+    1080
+}
+
+fn default_walk_concurrency() -> usize {
+    8
+}
+
+fn default_slide_duration_secs() -> f64 {
+    5.0
+}
+
+fn default_hamming_threshold() -> u32 {
+    5
+}
+
+impl Config {
+    // by the extension, toml, yaml or yml, and json otherwise
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let extension = path.extension().map(|extension| extension.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+        let mut config: Config = match extension.as_str() {
+            "toml" => toml::from_str(&text)?,
+            "yaml" | "yml" => serde_yaml::from_str(&text)?,
+            _ => serde_json::from_str(&text)?,
+        };
+        config.expand_paths(path.parent())?;
+        config.apply_junk_rules();
+        Ok(config)
+    }
+
+    // ~, $VAR and ${VAR} are expanded, and relative paths are of base_dir, e.g. the dir of the config file
+    pub fn expand_paths(&mut self, base_dir: Option<&Path>) -> Result<()> {
+        if let Some(thumbnail_dir) = &mut self.thumbnail_dir {
+            *thumbnail_dir = expand_path(thumbnail_dir, base_dir)?;
+        }
+        if let Some(face_model) = &mut self.face_model {
+            *face_model = expand_path(face_model, base_dir)?;
+        }
+        for slideshow in &mut self.slideshows {
+            slideshow.path = expand_path(&slideshow.path, base_dir)?;
+            // the urls and the mapped paths of the remote ones are as written
+            for image_dir in slideshow.image_dirs.iter_mut().filter(|image_dir| !image_dir.is_remote()) {
+                image_dir.path = expand_path(&image_dir.path, base_dir)?;
+            }
+            if let Some(catalog) = &mut slideshow.catalog {
+                *catalog = expand_path(catalog, base_dir)?;
+            }
+            if let Some(video_path) = &mut slideshow.video_path {
+                *video_path = expand_path(video_path, base_dir)?;
+            }
+            if let Some(export_dir) = &mut slideshow.export_dir {
+                *export_dir = expand_path(export_dir, base_dir)?;
+            }
+            if let Some(exif_failure_report) = &mut slideshow.exif_failure_report {
+                *exif_failure_report = expand_path(exif_failure_report, base_dir)?;
+            }
+            if let Some(chapters) = &mut slideshow.chapters {
+                chapters.font = expand_path(&chapters.font, base_dir)?;
+            }
+            for monitor in &mut slideshow.monitors {
+                monitor.path = expand_path(&monitor.path, base_dir)?;
+            }
+        }
+        Ok(())
+    }
+
+    // of the day, e.g. again on each run of the daemon
+    pub fn resolve_paths(&mut self, today: NaiveDate) {
+        for slideshow in &mut self.slideshows {
+            if slideshow.path_template.is_none() && has_placeholders(&slideshow.path) {
+                slideshow.path_template = Some(slideshow.path.clone());
+            }
+            if let Some(path_template) = &slideshow.path_template {
+                slideshow.path = resolve_path_template(path_template, slideshow.name.as_deref().unwrap_or_default(), today);
+            }
+        }
+    }
+
+    // the slideshows without their own junk take the one of the config
+    pub fn apply_junk_rules(&mut self) {
+        for slideshow in self.slideshows.iter_mut().filter(|slideshow| slideshow.junk.is_none()) {
+            slideshow.junk = Some(self.junk.clone());
+        }
+    }
+
+    pub fn is_exclusive(&self, slideshow: &SlideshowConfig) -> bool {
+        let name = slideshow.name();
+        self.exclusive || self.exclusive_groups.iter().any(|group| group.contains(&name))
+    }
+
+    // the two never share an image
+    pub fn are_rivals(&self, name: &str, other_name: &str) -> bool {
+        self.exclusive || self.exclusive_groups.iter().any(|group| group.iter().any(|member| member == name) && group.iter().any(|member| member == other_name))
+    }
+
+    // the indices of the slideshows in the config order, except that the earlier ones of each exclusive group come first,
+    // none when the groups contradict each other
+    pub fn generation_order(&self) -> Option<Vec<usize>> {
+        let names: Vec<String> = self.slideshows.iter().map(|slideshow| slideshow.name()).collect();
+        let index_of = |name: &String| names.iter().position(|other_name| other_name == name);
+        // the ones each has to wait for
+        let mut waits: Vec<HashSet<usize>> = vec![HashSet::new(); names.len()];
+        for group in &self.exclusive_groups {
+            let indices: Vec<usize> = group.iter().filter_map(index_of).collect();
+            for (i, later) in indices.iter().enumerate() {
+                waits[*later].extend(indices[..i].iter().copied().filter(|earlier| earlier != later));
+            }
+        }
+        let mut order = Vec::new();
+        while order.len() < names.len() {
+            let next = (0..names.len()).find(|i| !order.contains(i) && waits[*i].iter().all(|earlier| order.contains(earlier)))?;
+            order.push(next);
+        }
+        Some(order)
+    }
+
+    // of all the slideshows at once, prefixed with their paths
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        let mut paths = HashSet::new();
+        let mut names = HashSet::new();
+        for slideshow in &self.slideshows {
+            if !paths.insert(&slideshow.path) {
+                problems.push(format!("{}: written by another slideshow too", slideshow.path.display()));
+            }
+            if !names.insert(slideshow.name()) {
+                problems.push(format!("{}: named {} the same as another slideshow", slideshow.path.display(), slideshow.name()));
+            }
+            problems.extend(slideshow.problems().into_iter().map(|problem| format!("{}: {}", slideshow.path.display(), problem)));
+            if slideshow.filter.needs_faces() {
+                if !cfg!(feature = "faces") {
+                    problems.push(format!("{}: contains_faces and faces of the filter need the build with the faces feature", slideshow.path.display()));
+                } else if self.face_model.is_none() {
+                    problems.push(format!("{}: contains_faces and faces of the filter need face_model", slideshow.path.display()));
+                }
+            }
+        }
+        if let Some(face_model) = &self.face_model {
+            if !face_model.is_file() {
+                problems.push(format!("face model not found: {}", face_model.display()));
+            }
+        }
+        for notifier in &self.notifiers {
+            problems.extend(notifier.problems());
+        }
+        for group in &self.exclusive_groups {
+            for name in group {
+                if !names.contains(name) {
+                    problems.push(format!("no slideshow of the name in exclusive_groups: {}", name));
+                }
+            }
+        }
+        if self.generation_order().is_none() {
+            problems.push("exclusive_groups order the slideshows in a cycle".to_string());
+        }
+        if self.memory_budget_mb == Some(0) {
+            problems.push("memory_budget_mb is 0".to_string());
+        }
+        problems
+    }
+}
+
+impl SlideshowConfig {
+    pub fn image_dir_paths(&self) -> Vec<PathBuf> {
+        self.image_dirs.iter().map(|image_dir| image_dir.local_path().to_path_buf()).collect()
+    }
+
+    // either dir under the other, so that they may find the same images
+    pub fn shares_dirs_with(&self, other: &SlideshowConfig) -> bool {
+        let other_dir_paths = other.image_dir_paths();
+        self.image_dir_paths().iter().any(|dir_path| other_dir_paths.iter().any(|other_dir_path| dir_path.starts_with(other_dir_path) || other_dir_path.starts_with(dir_path)))
+    }
+
+    pub fn dir_filters(&self) -> Result<DirFilters> {
+        let dir_overrides: Vec<(PathBuf, FilterOverrides)> = self.image_dirs.iter().map(|image_dir| (image_dir.local_path().to_path_buf(), image_dir.filter_overrides.clone())).collect();
+        let dir_filters = DirFilters::new(self.filter.clone(), &dir_overrides)
+            .with_pins(build_glob_set(&self.always_include)?, build_glob_set(&self.never_include)?);
+        Ok(dir_filters)
+    }
+
+    // none unless any of the dirs has a weight
+    pub fn image_dir_weights(&self) -> Option<Vec<(PathBuf, f64)>> {
+        if self.image_dirs.iter().all(|image_dir| image_dir.weight.is_none()) {
+            return None;
+        }
+        Some(self.image_dirs.iter().map(|image_dir| (image_dir.local_path().to_path_buf(), image_dir.weight.unwrap_or(1.0))).collect())
+    }
+
+    // the ones which would make an empty or no output
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = self.filter.problems();
+        if self.image_dirs.is_empty() {
+            problems.push("no image_dirs".to_string());
+        }
+        for image_dir in &self.image_dirs {
+            if image_dir.is_remote() {
+                if let Err(e) = check_remote_url(&image_dir.path.to_string_lossy()) {
+                    problems.push(e.to_string());
+                }
+                if image_dir.mapped_path.is_none() {
+                    problems.push(format!("remote image dir needs mapped_path: {}", image_dir.path.display()));
+                }
+                // only the heads of the files are fetched
+                if self.analysis_options().needs_decode() {
+                    problems.push(format!("dedupe_similar, quality filters, contains_faces, is_monochrome and verify_decodable need whole files, not of remote image dirs: {}", image_dir.path.display()));
+                }
+            } else if !image_dir.path.is_dir() {
+                problems.push(format!("image dir not found: {}", image_dir.path.display()));
+            }
+            if image_dir.weight.is_some_and(|weight| weight < 0.0) {
+                problems.push(format!("weight of image dir is negative: {}", image_dir.path.display()));
+            }
+            if !image_dir.filter_overrides.is_empty() {
+                let dir_filter = self.filter.with_overrides(&image_dir.filter_overrides);
+                problems.extend(dir_filter.problems().into_iter().map(|problem| format!("{} in {}", problem, image_dir.path.display())));
+            }
+        }
+        if self.unpack_archives && !self.include_archives {
+            problems.push("unpack_archives without include_archives".to_string());
+        }
+        if self.include_archives && !self.unpack_archives && (self.export_dir.is_some() || self.thumbnails) {
+            problems.push("export_dir and thumbnails need unpack_archives for the images in the archives".to_string());
+        }
+        if let Some(freshness) = &self.freshness {
+            problems.extend(freshness.problems());
+            if self.image_dir_weights().is_some() {
+                problems.push("freshness and the weights of image dirs can't be combined".to_string());
+            }
+        }
+        if let Some(catalog) = &self.catalog {
+            if !catalog.is_file() {
+                problems.push(format!("catalog not found: {}", catalog.display()));
+            }
+        }
+        // the temp file is written next to the output
+        let output_dir = self.path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        match std::fs::metadata(output_dir) {
+            Ok(metadata) if !metadata.is_dir() => problems.push(format!("output dir is not a dir: {}", output_dir.display())),
+            Ok(metadata) if metadata.permissions().readonly() => problems.push(format!("output dir is read-only: {}", output_dir.display())),
+            Ok(_) => {}
+            Err(_) => problems.push(format!("output dir not found: {}", output_dir.display())),
+        }
+        if let (Some(min_file_size), Some(max_file_size)) = (self.min_file_size, self.max_file_size) {
+            if min_file_size > max_file_size {
+                problems.push(format!("min_file_size {} is greater than max_file_size {}", min_file_size.0, max_file_size.0));
+            }
+        }
+        if let Err(e) = WalkOptions::from_slideshow(self) {
+            problems.push(format!("{:#}", e));
+        }
+        if let Err(e) = self.dir_filters() {
+            problems.push(format!("{:#}", e));
+        }
+        if let Err(e) = DateOptions::from_slideshow(self) {
+            problems.push(format!("{:#}", e));
+        }
+        if let Err(e) = self.path_comparator() {
+            problems.push(format!("{:#}", e));
+        }
+        for duration_rule in &self.durations {
+            problems.extend(duration_rule.problems());
+        }
+        for entry_rule in &self.entry_rules {
+            problems.extend(entry_rule.problems());
+        }
+        if !self.variant_suffixes.is_empty() {
+            if let Err(e) = variant_suffix_regex(&self.variant_suffixes) {
+                problems.push(format!("{:#}", e));
+            }
+        }
+        if let Some(Err(e)) = self.schedule.as_deref().map(Schedule::parse) {
+            problems.push(format!("{:#}", e));
+        }
+        for monitor in &self.monitors {
+            problems.extend(monitor.problems());
+        }
+        if let Some(chapters) = &self.chapters {
+            problems.extend(chapters.problems());
+            if !matches!(self.sort_order(), SortOrder::CreationDateAsc | SortOrder::CreationDateDesc) {
+                problems.push("chapters need sort by the creation date".to_string());
+            }
+        }
+        if self.filter.needs_saturation() && !cfg!(feature = "monochrome") {
+            problems.push("is_monochrome and saturation of the filter need the build with the monochrome feature".to_string());
+        }
+        match &self.path_template {
+            Some(path_template) => {
+                if self.name.is_none() && path_template.to_string_lossy().contains("{name}") {
+                    problems.push("{name} of the path needs name".to_string());
+                }
+                if self.retention_days.is_some() && path_template.parent().is_some_and(has_placeholders) {
+                    problems.push("retention_days needs the placeholders only in the file name of the path".to_string());
+                }
+            }
+            None if self.retention_days.is_some() => problems.push("retention_days needs a path with placeholders like {today}".to_string()),
+            None => {}
+        }
+        if self.split_by.is_some() && !self.monitors.is_empty() {
+            problems.push("split_by and monitors are exclusive".to_string());
+        }
+        problems
+    }
+
+    // whether all the matched images are needed before writing any of them
+    pub fn needs_all_images(&self) -> bool {
+        self.dedupe_similar || self.min_seconds_between_shots.is_some() || self.max_per_day.is_some() || self.max_per_month.is_some() || !self.variant_suffixes.is_empty() || self.sample.is_some() || matches!(self.sort_order(), SortOrder::Random | SortOrder::InterleaveEvents) || self.is_split() || self.chapters.is_some() || self.image_dir_weights().is_some() || self.freshness.is_some()
+    }
+
+    // written into several outputs instead of the path
+    pub fn is_split(&self) -> bool {
+        self.split_by.is_some() || !self.monitors.is_empty()
+    }
+
+    pub fn header(&self) -> SlideshowHeader {
+        let mut header = self.header.clone();
+        if let Some(background_color) = self.background_color {
+            header.background_color = background_color;
+        }
+        if let Some(text_color) = self.text_color {
+            header.text_color = text_color;
+        }
+        // already shuffled or interleaved, so XnView must keep the order
+        if matches!(self.sort_order(), SortOrder::Random | SortOrder::InterleaveEvents) {
+            header.random_order = false;
+        }
+        header
+    }
+
+    // e.g. for the stats of dirs outside the config, the others are the defaults of the config file
+    pub fn from_image_dirs(image_dirs: &[PathBuf]) -> Result<Self> {
+        let slideshow = serde_json::from_value(serde_json::json!({
+            "path": "",
+            "image_dirs": image_dirs,
+        }))?;
+        Ok(slideshow)
+    }
+
+    // of the template rather than the path of the day, so that it stays the same
+    pub fn name(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => self.path_template.as_ref().unwrap_or(&self.path).file_stem().map(|file_stem| file_stem.to_string_lossy().to_string()).unwrap_or_default(),
+        }
+    }
+
+    pub fn info_template(&self) -> Option<String> {
+        match &self.info_template {
+            Some(info_template) => Some(info_template.clone()),
+            None => self.captions.then(|| "{description}".to_string()),
+        }
+    }
+
+    pub fn raw_pairing(&self) -> RawPairing {
+        if self.prefer_sibling_jpeg { RawPairing::PreferJpeg } else { self.raw_pairing }
+    }
+
+    pub fn sort_order(&self) -> SortOrder {
+        if self.shuffle { SortOrder::Random } else { self.sort }
+    }
+
+    pub fn path_comparator(&self) -> Result<PathComparator> {
+        PathComparator::new(self.path_collation, self.collation_locale.as_deref())
+    }
+
+    pub fn rng(&self) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
+    }
+
+    pub fn analysis_options(&self) -> AnalysisOptions {
+        AnalysisOptions {
+            dhash: self.dedupe_similar,
+            quality: self.filter.needs_quality(),
+            faces: self.filter.needs_faces(),
+            saturation: self.filter.needs_saturation(),
+            verify: self.verify_decodable,
+        }
+    }
+
+    pub fn scan_options(&self, n_threads: usize, cache_options: &CacheOptions) -> Result<ScanOptions> {
+        Ok(ScanOptions {
+            n_threads,
+            cache_options: cache_options.with_image_dirs(self.image_dir_paths()),
+            analysis_options: self.analysis_options(),
+            walk_options: WalkOptions::from_slideshow(self)?,
+            date_options: DateOptions::from_slideshow(self)?,
+            max_inflight_bytes: None,
+            skip_paths: Arc::new(HashSet::new()),
+            stats: Arc::new(ScanStats::default()),
+            catalog: None,
+            takeout: self.takeout,
+            rate_limiter: None,
+            strict: false,
+            remote_mirrors: vec![],
+        })
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            slideshows: vec![],
+            cache_ttl: None,
+            exclusive: false,
+            exclusive_groups: vec![],
+            cache_key: CacheKey::default(),
+            parse_concurrency: None,
+            parallel_slideshows: true,
+            max_files_per_sec: None,
+            max_bytes_per_sec: None,
+            strict: false,
+            schedule_jitter_secs: 0,
+            thumbnail_dir: None,
+            thumbnail_size: default_thumbnail_size(),
+            face_model: None,
+            memory_budget_mb: None,
+            screensaver_path: None,
+            junk: JunkRules::default(),
+            notifiers: vec![],
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::{fs::File, io::BufWriter, path::{Path, PathBuf}};
+use image::{DynamicImage, ImageFormat, codecs::jpeg::JpegEncoder, imageops::FilterType, metadata::Orientation};
+use tokio::task;
+use anyhow::Result;
+use crate::{cache::cache_parent_dir, image_info::ImageInfo};
+
+const JPEG_QUALITY: u8 = 90;
+
+// a display-ready copy filling the screen, center-cropped to its aspect ratio and shrunk to fit it,
+// named by the source and the size, so that an unchanged image is exported once
+pub async fn cropped_copy(image_info: &ImageInfo, export_dir: &Path, width: u32, height: u32) -> Result<PathBuf> {
+    let key = format!("{}\0{:?}\0{:?}\0{}x{}", image_info.path.display(), image_info.source_modified, image_info.source_size, width, height);
+    let stem = image_info.path.file_stem().map(|file_stem| file_stem.to_string_lossy().to_string()).unwrap_or_default();
+    // the stem is kept for the info of XnView, e.g. {Filename}
+    let path = export_dir.join(format!("{}-{:x}.jpg", stem, md5::compute(key.as_bytes())));
+    if path.exists() {
+        return Ok(path);
+    }
+    tokio::fs::create_dir_all(export_dir).await?;
+    let (source_path, orientation, copy_path) = (image_info.path.clone(), image_info.orientation, path.clone());
+    task::spawn_blocking(move || write_cropped_copy(&source_path, orientation, width, height, &copy_path)).await??;
+    Ok(path)
+}
+
+// a png of the first frame of an animated image, in the cache dir, named the same way as the cropped copies
+pub async fn first_frame_copy(image_info: &ImageInfo) -> Result<PathBuf> {
+    let key = format!("{}\0{:?}\0{:?}", image_info.path.display(), image_info.source_modified, image_info.source_size);
+    let stem = image_info.path.file_stem().map(|file_stem| file_stem.to_string_lossy().to_string()).unwrap_or_default();
+    let first_frames_dir = cache_parent_dir().await?.join("first_frames");
+    let path = first_frames_dir.join(format!("{}-{:x}.png", stem, md5::compute(key.as_bytes())));
+    if path.exists() {
+        return Ok(path);
+    }
+    tokio::fs::create_dir_all(&first_frames_dir).await?;
+    let (source_path, copy_path) = (image_info.path.clone(), path.clone());
+    task::spawn_blocking(move || -> Result<()> {
+        // only the first frame is decoded
+        let img = image::open(&source_path)?;
+        let temp_path = copy_path.with_extension("png.tmp");
+        img.save_with_format(&temp_path, ImageFormat::Png)?;
+        std::fs::rename(&temp_path, &copy_path)?;
+        Ok(())
+    }).await??;
+    Ok(path)
+}
+
+fn write_cropped_copy(source_path: &Path, orientation: Option<u16>, width: u32, height: u32, path: &Path) -> Result<()> {
+    let mut img = image::open(source_path)?;
+    if let Some(orientation) = orientation.and_then(|orientation| Orientation::from_exif(orientation as u8)) {
+        img.apply_orientation(orientation);
+    }
+    let img = crop_to_fill(img, width, height);
+    // written next to it and renamed, so that an interrupted run leaves no broken copy
+    let temp_path = path.with_extension("jpg.tmp");
+    let mut writer = BufWriter::new(File::create(&temp_path)?);
+    img.to_rgb8().write_with_encoder(JpegEncoder::new_with_quality(&mut writer, JPEG_QUALITY))?;
+    drop(writer);
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+// never upscaled, the smaller ones are only cropped
+fn crop_to_fill(img: DynamicImage, width: u32, height: u32) -> DynamicImage {
+    if img.width() >= width && img.height() >= height {
+        return img.resize_to_fill(width, height, FilterType::Lanczos3);
+    }
+    let target_aspect_ratio = width as f64 / height as f64;
+    let (crop_width, crop_height) = if (img.width() as f64 / img.height() as f64) > target_aspect_ratio {
+        ((img.height() as f64 * target_aspect_ratio).round() as u32, img.height())
+    } else {
+        (img.width(), (img.width() as f64 / target_aspect_ratio).round() as u32)
+    };
+    let x = (img.width() - crop_width) / 2;
+    let y = (img.height() - crop_height) / 2;
+    img.crop_imm(x, y, crop_width, crop_height)
+}
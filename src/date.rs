@@ -0,0 +1,130 @@
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use chrono::{NaiveDate, NaiveDateTime, Offset, TimeZone};
+use chrono_tz::Tz;
+use regex::{Captures, Regex};
+use anyhow::Result;
+use crate::{Error, config::SlideshowConfig, image_info::{DateSource, DateTimeCandidate, ImageInfo}};
+
+// e.g. "IMG_20190714_183000.jpg", "2019-07-14 18.30.00.jpg" and "2004-08-Summer/"
+const DEFAULT_PATH_DATE_PATTERNS: [&str; 2] = [
+    r"(?:^|[^0-9])(?P<year>(?:19|20)[0-9]{2})[-_.]?(?P<month>[01][0-9])[-_.]?(?P<day>[0-3][0-9])(?:[-_ T.]?(?P<hour>[0-2][0-9])[-_.:]?(?P<minute>[0-5][0-9])[-_.:]?(?P<second>[0-5][0-9]))?(?:[^0-9]|$)",
+    r"(?:^|[^0-9])(?P<year>(?:19|20)[0-9]{2})[-_.](?P<month>[01][0-9])(?:[^0-9]|$)",
+];
+
+// how the creation date is chosen from the candidates
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DatePick {
+    // the oldest, as copies and edits only make the file times newer
+    #[default]
+    Min,
+    Max,
+    // the oldest of the first source in the priority which has any
+    First,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DateOptions {
+    // empty means the path is not looked at
+    pub path_date_patterns: Vec<Regex>,
+    // the file system times are ignored when the path has a date
+    pub path_date_outranks_file_times: bool,
+    // none means all the sources
+    pub date_sources: Option<Vec<DateSource>>,
+    pub date_pick: DatePick,
+    // the dates with a known offset are moved to this timezone, instead of where they were taken
+    pub timezone: Option<Tz>,
+}
+
+impl DateOptions {
+    pub fn from_slideshow(slideshow: &SlideshowConfig) -> Result<Self> {
+        let path_date_patterns = if !slideshow.path_dates {
+            vec![]
+        } else if let Some(path_date_patterns) = &slideshow.path_date_patterns {
+            path_date_patterns.iter().map(|pattern| Regex::new(pattern)).collect::<Result<_, _>>()?
+        } else {
+            DEFAULT_PATH_DATE_PATTERNS.iter().map(|pattern| Regex::new(pattern).expect("valid pattern")).collect()
+        };
+        Ok(Self {
+            path_date_patterns,
+            path_date_outranks_file_times: slideshow.path_date_outranks_file_times,
+            date_sources: slideshow.date_sources.clone(),
+            date_pick: slideshow.date_pick,
+            timezone: slideshow.timezone.as_deref().map(|timezone| timezone.parse::<Tz>().map_err(|_| Error::TimezoneError(timezone.to_string()))).transpose()?,
+        })
+    }
+}
+
+// not cached, as the patterns are per slideshow, so this runs on every image info
+pub fn apply_date_options(image_info: &mut ImageInfo, date_options: &DateOptions) {
+    image_info.date_time_candidates.retain(|candidate| candidate.source != DateSource::Path);
+    if let Some(date_time) = path_date_time(&image_info.path, &date_options.path_date_patterns) {
+        image_info.date_time_candidates.push(DateTimeCandidate {
+            source: DateSource::Path,
+            date_time,
+            offset_secs: None,
+        });
+    }
+    if let Some(timezone) = date_options.timezone {
+        for candidate in &mut image_info.date_time_candidates {
+            if let Some(date_time) = candidate.to_fixed_offset() {
+                let date_time = timezone.from_utc_datetime(&date_time.naive_utc());
+                candidate.date_time = date_time.naive_local();
+                candidate.offset_secs = Some(date_time.offset().fix().local_minus_utc());
+            }
+        }
+    }
+    // takeout knows better than the exif rewritten by google, unless the sources are given explicitly
+    if date_options.date_sources.is_none() {
+        let takeout_date_time = image_info.date_time_candidates.iter().find(|candidate| candidate.source == DateSource::Takeout).map(|candidate| candidate.date_time);
+        if let Some(takeout_date_time) = takeout_date_time {
+            image_info.creation_date_time = takeout_date_time;
+            return;
+        }
+    }
+    let has_path_date = image_info.date_time_candidates.iter().any(|candidate| candidate.source == DateSource::Path);
+    let candidates: Vec<&DateTimeCandidate> = image_info.date_time_candidates.iter()
+        .filter(|candidate| {
+            let is_file_time = matches!(candidate.source, DateSource::Ctime | DateSource::Mtime);
+            !(date_options.path_date_outranks_file_times && has_path_date && is_file_time)
+        })
+        .collect();
+    // the priority of each candidate, none when its source is not listed
+    let priority = |candidate: &DateTimeCandidate| match &date_options.date_sources {
+        None => Some(0),
+        Some(date_sources) => date_sources.iter().position(|source| {
+            *source == candidate.source || (*source == DateSource::Exif && candidate.source == DateSource::Track)
+        }),
+    };
+    let prioritized = candidates.into_iter().filter_map(|candidate| priority(candidate).map(|priority| (priority, candidate.date_time)));
+    let creation_date_time = match date_options.date_pick {
+        DatePick::Min => prioritized.map(|(_, date_time)| date_time).min(),
+        DatePick::Max => prioritized.map(|(_, date_time)| date_time).max(),
+        DatePick::First => prioritized.min().map(|(_, date_time)| date_time),
+    };
+    // when none of the listed sources has any, the oldest of all is still better than nothing
+    if let Some(creation_date_time) = creation_date_time.or_else(|| image_info.date_time_candidates.iter().map(|candidate| candidate.date_time).min()) {
+        image_info.creation_date_time = creation_date_time;
+    }
+}
+
+// the file name first, then the dir names from the nearest
+fn path_date_time(path: &Path, patterns: &[Regex]) -> Option<NaiveDateTime> {
+    if patterns.is_empty() {
+        return None;
+    }
+    let file_stem = path.file_stem().map(|file_stem| file_stem.to_string_lossy());
+    let dir_names = path.ancestors().skip(1).filter_map(|dir| dir.file_name()).map(|dir_name| dir_name.to_string_lossy());
+    file_stem.into_iter().chain(dir_names).find_map(|name| {
+        patterns.iter().find_map(|pattern| pattern.captures(&name).and_then(|captures| date_time_from_captures(&captures)))
+    })
+}
+
+// missing day is the first of the month, and missing time is midnight
+fn date_time_from_captures(captures: &Captures) -> Option<NaiveDateTime> {
+    let number = |name: &str| captures.name(name).and_then(|value| value.as_str().parse::<u32>().ok());
+    let year = captures.name("year")?.as_str().parse::<i32>().ok()?;
+    let date = NaiveDate::from_ymd_opt(year, number("month")?, number("day").unwrap_or(1))?;
+    date.and_hms_opt(number("hour").unwrap_or(0), number("minute").unwrap_or(0), number("second").unwrap_or(0))
+}
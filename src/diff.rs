@@ -0,0 +1,41 @@
+use std::{collections::HashSet, path::{Path, PathBuf}};
+use anyhow::Result;
+use serde::Serialize;
+use crate::{m3u::read_m3u, slideshow::{OutputEncoding, read_slideshow, xnview_path}};
+
+// the images of a new output against an old one, in the order of each
+#[derive(Serialize, Debug, Default)]
+pub struct SlideshowDiff {
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+}
+
+impl SlideshowDiff {
+    // compared in the written form, so that e.g. the separators of the two don't matter
+    pub fn new(old_paths: &[PathBuf], new_paths: &[PathBuf]) -> Self {
+        let old_path_set: HashSet<String> = old_paths.iter().map(|path| xnview_path(path)).collect();
+        let new_path_set: HashSet<String> = new_paths.iter().map(|path| xnview_path(path)).collect();
+        Self {
+            added: new_paths.iter().filter(|path| !old_path_set.contains(&xnview_path(path))).cloned().collect(),
+            removed: old_paths.iter().filter(|path| !new_path_set.contains(&xnview_path(path))).cloned().collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+
+    // "+ path" and "- path" like a diff, the removed ones first
+    pub fn lines(&self) -> Vec<String> {
+        self.removed.iter().map(|path| format!("- {}", path.display()))
+            .chain(self.added.iter().map(|path| format!("+ {}", path.display())))
+            .collect()
+    }
+}
+
+// by the extension, an m3u8 or else an sld of the encoding
+pub async fn read_output_paths(path: &Path, encoding: OutputEncoding) -> Result<Vec<PathBuf>> {
+    let is_m3u = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("m3u8") || extension.eq_ignore_ascii_case("m3u"));
+    let existing_slideshow = if is_m3u { read_m3u(path).await? } else { read_slideshow(path, encoding).await? };
+    Ok(existing_slideshow.paths)
+}
@@ -0,0 +1,58 @@
+use serde::{Serialize, Deserialize};
+use crate::{filter::Orientation, image_info::ImageInfo};
+
+// how long an image is shown when it matches all the conditions given, the first matching rule wins,
+// e.g. min_aspect_ratio = 2.0 and secs = 6.0 for the panoramas
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DurationRule {
+    #[serde(default)]
+    pub orientation: Orientation,
+    #[serde(default)]
+    pub min_aspect_ratio: Option<f64>,
+    #[serde(default)]
+    pub max_aspect_ratio: Option<f64>,
+    // only videos when true, only images when false
+    #[serde(default)]
+    pub video: Option<bool>,
+    #[serde(default)]
+    pub secs: Option<f64>,
+    // the length of the video, secs is used for the images and the videos of unknown length
+    #[serde(default)]
+    pub full_length: bool,
+}
+
+impl DurationRule {
+    fn matches(&self, image_info: &ImageInfo) -> bool {
+        let aspect_ratio = image_info.aspect_ratio();
+        self.orientation.accepts(aspect_ratio)
+            && self.min_aspect_ratio.is_none_or(|min_aspect_ratio| aspect_ratio >= min_aspect_ratio)
+            && self.max_aspect_ratio.is_none_or(|max_aspect_ratio| aspect_ratio <= max_aspect_ratio)
+            && self.video.is_none_or(|video| video == image_info.is_video)
+    }
+
+    fn duration_secs(&self, image_info: &ImageInfo) -> Option<f64> {
+        let video_secs = image_info.duration_ms.filter(|_| self.full_length && image_info.is_video).map(|duration_ms| duration_ms as f64 / 1000.0);
+        video_secs.or(self.secs)
+    }
+
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.secs.is_none() && !self.full_length {
+            problems.push("duration rule has neither secs nor full_length".to_string());
+        }
+        if self.secs.is_some_and(|secs| secs <= 0.0) {
+            problems.push(format!("secs of duration rule is not positive: {}", self.secs.unwrap_or_default()));
+        }
+        if let (Some(min_aspect_ratio), Some(max_aspect_ratio)) = (self.min_aspect_ratio, self.max_aspect_ratio) {
+            if min_aspect_ratio > max_aspect_ratio {
+                problems.push(format!("min_aspect_ratio {} of duration rule is greater than max_aspect_ratio {}", min_aspect_ratio, max_aspect_ratio));
+            }
+        }
+        problems
+    }
+}
+
+// none when no rule matches, and the output falls back to its single duration
+pub fn display_duration_secs(rules: &[DurationRule], image_info: &ImageInfo) -> Option<f64> {
+    rules.iter().find(|rule| rule.matches(image_info)).and_then(|rule| rule.duration_secs(image_info))
+}
@@ -0,0 +1,56 @@
+use serde::{Serialize, Deserialize};
+use crate::{filter::Orientation, image_info::ImageInfo};
+
+// the per-image options of XnView's slideshow for the images matching all the conditions given, e.g.
+// min_aspect_ratio = 2.0 and stretch = 0 so that the panoramas are not cropped, where each option is of the first
+// matching rule which has it
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct EntryRule {
+    #[serde(default)]
+    pub orientation: Orientation,
+    #[serde(default)]
+    pub min_aspect_ratio: Option<f64>,
+    #[serde(default)]
+    pub max_aspect_ratio: Option<f64>,
+    // only videos when true, only images when false
+    #[serde(default)]
+    pub video: Option<bool>,
+    #[serde(default)]
+    pub keyword: Option<String>,
+    // the transition into the image, by the number of XnView's effect list
+    #[serde(default)]
+    pub effect: Option<u32>,
+    // the same as Stretch of the header
+    #[serde(default)]
+    pub stretch: Option<u32>,
+}
+
+impl EntryRule {
+    fn matches(&self, image_info: &ImageInfo) -> bool {
+        let aspect_ratio = image_info.aspect_ratio();
+        self.orientation.accepts(aspect_ratio)
+            && self.min_aspect_ratio.is_none_or(|min_aspect_ratio| aspect_ratio >= min_aspect_ratio)
+            && self.max_aspect_ratio.is_none_or(|max_aspect_ratio| aspect_ratio <= max_aspect_ratio)
+            && self.video.is_none_or(|video| video == image_info.is_video)
+            && self.keyword.as_ref().is_none_or(|keyword| image_info.keywords.iter().any(|image_keyword| image_keyword.eq_ignore_ascii_case(keyword)))
+    }
+
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.effect.is_none() && self.stretch.is_none() {
+            problems.push("entry rule has neither effect nor stretch".to_string());
+        }
+        if let (Some(min_aspect_ratio), Some(max_aspect_ratio)) = (self.min_aspect_ratio, self.max_aspect_ratio) {
+            if min_aspect_ratio > max_aspect_ratio {
+                problems.push(format!("min_aspect_ratio {} of entry rule is greater than max_aspect_ratio {}", min_aspect_ratio, max_aspect_ratio));
+            }
+        }
+        problems
+    }
+}
+
+// (effect, stretch), none for each when no rule gives it, so that the header's applies
+pub fn entry_options(rules: &[EntryRule], image_info: &ImageInfo) -> (Option<u32>, Option<u32>) {
+    let matching_rules = || rules.iter().filter(|rule| rule.matches(image_info));
+    (matching_rules().find_map(|rule| rule.effect), matching_rules().find_map(|rule| rule.stretch))
+}
@@ -0,0 +1,25 @@
+use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}};
+use crate::config::{Config, SlideshowConfig};
+
+// the images taken by the slideshows written so far in a run, by their names,
+// so that the later ones of the exclusive config or of an exclusive group skip them
+#[derive(Debug, Default)]
+pub struct Claims {
+    paths_by_slideshow: HashMap<String, HashSet<PathBuf>>,
+}
+
+impl Claims {
+    pub fn is_claimed(&self, config: &Config, slideshow: &SlideshowConfig, path: &Path) -> bool {
+        let name = slideshow.name();
+        self.paths_by_slideshow.iter()
+            .any(|(other_name, paths)| *other_name != name && config.are_rivals(other_name, &name) && paths.contains(path))
+    }
+
+    // nothing is kept for the slideshows without rivals
+    pub fn claim(&mut self, config: &Config, slideshow: &SlideshowConfig, paths: impl IntoIterator<Item = PathBuf>) {
+        if !config.is_exclusive(slideshow) {
+            return;
+        }
+        self.paths_by_slideshow.entry(slideshow.name()).or_default().extend(paths);
+    }
+}
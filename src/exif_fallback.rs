@@ -0,0 +1,39 @@
+use std::{fs::File, io::BufReader, path::Path};
+use chrono::NaiveDateTime;
+use exif::{In, Reader, Tag, Value};
+use anyhow::Result;
+
+// what the second parser could read of the exif nom_exif failed on
+#[derive(Debug, Default)]
+pub struct FallbackExif {
+    pub date_times: Vec<NaiveDateTime>,
+    pub orientation: Option<u16>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens_model: Option<String>,
+}
+
+// by kamadak-exif, which is stricter about the containers but tolerates other broken entries,
+// blocking, so run it in spawn_blocking
+pub fn read_fallback_exif(path: &Path) -> Result<FallbackExif> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let exif = Reader::new().read_from_container(&mut reader)?;
+    let text = |tag: Tag| exif.get_field(tag, In::PRIMARY).and_then(|field| match &field.value {
+        Value::Ascii(values) => values.first().map(|value| String::from_utf8_lossy(value).trim_matches(|c: char| c.is_whitespace() || c == '\0').to_string()),
+        _ => None,
+    }).filter(|text| !text.is_empty());
+    let date_times = [Tag::DateTimeOriginal, Tag::DateTimeDigitized, Tag::DateTime].into_iter()
+        .filter_map(|tag| text(tag))
+        .filter_map(|text| NaiveDateTime::parse_from_str(&text, "%Y:%m:%d %H:%M:%S").ok())
+        .collect();
+    let orientation = exif.get_field(Tag::Orientation, In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        .map(|orientation| orientation as u16);
+    Ok(FallbackExif {
+        date_times,
+        orientation,
+        camera_make: text(Tag::Make),
+        camera_model: text(Tag::Model),
+        lens_model: text(Tag::LensModel),
+    })
+}
@@ -0,0 +1,116 @@
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use chrono::NaiveDateTime;
+use anyhow::Result;
+use crate::image_info::ImageInfo;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+impl ExportFormat {
+    // json unless the extension is csv
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => ExportFormat::Csv,
+            _ => ExportFormat::Json,
+        }
+    }
+}
+
+// a row of the export, flat so that the csv has the same columns as the json
+#[derive(Serialize, Debug, Clone)]
+pub struct ExportedImage {
+    pub slideshow: PathBuf,
+    pub path: PathBuf,
+    // as displayed, after the exif rotation
+    pub width: u32,
+    pub height: u32,
+    pub creation_date_time: NaiveDateTime,
+    pub is_video: bool,
+    pub duration_ms: Option<u64>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens_model: Option<String>,
+    pub rating: Option<i32>,
+    pub keywords: Vec<String>,
+    pub description: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+impl ExportedImage {
+    pub fn new(slideshow_path: &Path, image_info: &ImageInfo) -> Self {
+        let (width, height) = image_info.displayed_size();
+        Self {
+            slideshow: slideshow_path.to_path_buf(),
+            path: image_info.path.clone(),
+            width,
+            height,
+            creation_date_time: image_info.creation_date_time,
+            is_video: image_info.is_video,
+            duration_ms: image_info.duration_ms,
+            camera_make: image_info.camera_make.clone(),
+            camera_model: image_info.camera_model.clone(),
+            lens_model: image_info.lens_model.clone(),
+            rating: image_info.rating,
+            keywords: image_info.keywords.clone(),
+            description: image_info.description.clone(),
+            latitude: image_info.gps_position.as_ref().map(|gps_position| gps_position.latitude),
+            longitude: image_info.gps_position.as_ref().map(|gps_position| gps_position.longitude),
+        }
+    }
+
+    fn csv_fields(&self) -> Vec<String> {
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        vec![
+            self.slideshow.to_string_lossy().to_string(),
+            self.path.to_string_lossy().to_string(),
+            self.width.to_string(),
+            self.height.to_string(),
+            self.creation_date_time.to_string(),
+            self.is_video.to_string(),
+            optional(self.duration_ms.map(|duration_ms| duration_ms.to_string())),
+            optional(self.camera_make.clone()),
+            optional(self.camera_model.clone()),
+            optional(self.lens_model.clone()),
+            optional(self.rating.map(|rating| rating.to_string())),
+            // a cell, so joined the same as the xmp lists them
+            self.keywords.join("; "),
+            optional(self.description.clone()),
+            optional(self.latitude.map(|latitude| latitude.to_string())),
+            optional(self.longitude.map(|longitude| longitude.to_string())),
+        ]
+    }
+}
+
+const CSV_HEADER: &str = "slideshow,path,width,height,creation_date_time,is_video,duration_ms,camera_make,camera_model,lens_model,rating,keywords,description,latitude,longitude";
+
+// in the order written to the slideshows
+pub async fn write_export(path: &Path, format: ExportFormat, exported_images: &[ExportedImage]) -> Result<()> {
+    let text = match format {
+        ExportFormat::Json => serde_json::to_string_pretty(exported_images)?,
+        ExportFormat::Csv => {
+            let mut text = format!("{}\n", CSV_HEADER);
+            for exported_image in exported_images {
+                let fields: Vec<String> = exported_image.csv_fields().iter().map(|field| quote_csv(field)).collect();
+                text.push_str(&fields.join(","));
+                text.push('\n');
+            }
+            text
+        }
+    };
+    tokio::fs::write(path, text).await?;
+    Ok(())
+}
+
+// rfc 4180, quoted only when needed
+fn quote_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
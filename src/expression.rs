@@ -0,0 +1,427 @@
+use serde::{Serialize, Deserialize};
+use chrono::{Datelike, Timelike};
+use crate::{Error, image_info::ImageInfo};
+
+// a filter like `rating >= 4 && (keyword("family") || camera == "X100V") && aspect in 1.2..1.9`,
+// parsed once when the config is read, where a field is always on the left of its comparison,
+// the text ones are compared case-insensitively the same as the allowlists, and a comparison
+// with a field the image lacks, e.g. the rating of an unrated one, is false
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(try_from = "String", into = "String")]
+pub struct FilterExpression {
+    source: String,
+    node: Node,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Rating,
+    Width,
+    Height,
+    Megapixels,
+    Aspect,
+    Year,
+    Month,
+    Day,
+    Hour,
+    // 1 for monday to 7 for sunday, so that "evenings and weekends" is `hour >= 18 || weekday >= 6`
+    Weekday,
+    DurationSecs,
+    Sharpness,
+    Brightness,
+    Saturation,
+    Camera,
+    Make,
+    Lens,
+    Label,
+    Country,
+    City,
+    Path,
+    Description,
+    Video,
+    Animated,
+    Faces,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    Number,
+    Text,
+    Bool,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        let field = match name {
+            "rating" => Field::Rating,
+            "width" => Field::Width,
+            "height" => Field::Height,
+            "megapixels" => Field::Megapixels,
+            "aspect" | "aspect_ratio" => Field::Aspect,
+            "year" => Field::Year,
+            "month" => Field::Month,
+            "day" => Field::Day,
+            "hour" => Field::Hour,
+            "weekday" => Field::Weekday,
+            "duration" => Field::DurationSecs,
+            "sharpness" => Field::Sharpness,
+            "brightness" => Field::Brightness,
+            "saturation" => Field::Saturation,
+            "camera" | "camera_model" => Field::Camera,
+            "make" | "camera_make" => Field::Make,
+            "lens" | "lens_model" => Field::Lens,
+            "label" | "color_label" => Field::Label,
+            "country" => Field::Country,
+            "city" => Field::City,
+            "path" => Field::Path,
+            "description" => Field::Description,
+            "video" => Field::Video,
+            "animated" => Field::Animated,
+            "faces" => Field::Faces,
+            _ => return None,
+        };
+        Some(field)
+    }
+
+    fn value_type(&self) -> Type {
+        match self {
+            Field::Camera | Field::Make | Field::Lens | Field::Label | Field::Country | Field::City | Field::Path | Field::Description => Type::Text,
+            Field::Video | Field::Animated | Field::Faces => Type::Bool,
+            _ => Type::Number,
+        }
+    }
+
+    fn number(&self, image_info: &ImageInfo) -> Option<f64> {
+        let (width, height) = image_info.displayed_size();
+        let date_time = image_info.creation_date_time;
+        match self {
+            Field::Rating => image_info.rating.map(f64::from),
+            Field::Width => Some(width as f64),
+            Field::Height => Some(height as f64),
+            Field::Megapixels => Some(width as f64 * height as f64 / 1_000_000.0),
+            Field::Aspect => Some(image_info.aspect_ratio()),
+            Field::Year => Some(date_time.year() as f64),
+            Field::Month => Some(date_time.month() as f64),
+            Field::Day => Some(date_time.day() as f64),
+            Field::Hour => Some(date_time.hour() as f64),
+            Field::Weekday => Some(date_time.weekday().number_from_monday() as f64),
+            Field::DurationSecs => image_info.duration_ms.map(|duration_ms| duration_ms as f64 / 1000.0),
+            Field::Sharpness => image_info.sharpness,
+            Field::Brightness => image_info.brightness,
+            Field::Saturation => image_info.saturation,
+            _ => None,
+        }
+    }
+
+    fn text(&self, image_info: &ImageInfo) -> Option<String> {
+        match self {
+            Field::Camera => image_info.camera_model.clone(),
+            Field::Make => image_info.camera_make.clone(),
+            Field::Lens => image_info.lens_model.clone(),
+            Field::Label => image_info.color_label.clone(),
+            Field::Country => image_info.country.clone(),
+            Field::City => image_info.city.clone(),
+            Field::Path => Some(image_info.path.to_string_lossy().into_owned()),
+            Field::Description => image_info.description.clone(),
+            _ => None,
+        }
+    }
+
+    fn flag(&self, image_info: &ImageInfo) -> bool {
+        match self {
+            Field::Video => image_info.is_video,
+            Field::Animated => image_info.animated,
+            // undecoded ones, e.g. videos, have no faces to tell
+            Field::Faces => image_info.has_faces == Some(true),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Or(Box<Node>, Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    CompareNumber(Field, CompareOp, f64),
+    CompareText(Field, CompareOp, String),
+    CompareFlag(Field, CompareOp, bool),
+    // inclusive of both ends
+    InRange(Field, f64, f64),
+    Keyword(String),
+    Flag(Field),
+    Const(bool),
+}
+
+impl Node {
+    fn accepts(&self, image_info: &ImageInfo) -> bool {
+        match self {
+            Node::Or(a, b) => a.accepts(image_info) || b.accepts(image_info),
+            Node::And(a, b) => a.accepts(image_info) && b.accepts(image_info),
+            Node::Not(node) => !node.accepts(image_info),
+            Node::CompareNumber(field, op, value) => field.number(image_info).is_some_and(|number| match op {
+                CompareOp::Eq => number == *value,
+                CompareOp::Ne => number != *value,
+                CompareOp::Lt => number < *value,
+                CompareOp::Le => number <= *value,
+                CompareOp::Gt => number > *value,
+                CompareOp::Ge => number >= *value,
+            }),
+            Node::CompareText(field, op, value) => field.text(image_info).is_some_and(|text| {
+                let is_equal = text.trim().eq_ignore_ascii_case(value.trim());
+                if *op == CompareOp::Eq { is_equal } else { !is_equal }
+            }),
+            Node::CompareFlag(field, op, value) => (field.flag(image_info) == *value) == (*op == CompareOp::Eq),
+            Node::InRange(field, min, max) => field.number(image_info).is_some_and(|number| *min <= number && number <= *max),
+            Node::Keyword(keyword) => image_info.keywords.iter().any(|image_keyword| image_keyword.eq_ignore_ascii_case(keyword.trim())),
+            Node::Flag(field) => field.flag(image_info),
+            Node::Const(value) => *value,
+        }
+    }
+
+    fn uses(&self, field: Field) -> bool {
+        match self {
+            Node::Or(a, b) | Node::And(a, b) => a.uses(field) || b.uses(field),
+            Node::Not(node) => node.uses(field),
+            Node::CompareNumber(used, ..) | Node::CompareText(used, ..) | Node::CompareFlag(used, ..) | Node::InRange(used, ..) | Node::Flag(used) => *used == field,
+            Node::Keyword(_) | Node::Const(_) => false,
+        }
+    }
+}
+
+impl FilterExpression {
+    pub fn accepts(&self, image_info: &ImageInfo) -> bool {
+        self.node.accepts(image_info)
+    }
+
+    // the scores which are computed only when needed
+    pub fn needs_quality(&self) -> bool {
+        self.node.uses(Field::Sharpness) || self.node.uses(Field::Brightness)
+    }
+
+    pub fn needs_faces(&self) -> bool {
+        self.node.uses(Field::Faces)
+    }
+
+    pub fn needs_saturation(&self) -> bool {
+        self.node.uses(Field::Saturation)
+    }
+}
+
+impl TryFrom<String> for FilterExpression {
+    type Error = Error;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        let invalid = |message: String| Error::FilterExpressionError(source.clone(), message);
+        let tokens = tokenize(&source).map_err(invalid)?;
+        let mut parser = Parser { tokens, position: 0 };
+        let node = parser.parse_or().map_err(invalid)?;
+        if let Some(token) = parser.peek() {
+            return Err(invalid(format!("unexpected {:?}", token)));
+        }
+        Ok(Self { source, node })
+    }
+}
+
+impl From<FilterExpression> for String {
+    fn from(filter_expression: FilterExpression) -> Self {
+        filter_expression.source
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Identifier(String),
+    Number(f64),
+    Text(String),
+    And,
+    Or,
+    Not,
+    Op(CompareOp),
+    Range,
+    LeftParen,
+    RightParen,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (token, len) = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('=', Some('=')) => (Token::Op(CompareOp::Eq), 2),
+            ('!', Some('=')) => (Token::Op(CompareOp::Ne), 2),
+            ('<', Some('=')) => (Token::Op(CompareOp::Le), 2),
+            ('>', Some('=')) => (Token::Op(CompareOp::Ge), 2),
+            ('.', Some('.')) => (Token::Range, 2),
+            ('!', _) => (Token::Not, 1),
+            ('<', _) => (Token::Op(CompareOp::Lt), 1),
+            ('>', _) => (Token::Op(CompareOp::Gt), 1),
+            ('(', _) => (Token::LeftParen, 1),
+            (')', _) => (Token::RightParen, 1),
+            ('"', _) => {
+                let mut text = String::new();
+                let mut end = i + 1;
+                loop {
+                    match chars.get(end) {
+                        None => return Err("unterminated string".to_string()),
+                        Some('"') => break,
+                        Some('\\') if end + 1 < chars.len() => {
+                            text.push(chars[end + 1]);
+                            end += 2;
+                        }
+                        Some(c) => {
+                            text.push(*c);
+                            end += 1;
+                        }
+                    }
+                }
+                (Token::Text(text), end + 1 - i)
+            }
+            (c, _) if c.is_ascii_digit() || c == '-' => {
+                let mut end = i + 1;
+                // the dot is of the number only when a digit follows, so that "1..2" is a range
+                while end < chars.len() && (chars[end].is_ascii_digit() || (chars[end] == '.' && chars.get(end + 1).is_some_and(|c| c.is_ascii_digit()))) {
+                    end += 1;
+                }
+                let text: String = chars[i..end].iter().collect();
+                let number = text.parse().map_err(|_| format!("invalid number {}", text))?;
+                (Token::Number(number), end - i)
+            }
+            (c, _) if c.is_ascii_alphabetic() || c == '_' => {
+                let mut end = i + 1;
+                while end < chars.len() && (chars[end].is_ascii_alphanumeric() || chars[end] == '_') {
+                    end += 1;
+                }
+                (Token::Identifier(chars[i..end].iter().collect()), end - i)
+            }
+            (c, _) => return Err(format!("unexpected {}", c)),
+        };
+        tokens.push(token);
+        i += len;
+    }
+    Ok(tokens)
+}
+
+// recursive descent, where && binds tighter than ||
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!("expected {:?}, found {:?}", expected, token)),
+            None => Err(format!("expected {:?} at the end", expected)),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Node, String> {
+        let mut node = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            node = Node::Or(Box::new(node), Box::new(self.parse_and()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_and(&mut self) -> Result<Node, String> {
+        let mut node = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            node = Node::And(Box::new(node), Box::new(self.parse_unary()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_unary(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(Node::Not(Box::new(self.parse_unary()?))),
+            Some(Token::LeftParen) => {
+                let node = self.parse_or()?;
+                self.expect(Token::RightParen)?;
+                Ok(node)
+            }
+            Some(Token::Identifier(name)) => self.parse_identifier(name),
+            Some(token) => Err(format!("unexpected {:?}", token)),
+            None => Err("unexpected end".to_string()),
+        }
+    }
+
+    fn parse_identifier(&mut self, name: String) -> Result<Node, String> {
+        match name.as_str() {
+            "true" => return Ok(Node::Const(true)),
+            "false" => return Ok(Node::Const(false)),
+            "keyword" => {
+                self.expect(Token::LeftParen)?;
+                let keyword = match self.next() {
+                    Some(Token::Text(keyword)) => keyword,
+                    token => return Err(format!("expected a string in keyword(), found {:?}", token)),
+                };
+                self.expect(Token::RightParen)?;
+                return Ok(Node::Keyword(keyword));
+            }
+            _ => {}
+        }
+        let field = Field::parse(&name).ok_or_else(|| format!("unknown field {}", name))?;
+        match (self.peek().cloned(), field.value_type()) {
+            (Some(Token::Op(op)), value_type) => {
+                self.next();
+                match (self.next(), value_type) {
+                    (Some(Token::Number(value)), Type::Number) => Ok(Node::CompareNumber(field, op, value)),
+                    (Some(Token::Text(value)), Type::Text) if matches!(op, CompareOp::Eq | CompareOp::Ne) => Ok(Node::CompareText(field, op, value)),
+                    (Some(Token::Identifier(value)), Type::Bool) if matches!(op, CompareOp::Eq | CompareOp::Ne) && (value == "true" || value == "false") => {
+                        Ok(Node::CompareFlag(field, op, value == "true"))
+                    }
+                    (token, _) => Err(format!("can't compare {} with {:?}", name, token)),
+                }
+            }
+            (Some(Token::Identifier(keyword)), Type::Number) if keyword == "in" => {
+                self.next();
+                let min = self.parse_number()?;
+                self.expect(Token::Range)?;
+                let max = self.parse_number()?;
+                Ok(Node::InRange(field, min, max))
+            }
+            (_, Type::Bool) => Ok(Node::Flag(field)),
+            _ => Err(format!("{} needs a comparison", name)),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<f64, String> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok(number),
+            token => Err(format!("expected a number, found {:?}", token)),
+        }
+    }
+}
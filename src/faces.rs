@@ -0,0 +1,46 @@
+use std::{path::Path, sync::OnceLock};
+use anyhow::Result;
+use image::DynamicImage;
+use ndarray::Array4;
+use ort::session::{Session, builder::GraphOptimizationLevel};
+
+// the input size of the UltraFace models (version-RFB-320.onnx), the image is stretched into it
+const MODEL_WIDTH: u32 = 320;
+const MODEL_HEIGHT: u32 = 240;
+// of the face class, lower ones are mostly textures looking like faces
+const FACE_SCORE_THRESHOLD: f32 = 0.7;
+
+// loaded once for the run, and shared by the parsing threads
+static FACE_DETECTOR: OnceLock<Session> = OnceLock::new();
+
+// before scanning, the later calls are ignored as the model is of the config
+pub fn load_face_model(model_path: &Path) -> Result<()> {
+    if FACE_DETECTOR.get().is_some() {
+        return Ok(());
+    }
+    let session = Session::builder()?
+        .with_optimization_level(GraphOptimizationLevel::Level3)?
+        .commit_from_file(model_path)?;
+    let _ = FACE_DETECTOR.set(session);
+    Ok(())
+}
+
+// none when no model is loaded, e.g. none of the slideshows needs it
+pub fn contains_faces(img: &DynamicImage) -> Result<Option<bool>> {
+    let Some(session) = FACE_DETECTOR.get() else {
+        return Ok(None);
+    };
+    let rgb = img.resize_exact(MODEL_WIDTH, MODEL_HEIGHT, image::imageops::FilterType::Triangle).to_rgb8();
+    // nchw, normalized to about -1 to 1
+    let mut input = Array4::<f32>::zeros((1, 3, MODEL_HEIGHT as usize, MODEL_WIDTH as usize));
+    for (x, y, pixel) in rgb.enumerate_pixels() {
+        for channel in 0..3 {
+            input[[0, channel, y as usize, x as usize]] = (pixel[channel] as f32 - 127.0) / 128.0;
+        }
+    }
+    let outputs = session.run(ort::inputs![input.view()]?)?;
+    // [1, n, 2] of (background, face) for each candidate box
+    let scores = outputs[0].try_extract_tensor::<f32>()?;
+    let has_face = scores.as_slice().unwrap_or_default().chunks_exact(2).any(|score| score[1] > FACE_SCORE_THRESHOLD);
+    Ok(Some(has_face))
+}
@@ -0,0 +1,117 @@
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use tokio::{io::AsyncWriteExt, process::Command};
+use crate::{Error, format::{FormatHeader, SlideshowFormat}, slideshow::EntryOptions};
+
+const VIDEO_FPS: u32 = 30;
+
+#[derive(Debug, Clone)]
+pub struct VideoOptions {
+    pub width: u32,
+    pub height: u32,
+    pub slide_duration_secs: f64,
+    pub crossfade_secs: Option<f64>,
+    // rendered with ffmpeg when given, otherwise only the script is written
+    pub video_path: Option<PathBuf>,
+}
+
+// an ffmpeg concat demuxer script
+pub struct FfconcatWriter {
+    file: tokio::fs::File,
+    script_path: PathBuf,
+    video_options: VideoOptions,
+    // with the durations, the last one is repeated at the end, and crossfade needs all of them as inputs
+    paths: Vec<(PathBuf, f64)>,
+}
+
+impl FfconcatWriter {
+    pub async fn from_path(path: impl AsRef<Path>, video_options: VideoOptions) -> Result<Self> {
+        let file = tokio::fs::OpenOptions::new().create(true).write(true).truncate(true).open(path.as_ref()).await?;
+        Ok(Self {
+            file,
+            script_path: path.as_ref().to_path_buf(),
+            video_options,
+            paths: vec![],
+        })
+    }
+}
+
+impl SlideshowFormat for FfconcatWriter {
+    async fn write_header(&mut self, _header: &FormatHeader<'_>) -> Result<()> {
+        self.file.write_all(b"ffconcat version 1.0\n").await?;
+        Ok(())
+    }
+
+    // slide_duration_secs unless the duration is given
+    async fn write_entry(&mut self, path: &Path, entry_options: &EntryOptions<'_>) -> Result<()> {
+        let duration_secs = entry_options.duration_secs.unwrap_or(self.video_options.slide_duration_secs);
+        let entry = format!("file {}\nduration {}\n", quote(path), duration_secs);
+        self.file.write_all(entry.as_bytes()).await?;
+        self.paths.push((path.to_path_buf(), duration_secs));
+        Ok(())
+    }
+
+    async fn finish(&mut self) -> Result<()> {
+        // the duration of the last file is ignored by ffmpeg unless it's listed again
+        if let Some((last_path, _)) = self.paths.last() {
+            let entry = format!("file {}\n", quote(last_path));
+            self.file.write_all(entry.as_bytes()).await?;
+        }
+        self.file.flush().await?;
+        if let Some(video_path) = &self.video_options.video_path {
+            if !self.paths.is_empty() {
+                render_video(&self.script_path, &self.paths, video_path, &self.video_options).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// inside single quotes, a single quote is closed, escaped and reopened
+fn quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', r"'\''"))
+}
+
+// fitted into the size with black bars, as the images differ in their sizes
+fn fit_filter(video_options: &VideoOptions) -> String {
+    let (width, height) = (video_options.width, video_options.height);
+    format!("scale={width}:{height}:force_original_aspect_ratio=decrease,pad={width}:{height}:(ow-iw)/2:(oh-ih)/2,setsar=1,format=yuv420p,fps={VIDEO_FPS}")
+}
+
+async fn render_video(script_path: &Path, paths: &[(PathBuf, f64)], video_path: &Path, video_options: &VideoOptions) -> Result<()> {
+    let mut command = Command::new("ffmpeg");
+    command.arg("-y");
+    match video_options.crossfade_secs {
+        // the concat demuxer can't overlap the slides, so each image is an input of the xfade chain
+        Some(crossfade_secs) if paths.len() > 1 => {
+            for (path, duration_secs) in paths {
+                let input_duration = duration_secs + crossfade_secs;
+                command.arg("-loop").arg("1").arg("-t").arg(input_duration.to_string()).arg("-i").arg(path);
+            }
+            let mut filter = String::new();
+            for i in 0..paths.len() {
+                filter.push_str(&format!("[{i}:v]{}[v{i}];", fit_filter(video_options)));
+            }
+            let mut previous = "v0".to_string();
+            let mut offset = 0.0;
+            for i in 1..paths.len() {
+                offset += paths[i - 1].1;
+                let output = if i == paths.len() - 1 { "out".to_string() } else { format!("x{i}") };
+                filter.push_str(&format!("[{previous}][v{i}]xfade=transition=fade:duration={crossfade_secs}:offset={offset}[{output}];"));
+                previous = output;
+            }
+            filter.pop();
+            command.arg("-filter_complex").arg(filter).arg("-map").arg("[out]");
+        }
+        _ => {
+            command.arg("-f").arg("concat").arg("-safe").arg("0").arg("-i").arg(script_path);
+            command.arg("-vf").arg(fit_filter(video_options));
+        }
+    }
+    command.arg("-c:v").arg("libx264").arg(video_path);
+    let status = command.status().await?;
+    if !status.success() {
+        return Err(Error::FfmpegError(status.to_string()).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,672 @@
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use chrono::{Datelike, Days, Months, NaiveDate, NaiveTime, Weekday};
+use globset::GlobSet;
+use crate::{Error, clock, expression::FilterExpression, image_info::{GpsPosition, ImageInfo}};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImageFilter {
+    // any aspect ratio when omitted
+    #[serde(default)]
+    pub min_aspect_ratio: Option<f64>,
+    #[serde(default)]
+    pub max_aspect_ratio: Option<f64>,
+    // the same as the aspect ratios but by name, of the size after the exif rotation
+    #[serde(default)]
+    pub orientation: Orientation,
+    // the single min/max pair and date_ranges are OR'd together, an image passes if its date falls
+    // in any one of them, and if none of them is given any date passes
+    #[serde(default)]
+    pub min_creation_date: Option<DateBound>,
+    #[serde(default)]
+    pub max_creation_date: Option<DateBound>,
+    // the same as min_creation_date = "now-{n}d", the later of the two wins
+    #[serde(default)]
+    pub last_n_days: Option<u64>,
+    #[serde(default)]
+    pub date_ranges: Vec<DateRange>,
+    // "memories", the images taken within this many days of today's month and day in any year,
+    // on top of the other dates
+    #[serde(default)]
+    pub on_this_day: Option<u32>,
+    // of the creation date time, e.g. "18:00-23:00", or "22:00-02:00" across midnight
+    #[serde(default)]
+    pub time_of_day: Option<TimeOfDay>,
+    // e.g. ["Sat", "Sun"], empty means all
+    #[serde(default)]
+    pub weekdays: Vec<Weekday>,
+    // of the displayed size, so that screenshots and thumbnails are left out
+    #[serde(default)]
+    pub min_width: Option<u32>,
+    #[serde(default)]
+    pub min_height: Option<u32>,
+    #[serde(default)]
+    pub min_megapixels: Option<f64>,
+    // allowlists compared case-insensitively, empty means all, and images without the tag never match a non-empty one
+    #[serde(default)]
+    pub camera_models: Vec<String>,
+    #[serde(default)]
+    pub lens_models: Vec<String>,
+    // xmp:Rating or the rating of the catalog, images without the rating never pass it
+    #[serde(default, alias = "min_xnview_rating")]
+    pub min_rating: Option<i32>,
+    // xmp:Label or the color label of the catalog, e.g. ["Red", "Green"], an allowlist the same as camera_models
+    #[serde(default)]
+    pub color_labels: Vec<String>,
+    // dc:subject compared case-insensitively, all the required ones and none of the excluded ones
+    #[serde(default)]
+    pub required_keywords: Vec<String>,
+    #[serde(default)]
+    pub excluded_keywords: Vec<String>,
+    // images without the gps position never pass it
+    #[serde(default)]
+    pub geo_filter: Option<GeoFilter>,
+    // of the nearest city to the gps position, the countries as ISO 3166 codes, e.g. ["JP"], allowlists the same as camera_models
+    #[serde(default)]
+    pub countries: Vec<String>,
+    #[serde(default)]
+    pub cities: Vec<String>,
+    // the variance of the laplacian, e.g. 100 leaves out the obviously blurry ones, measured at 1024 px
+    #[serde(default)]
+    pub min_sharpness: Option<f64>,
+    // the mean luma from 0 to 1, e.g. 0.05 leaves out the pitch-black frames
+    #[serde(default)]
+    pub min_brightness: Option<f64>,
+    #[serde(default)]
+    pub max_brightness: Option<f64>,
+    // true for a family album, false for a landscape screensaver, by the face_model of the config
+    #[serde(default)]
+    pub contains_faces: Option<bool>,
+    // true for black and white only, false for color only, by the saturation with the monochrome feature
+    #[serde(default)]
+    pub is_monochrome: Option<bool>,
+    // the saturation up to which an image is monochrome, raise it to take sepia as monochrome too
+    #[serde(default = "default_max_monochrome_saturation")]
+    pub max_monochrome_saturation: f64,
+    #[serde(default)]
+    pub animated: AnimatedPolicy,
+    // "filter" of the slideshow, for the selections the fields above can't make, e.g.
+    // `rating >= 4 && (keyword("family") || camera == "X100V") && aspect in 1.2..1.9`, see FilterExpression
+    #[serde(default, rename = "filter")]
+    pub expression: Option<FilterExpression>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnimatedPolicy {
+    #[default]
+    Include,
+    Exclude,
+    // a still of the first frame is listed instead, from the cache dir or export_dir
+    FirstFrameOnly,
+}
+
+// the mean chroma of a scanned black and white print or a jpeg of a gray one, which is never exactly 0
+fn default_max_monochrome_saturation() -> f64 {
+    0.03
+}
+
+// within this of 1, an aspect ratio counts as square, e.g. 1080x1080 and 1000x1040
+const SQUARE_TOLERANCE: f64 = 0.05;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    #[default]
+    Any,
+    Landscape,
+    Portrait,
+    Square,
+}
+
+impl Orientation {
+    pub fn accepts(&self, aspect_ratio: f64) -> bool {
+        match self {
+            Orientation::Any => true,
+            Orientation::Landscape => aspect_ratio > 1.0 + SQUARE_TOLERANCE,
+            Orientation::Portrait => aspect_ratio < 1.0 - SQUARE_TOLERANCE,
+            Orientation::Square => (aspect_ratio - 1.0).abs() <= SQUARE_TOLERANCE,
+        }
+    }
+}
+
+// given in an image dir, the fields given replace the ones of the slideshow for the images under it
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FilterOverrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_aspect_ratio: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_aspect_ratio: Option<f64>,
+    // any of the dates replaces all the dates of the slideshow, as they are OR'd together
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_creation_date: Option<DateBound>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_creation_date: Option<DateBound>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_n_days: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date_ranges: Option<Vec<DateRange>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_rating: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub required_keywords: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub excluded_keywords: Option<Vec<String>>,
+}
+
+impl FilterOverrides {
+    pub fn is_empty(&self) -> bool {
+        self.min_aspect_ratio.is_none()
+            && self.max_aspect_ratio.is_none()
+            && !self.overrides_dates()
+            && self.min_rating.is_none()
+            && self.required_keywords.is_none()
+            && self.excluded_keywords.is_none()
+    }
+
+    fn overrides_dates(&self) -> bool {
+        self.min_creation_date.is_some() || self.max_creation_date.is_some() || self.last_n_days.is_some() || self.date_ranges.is_some()
+    }
+}
+
+// the filter of the slideshow, and the overridden ones of the image dirs, where the deepest dir wins
+#[derive(Debug, Clone)]
+pub struct DirFilters {
+    filter: ImageFilter,
+    dir_filters: Vec<(PathBuf, ImageFilter)>,
+    // win over the filters, the never ones over the always ones
+    always_include: GlobSet,
+    never_include: GlobSet,
+}
+
+impl DirFilters {
+    pub fn new(filter: ImageFilter, dir_overrides: &[(PathBuf, FilterOverrides)]) -> Self {
+        let dir_filters = dir_overrides.iter()
+            .filter(|(_, overrides)| !overrides.is_empty())
+            .map(|(dir, overrides)| (dir.clone(), filter.with_overrides(overrides)))
+            .collect();
+        Self { filter, dir_filters, always_include: GlobSet::empty(), never_include: GlobSet::empty() }
+    }
+
+    pub fn with_pins(self, always_include: GlobSet, never_include: GlobSet) -> Self {
+        Self { always_include, never_include, ..self }
+    }
+
+    pub fn filter_for(&self, path: &Path) -> &ImageFilter {
+        self.dir_filters.iter()
+            .filter(|(dir, _)| path.starts_with(dir))
+            .max_by_key(|(dir, _)| dir.components().count())
+            .map_or(&self.filter, |(_, filter)| filter)
+    }
+
+    pub fn rejection(&self, image_info: &ImageInfo) -> Option<FilterReason> {
+        if self.never_include.is_match(&image_info.path) {
+            return Some(FilterReason::NeverInclude);
+        }
+        if self.always_include.is_match(&image_info.path) {
+            return None;
+        }
+        self.filter_for(&image_info.path).rejection(image_info)
+    }
+}
+
+// which filter rejected an image, for the stats
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterReason {
+    CreationDate,
+    TimeOfDay,
+    Weekday,
+    AspectRatio,
+    Orientation,
+    Resolution,
+    CameraModel,
+    LensModel,
+    Rating,
+    ColorLabel,
+    Keywords,
+    Geo,
+    Place,
+    Quality,
+    Faces,
+    Monochrome,
+    Animated,
+    Expression,
+    NeverInclude,
+}
+
+impl std::fmt::Display for FilterReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            FilterReason::CreationDate => "creation date",
+            FilterReason::TimeOfDay => "time of day",
+            FilterReason::Weekday => "weekday",
+            FilterReason::AspectRatio => "aspect ratio",
+            FilterReason::Orientation => "orientation",
+            FilterReason::Resolution => "resolution",
+            FilterReason::CameraModel => "camera model",
+            FilterReason::LensModel => "lens model",
+            FilterReason::Rating => "rating",
+            FilterReason::ColorLabel => "color label",
+            FilterReason::Keywords => "keywords",
+            FilterReason::Geo => "geo filter",
+            FilterReason::Place => "place",
+            FilterReason::Quality => "quality",
+            FilterReason::Faces => "faces",
+            FilterReason::Monochrome => "monochrome",
+            FilterReason::Animated => "animated",
+            FilterReason::Expression => "expression",
+            FilterReason::NeverInclude => "never_include",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl ImageFilter {
+    pub fn accepts(&self, image_info: &ImageInfo) -> bool {
+        self.rejection(image_info).is_none()
+    }
+
+    pub fn with_overrides(&self, overrides: &FilterOverrides) -> Self {
+        let mut filter = self.clone();
+        if overrides.min_aspect_ratio.is_some() {
+            filter.min_aspect_ratio = overrides.min_aspect_ratio;
+        }
+        if overrides.max_aspect_ratio.is_some() {
+            filter.max_aspect_ratio = overrides.max_aspect_ratio;
+        }
+        if overrides.overrides_dates() {
+            filter.min_creation_date = overrides.min_creation_date;
+            filter.max_creation_date = overrides.max_creation_date;
+            filter.last_n_days = overrides.last_n_days;
+            filter.date_ranges = overrides.date_ranges.clone().unwrap_or_default();
+        }
+        if overrides.min_rating.is_some() {
+            filter.min_rating = overrides.min_rating;
+        }
+        if let Some(required_keywords) = &overrides.required_keywords {
+            filter.required_keywords = required_keywords.clone();
+        }
+        if let Some(excluded_keywords) = &overrides.excluded_keywords {
+            filter.excluded_keywords = excluded_keywords.clone();
+        }
+        filter
+    }
+
+    // the combinations which can never match
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if let (Some(min_aspect_ratio), Some(max_aspect_ratio)) = (self.min_aspect_ratio, self.max_aspect_ratio) {
+            if min_aspect_ratio > max_aspect_ratio {
+                problems.push(format!("min_aspect_ratio {} is greater than max_aspect_ratio {}", min_aspect_ratio, max_aspect_ratio));
+            }
+        }
+        if let (Some(min_brightness), Some(max_brightness)) = (self.min_brightness, self.max_brightness) {
+            if min_brightness > max_brightness {
+                problems.push(format!("min_brightness {} is greater than max_brightness {}", min_brightness, max_brightness));
+            }
+        }
+        let today = clock::today();
+        if let (Some(min_creation_date), Some(max_creation_date)) = (self.min_date(today), self.max_date(today)) {
+            if min_creation_date > max_creation_date {
+                problems.push(format!("min_creation_date {} is later than max_creation_date {}", min_creation_date, max_creation_date));
+            }
+        }
+        for date_range in &self.date_ranges {
+            if date_range.min > date_range.max {
+                problems.push(format!("date range {} to {} is reversed", date_range.min, date_range.max));
+            }
+        }
+        if let Some(time_of_day) = self.time_of_day {
+            if time_of_day.start == time_of_day.end {
+                problems.push(format!("time_of_day {} is empty", String::from(time_of_day)));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.max_monochrome_saturation) {
+            problems.push(format!("max_monochrome_saturation {} is not from 0 to 1", self.max_monochrome_saturation));
+        }
+        if let Some(min_rating) = self.min_rating {
+            if min_rating > 5 {
+                problems.push(format!("min_rating {} is greater than 5", min_rating));
+            }
+        }
+        match self.geo_filter {
+            Some(GeoFilter::Circle { radius_km, .. }) if radius_km <= 0.0 => {
+                problems.push(format!("radius_km {} of geo_filter is not positive", radius_km));
+            }
+            // only the latitudes, as the longitudes may cross the antimeridian
+            Some(GeoFilter::BoundingBox { min_latitude, max_latitude, .. }) if min_latitude > max_latitude => {
+                problems.push(format!("min_latitude {} of geo_filter is greater than max_latitude {}", min_latitude, max_latitude));
+            }
+            _ => {}
+        }
+        problems
+    }
+
+    // the first filter the image fails, none when accepted
+    pub fn rejection(&self, image_info: &ImageInfo) -> Option<FilterReason> {
+        let date_time = image_info.creation_date_time;
+        let date = date_time.date();
+        if !self.accepts_creation_date(date) || !self.accepts_on_this_day(date) {
+            return Some(FilterReason::CreationDate);
+        }
+        if self.time_of_day.is_some_and(|time_of_day| !time_of_day.contains(date_time.time())) {
+            return Some(FilterReason::TimeOfDay);
+        }
+        if !self.weekdays.is_empty() && !self.weekdays.contains(&date.weekday()) {
+            return Some(FilterReason::Weekday);
+        }
+        let aspect_ratio = image_info.aspect_ratio();
+        let below_min = self.min_aspect_ratio.is_some_and(|min_aspect_ratio| aspect_ratio < min_aspect_ratio);
+        let above_max = self.max_aspect_ratio.is_some_and(|max_aspect_ratio| aspect_ratio > max_aspect_ratio);
+        if below_min || above_max {
+            return Some(FilterReason::AspectRatio);
+        }
+        if !self.orientation.accepts(aspect_ratio) {
+            return Some(FilterReason::Orientation);
+        }
+        let (width, height) = image_info.displayed_size();
+        let megapixels = width as f64 * height as f64 / 1_000_000.0;
+        if self.min_width.is_some_and(|min_width| width < min_width)
+            || self.min_height.is_some_and(|min_height| height < min_height)
+            || self.min_megapixels.is_some_and(|min_megapixels| megapixels < min_megapixels) {
+            return Some(FilterReason::Resolution);
+        }
+        if !is_allowed(&self.camera_models, image_info.camera_model.as_deref()) {
+            return Some(FilterReason::CameraModel);
+        }
+        if !is_allowed(&self.lens_models, image_info.lens_model.as_deref()) {
+            return Some(FilterReason::LensModel);
+        }
+        if self.min_rating.is_some_and(|min_rating| image_info.rating.is_none_or(|rating| rating < min_rating)) {
+            return Some(FilterReason::Rating);
+        }
+        if !is_allowed(&self.color_labels, image_info.color_label.as_deref()) {
+            return Some(FilterReason::ColorLabel);
+        }
+        let has_keyword = |keyword: &String| image_info.keywords.iter().any(|image_keyword| image_keyword.eq_ignore_ascii_case(keyword.trim()));
+        if !self.required_keywords.iter().all(has_keyword) || self.excluded_keywords.iter().any(has_keyword) {
+            return Some(FilterReason::Keywords);
+        }
+        if let Some(geo_filter) = &self.geo_filter {
+            if !image_info.gps_position.is_some_and(|gps_position| geo_filter.contains(gps_position)) {
+                return Some(FilterReason::Geo);
+            }
+        }
+        if !is_allowed(&self.countries, image_info.country.as_deref()) || !is_allowed(&self.cities, image_info.city.as_deref()) {
+            return Some(FilterReason::Place);
+        }
+        // images which are not decoded, e.g. videos, have no scores and pass
+        let too_blurry = matches!((self.min_sharpness, image_info.sharpness), (Some(min_sharpness), Some(sharpness)) if sharpness < min_sharpness);
+        let too_dark = matches!((self.min_brightness, image_info.brightness), (Some(min_brightness), Some(brightness)) if brightness < min_brightness);
+        let too_bright = matches!((self.max_brightness, image_info.brightness), (Some(max_brightness), Some(brightness)) if brightness > max_brightness);
+        if too_blurry || too_dark || too_bright {
+            return Some(FilterReason::Quality);
+        }
+        if matches!((self.contains_faces, image_info.has_faces), (Some(contains_faces), Some(has_faces)) if contains_faces != has_faces) {
+            return Some(FilterReason::Faces);
+        }
+        if matches!((self.is_monochrome, image_info.saturation), (Some(is_monochrome), Some(saturation)) if is_monochrome != (saturation <= self.max_monochrome_saturation)) {
+            return Some(FilterReason::Monochrome);
+        }
+        if self.animated == AnimatedPolicy::Exclude && image_info.animated {
+            return Some(FilterReason::Animated);
+        }
+        if self.expression.as_ref().is_some_and(|expression| !expression.accepts(image_info)) {
+            return Some(FilterReason::Expression);
+        }
+        None
+    }
+
+    pub fn needs_quality(&self) -> bool {
+        self.min_sharpness.is_some() || self.min_brightness.is_some() || self.max_brightness.is_some()
+            || self.expression.as_ref().is_some_and(|expression| expression.needs_quality())
+    }
+
+    pub fn needs_faces(&self) -> bool {
+        self.contains_faces.is_some() || self.expression.as_ref().is_some_and(|expression| expression.needs_faces())
+    }
+
+    pub fn needs_saturation(&self) -> bool {
+        self.is_monochrome.is_some() || self.expression.as_ref().is_some_and(|expression| expression.needs_saturation())
+    }
+
+    fn accepts_creation_date(&self, date: NaiveDate) -> bool {
+        let today = clock::today();
+        let (min_date, max_date) = (self.min_date(today), self.max_date(today));
+        let has_single_range = min_date.is_some() || max_date.is_some();
+        if !has_single_range && self.date_ranges.is_empty() {
+            return true;
+        }
+        let in_single_range = has_single_range
+            && min_date.is_none_or(|min| min <= date)
+            && max_date.is_none_or(|max| date <= max);
+        in_single_range || self.date_ranges.iter().any(|date_range| date_range.contains(date))
+    }
+
+    fn accepts_on_this_day(&self, date: NaiveDate) -> bool {
+        let Some(window_days) = self.on_this_day else {
+            return true;
+        };
+        let today = clock::today();
+        // the neighboring years too, as the window may cross the new year
+        (date.year() - 1..=date.year() + 1).any(|year| {
+            // feb 29 is celebrated on feb 28 in the other years
+            let anniversary = NaiveDate::from_ymd_opt(year, today.month(), today.day())
+                .or_else(|| NaiveDate::from_ymd_opt(year, today.month(), today.day() - 1));
+            anniversary.is_some_and(|anniversary| (date - anniversary).num_days().unsigned_abs() <= window_days as u64)
+        })
+    }
+
+    fn min_date(&self, today: NaiveDate) -> Option<NaiveDate> {
+        let min_creation_date = self.min_creation_date.map(|min_creation_date| min_creation_date.resolve(today));
+        let last_n_days = self.last_n_days.map(|last_n_days| DateBound::DaysAgo(last_n_days).resolve(today));
+        min_creation_date.max(last_n_days)
+    }
+
+    fn max_date(&self, today: NaiveDate) -> Option<NaiveDate> {
+        self.max_creation_date.map(|max_creation_date| max_creation_date.resolve(today))
+    }
+}
+
+fn is_allowed(allowlist: &[String], value: Option<&str>) -> bool {
+    if allowlist.is_empty() {
+        return true;
+    }
+    value.is_some_and(|value| allowlist.iter().any(|allowed| allowed.trim().eq_ignore_ascii_case(value)))
+}
+
+// a date, or one relative to the day of the run like "today", "now-90d", "now-2w", "now-6m" or "now-1y",
+// so that a scheduled run always covers the recent ones
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub enum DateBound {
+    Date(NaiveDate),
+    DaysAgo(u64),
+    MonthsAgo(u32),
+}
+
+impl DateBound {
+    pub fn resolve(&self, today: NaiveDate) -> NaiveDate {
+        match *self {
+            DateBound::Date(date) => date,
+            DateBound::DaysAgo(days) => today.checked_sub_days(Days::new(days)).unwrap_or(NaiveDate::MIN),
+            DateBound::MonthsAgo(months) => today.checked_sub_months(Months::new(months)).unwrap_or(NaiveDate::MIN),
+        }
+    }
+}
+
+impl TryFrom<String> for DateBound {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if let Ok(date) = NaiveDate::parse_from_str(&value, "%Y-%m-%d") {
+            return Ok(DateBound::Date(date));
+        }
+        let invalid = || Error::DateBoundError(value.clone());
+        let rest = value.trim().strip_prefix("now").or_else(|| value.trim().strip_prefix("today")).ok_or_else(invalid)?;
+        if rest.is_empty() {
+            return Ok(DateBound::DaysAgo(0));
+        }
+        let rest = rest.strip_prefix('-').ok_or_else(invalid)?;
+        let (unit_index, _) = rest.char_indices().last().ok_or_else(invalid)?;
+        let (n, unit) = rest.split_at(unit_index);
+        let n: u64 = n.parse().map_err(|_| invalid())?;
+        let months = |n: u64| u32::try_from(n).map_err(|_| invalid());
+        match unit {
+            "d" => Ok(DateBound::DaysAgo(n)),
+            "w" => Ok(DateBound::DaysAgo(n.saturating_mul(7))),
+            "m" => Ok(DateBound::MonthsAgo(months(n)?)),
+            "y" => Ok(DateBound::MonthsAgo(months(n.saturating_mul(12))?)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl From<DateBound> for String {
+    fn from(date_bound: DateBound) -> Self {
+        match date_bound {
+            DateBound::Date(date) => date.format("%Y-%m-%d").to_string(),
+            DateBound::DaysAgo(0) => "now".to_string(),
+            DateBound::DaysAgo(days) => format!("now-{}d", days),
+            DateBound::MonthsAgo(months) => format!("now-{}m", months),
+        }
+    }
+}
+
+// in bytes, given as a number or like "200KB" or "30MB", where a KB is 1024 bytes the same as Explorer shows
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(try_from = "FileSizeValue", into = "FileSizeValue")]
+pub struct FileSize(pub u64);
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum FileSizeValue {
+    Bytes(u64),
+    Text(String),
+}
+
+impl TryFrom<FileSizeValue> for FileSize {
+    type Error = Error;
+
+    fn try_from(value: FileSizeValue) -> Result<Self, Self::Error> {
+        let text = match value {
+            FileSizeValue::Bytes(bytes) => return Ok(FileSize(bytes)),
+            FileSizeValue::Text(text) => text,
+        };
+        let invalid = || Error::FileSizeError(text.clone());
+        let upper_text = text.trim().to_ascii_uppercase();
+        let unit_start = upper_text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(upper_text.len());
+        let (n, unit) = upper_text.split_at(unit_start);
+        let n: f64 = n.parse().map_err(|_| invalid())?;
+        let multiplier: u64 = match unit.trim() {
+            "" | "B" => 1,
+            "K" | "KB" | "KIB" => 1024,
+            "M" | "MB" | "MIB" => 1024 * 1024,
+            "G" | "GB" | "GIB" => 1024 * 1024 * 1024,
+            _ => return Err(invalid()),
+        };
+        Ok(FileSize((n * multiplier as f64) as u64))
+    }
+}
+
+impl From<FileSize> for FileSizeValue {
+    fn from(file_size: FileSize) -> Self {
+        Self::Bytes(file_size.0)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DateRange {
+    pub min: NaiveDate,
+    pub max: NaiveDate,
+}
+
+impl DateRange {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.min <= date && date <= self.max
+    }
+}
+
+// the start is inclusive and the end exclusive, so that "18:00-23:00" and "23:00-02:00" don't overlap
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl TimeOfDay {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            // across midnight
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let invalid = || Error::TimeOfDayError(value.clone());
+        // an en dash too, as it's how the ranges are often written
+        let (start, end) = value.split_once('-').or_else(|| value.split_once('–')).ok_or_else(invalid)?;
+        let parse_time = |text: &str| NaiveTime::parse_from_str(text.trim(), "%H:%M")
+            .or_else(|_| NaiveTime::parse_from_str(text.trim(), "%H:%M:%S"))
+            .map_err(|_| invalid());
+        Ok(TimeOfDay { start: parse_time(start)?, end: parse_time(end)? })
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(time_of_day: TimeOfDay) -> Self {
+        format!("{}-{}", time_of_day.start.format("%H:%M"), time_of_day.end.format("%H:%M"))
+    }
+}
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+// either a circle or a bounding box, told apart by the fields
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum GeoFilter {
+    Circle {
+        latitude: f64,
+        longitude: f64,
+        radius_km: f64,
+    },
+    BoundingBox {
+        min_latitude: f64,
+        max_latitude: f64,
+        min_longitude: f64,
+        max_longitude: f64,
+    },
+}
+
+impl GeoFilter {
+    pub fn contains(&self, gps_position: GpsPosition) -> bool {
+        match *self {
+            GeoFilter::Circle { latitude, longitude, radius_km } => {
+                // haversine
+                let (latitude_1, latitude_2) = (latitude.to_radians(), gps_position.latitude.to_radians());
+                let delta_latitude = latitude_2 - latitude_1;
+                let delta_longitude = (gps_position.longitude - longitude).to_radians();
+                let a = (delta_latitude / 2.0).sin().powi(2) + latitude_1.cos() * latitude_2.cos() * (delta_longitude / 2.0).sin().powi(2);
+                let distance_km = 2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin();
+                distance_km <= radius_km
+            }
+            GeoFilter::BoundingBox { min_latitude, max_latitude, min_longitude, max_longitude } => {
+                let in_longitude = if min_longitude <= max_longitude {
+                    min_longitude <= gps_position.longitude && gps_position.longitude <= max_longitude
+                } else {
+                    // across the antimeridian
+                    min_longitude <= gps_position.longitude || gps_position.longitude <= max_longitude
+                };
+                min_latitude <= gps_position.latitude && gps_position.latitude <= max_latitude && in_longitude
+            }
+        }
+    }
+}
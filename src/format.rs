@@ -0,0 +1,25 @@
+use std::path::Path;
+use anyhow::Result;
+use crate::slideshow::{EntryOptions, SlideshowHeader};
+
+// of the whole output, each format writes the parts it has a place for, e.g. html only the title
+pub struct FormatHeader<'a> {
+    // e.g. the file stem of the output
+    pub title: &'a str,
+    // of the screen, e.g. of the monitor
+    pub width: u32,
+    pub height: u32,
+    pub slideshow_header: &'a SlideshowHeader,
+}
+
+// an output format, written as the header, the entries and then finish, after which the file is complete
+// only used with the concrete writers, so there's no need of Send bounds on the futures
+#[allow(async_fn_in_trait)]
+pub trait SlideshowFormat {
+    async fn write_header(&mut self, header: &FormatHeader<'_>) -> Result<()>;
+
+    // the options the format has no place for are dropped
+    async fn write_entry(&mut self, path: &Path, entry_options: &EntryOptions<'_>) -> Result<()>;
+
+    async fn finish(&mut self) -> Result<()>;
+}
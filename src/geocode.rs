@@ -0,0 +1,13 @@
+use std::sync::OnceLock;
+use reverse_geocoder::ReverseGeocoder;
+use crate::image_info::GpsPosition;
+
+// the cities of GeoNames embedded in the binary, built into the tree on the first lookup
+static GEOCODER: OnceLock<ReverseGeocoder> = OnceLock::new();
+
+// the nearest city of the position, offline, e.g. ("JP", "Shibuya")
+pub fn place(gps_position: GpsPosition) -> (String, String) {
+    let geocoder = GEOCODER.get_or_init(ReverseGeocoder::new);
+    let record = geocoder.search((gps_position.latitude, gps_position.longitude)).record;
+    (record.cc.clone(), record.name.clone())
+}
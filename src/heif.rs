@@ -0,0 +1,61 @@
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+use anyhow::Result;
+use crate::Error;
+
+// the meta box comes before the image data in the files from cameras and phones
+const META_SEARCH_BYTES: u64 = 1024 * 1024;
+
+// heic, heif and avif can't be decoded by the image crate, but the container has the size
+pub fn is_heif_path(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+        .is_some_and(|extension| matches!(extension.as_str(), "heic" | "heif" | "hif" | "avif"))
+}
+
+// the biggest ispe box is the primary image, others are of the thumbnails or the grid tiles
+pub async fn read_heif_size(path: &Path) -> Result<(u32, u32)> {
+    let mut head = Vec::new();
+    tokio::fs::File::open(path).await?.take(META_SEARCH_BYTES).read_to_end(&mut head).await?;
+    let size = find_box(&head, b"meta")
+        // meta is a full box, with the version and the flags first
+        .and_then(|meta| meta.get(4..))
+        .and_then(|meta| find_box(meta, b"iprp"))
+        .and_then(|iprp| find_box(iprp, b"ipco"))
+        .and_then(|ipco| {
+            boxes(ipco)
+                .filter(|(box_type, _)| box_type == b"ispe")
+                .filter_map(|(_, ispe)| {
+                    let width = u32::from_be_bytes(ispe.get(4..8)?.try_into().ok()?);
+                    let height = u32::from_be_bytes(ispe.get(8..12)?.try_into().ok()?);
+                    Some((width, height))
+                })
+                .max_by_key(|(width, height)| *width as u64 * *height as u64)
+        });
+    size.ok_or_else(|| Error::HeifSizeError(path.to_path_buf()).into())
+}
+
+fn find_box<'a>(data: &'a [u8], box_type: &[u8; 4]) -> Option<&'a [u8]> {
+    boxes(data).find(|(found_type, _)| found_type == box_type).map(|(_, body)| body)
+}
+
+// (type, body) of each box, stops at a broken or truncated one
+fn boxes<'a>(mut data: &'a [u8]) -> impl Iterator<Item = ([u8; 4], &'a [u8])> + 'a {
+    std::iter::from_fn(move || {
+        let size = u32::from_be_bytes(data.get(0..4)?.try_into().ok()?) as u64;
+        let box_type: [u8; 4] = data.get(4..8)?.try_into().ok()?;
+        let (header_size, size) = match size {
+            // 64-bit size follows the type
+            1 => (16, u64::from_be_bytes(data.get(8..16)?.try_into().ok()?)),
+            // to the end
+            0 => (8, data.len() as u64),
+            size => (8, size),
+        };
+        if size < header_size || size > data.len() as u64 {
+            return None;
+        }
+        let body = &data[header_size as usize..size as usize];
+        data = &data[size as usize..];
+        Some((box_type, body))
+    })
+}
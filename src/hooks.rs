@@ -0,0 +1,28 @@
+use tokio::process::Command;
+use anyhow::Result;
+use tracing::info;
+use crate::{Error, config::SlideshowConfig};
+
+// by the shell of the platform, with the slideshow in the environment, e.g. "scp \"$SLIDESHOW_PATH\" mediapc:",
+// where SLIDESHOW_MATCHES is the number of the images written, only set after generating
+pub async fn run_hook(command: &str, slideshow: &SlideshowConfig, n_matches: Option<usize>) -> Result<()> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C").arg(command);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c").arg(command);
+        shell
+    };
+    shell.env("SLIDESHOW_PATH", &slideshow.path).env("SLIDESHOW_NAME", slideshow.name());
+    if let Some(n_matches) = n_matches {
+        shell.env("SLIDESHOW_MATCHES", n_matches.to_string());
+    }
+    info!("Running: {}", command);
+    let status = shell.status().await?;
+    if !status.success() {
+        return Err(Error::HookError(command.to_string(), status.to_string()).into());
+    }
+    Ok(())
+}
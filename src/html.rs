@@ -0,0 +1,140 @@
+use std::{io::Cursor, path::{Path, PathBuf}};
+use anyhow::Result;
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use image::ImageFormat;
+use tokio::{io::AsyncWriteExt, task};
+use tracing::warn;
+use crate::{format::{FormatHeader, SlideshowFormat}, slideshow::EntryOptions};
+
+// the images are embedded in these sizes, so that the single file can be shared as is
+const THUMBNAIL_SIZE: u32 = 256;
+const PREVIEW_SIZE: u32 = 1600;
+
+const HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body { margin: 0; background: #111; color: #eee; font-family: sans-serif; }
+h1 { font-size: 1.2em; margin: 16px; }
+.grid { display: flex; flex-wrap: wrap; gap: 8px; padding: 8px; }
+.grid figure { margin: 0; cursor: pointer; }
+.grid img { height: 160px; display: block; }
+.grid figcaption { font-size: 0.7em; max-width: 240px; overflow: hidden; white-space: nowrap; text-overflow: ellipsis; }
+#lightbox { display: none; position: fixed; inset: 0; background: rgba(0, 0, 0, 0.95); align-items: center; justify-content: center; }
+#lightbox.open { display: flex; }
+#lightbox img { max-width: 100vw; max-height: 100vh; }
+#lightbox .controls { position: fixed; bottom: 16px; right: 16px; }
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<div class="grid">
+"#;
+
+const TAIL: &str = r#"</div>
+<div id="lightbox">
+<img id="preview">
+<div class="controls"><button id="play">Play</button> <button id="close">Close</button></div>
+</div>
+<script>
+const figures = Array.from(document.querySelectorAll('.grid figure'));
+const lightbox = document.getElementById('lightbox');
+const preview = document.getElementById('preview');
+const play = document.getElementById('play');
+let current = 0;
+let timer = null;
+function show(i) {
+  current = (i + figures.length) % figures.length;
+  preview.src = figures[current].dataset.preview;
+  lightbox.classList.add('open');
+}
+function stop() {
+  clearInterval(timer);
+  timer = null;
+  play.textContent = 'Play';
+}
+figures.forEach((figure, i) => figure.addEventListener('click', () => show(i)));
+preview.addEventListener('click', () => show(current + 1));
+document.getElementById('close').addEventListener('click', () => { stop(); lightbox.classList.remove('open'); });
+play.addEventListener('click', () => {
+  if (timer) { stop(); return; }
+  timer = setInterval(() => show(current + 1), 5000);
+  play.textContent = 'Pause';
+});
+document.addEventListener('keydown', (e) => {
+  if (!lightbox.classList.contains('open')) return;
+  if (e.key === 'ArrowRight') show(current + 1);
+  if (e.key === 'ArrowLeft') show(current - 1);
+  if (e.key === 'Escape') { stop(); lightbox.classList.remove('open'); }
+});
+</script>
+</body>
+</html>
+"#;
+
+pub struct HtmlWriter {
+    file: tokio::fs::File,
+}
+
+impl HtmlWriter {
+    pub async fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let file = tokio::fs::OpenOptions::new().create(true).write(true).truncate(true).open(path.as_ref()).await?;
+        Ok(Self { file })
+    }
+}
+
+impl SlideshowFormat for HtmlWriter {
+    async fn write_header(&mut self, header: &FormatHeader<'_>) -> Result<()> {
+        self.file.write_all(HEAD.replace("{title}", &escape_html(header.title)).as_bytes()).await?;
+        Ok(())
+    }
+
+    // images which can't be decoded, e.g. videos, are listed without the thumbnail
+    // the gallery is browsed by hand, so there's no duration, and the caption is the info or the file name
+    async fn write_entry(&mut self, path: &Path, entry_options: &EntryOptions<'_>) -> Result<()> {
+        let name = escape_html(&path.file_name().unwrap_or(path.as_os_str()).to_string_lossy());
+        let caption = entry_options.info.map(escape_html).unwrap_or_else(|| name.clone());
+        let figure = match read_thumbnail_and_preview(path.to_path_buf()).await {
+            Ok((thumbnail, preview)) => format!(
+                "<figure data-preview=\"data:image/jpeg;base64,{}\"><img src=\"data:image/jpeg;base64,{}\" alt=\"{}\"><figcaption>{}</figcaption></figure>\n",
+                preview, thumbnail, name, caption,
+            ),
+            Err(e) => {
+                warn!("Failed to make the thumbnail, list it without: {}: {:?}", path.display(), e);
+                format!("<figure><figcaption>{}</figcaption></figure>\n", caption)
+            }
+        };
+        self.file.write_all(figure.as_bytes()).await?;
+        Ok(())
+    }
+
+    async fn finish(&mut self) -> Result<()> {
+        self.file.write_all(TAIL.as_bytes()).await?;
+        self.file.flush().await?;
+        Ok(())
+    }
+}
+
+// base64 jpegs
+async fn read_thumbnail_and_preview(path: PathBuf) -> Result<(String, String)> {
+    task::spawn_blocking(move || {
+        let img = image::open(path)?;
+        let encode = |img: &image::DynamicImage| -> Result<String> {
+            let mut bytes = Cursor::new(Vec::new());
+            img.to_rgb8().write_to(&mut bytes, ImageFormat::Jpeg)?;
+            Ok(BASE64.encode(bytes.into_inner()))
+        };
+        let preview = img.thumbnail(PREVIEW_SIZE, PREVIEW_SIZE);
+        let thumbnail = preview.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+        Ok((encode(&thumbnail)?, encode(&preview)?))
+    }).await?
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
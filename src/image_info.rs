@@ -0,0 +1,752 @@
+use std::{fs::Metadata, io::{Read, Seek, SeekFrom}, path::{Path, PathBuf}, time::SystemTime};
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Local, TimeZone};
+use nom_exif::{AsyncMediaParser, AsyncMediaSource, EntryValue, ExifIter, ExifTag, GPSInfo, LatLng, TrackInfo, TrackInfoTag, URational};
+use tokio::task;
+use image::{self, AnimationDecoder, GenericImageView, ImageReader, codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder}};
+use anyhow::Result;
+use tracing::{debug, warn};
+use crate::{Error, cache::{CacheKey, CacheOptions, cache_image_info, cached_image_info}, catalog::CatalogEntry, exif_fallback::read_fallback_exif, geocode, heif, iptc, raw, xmp};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImageInfo {
+    pub path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    pub creation_date_time: NaiveDateTime,
+    // all the dates the creation date was chosen from, with where each of them came from
+    #[serde(default)]
+    pub date_time_candidates: Vec<DateTimeCandidate>,
+    // difference hash of the image, only computed when needed
+    #[serde(default)]
+    pub dhash: Option<u64>,
+    // variance of the laplacian of the luma, low when blurry, only computed when needed
+    #[serde(default)]
+    pub sharpness: Option<f64>,
+    // mean luma from 0 to 1, e.g. low for a pitch-black frame
+    #[serde(default)]
+    pub brightness: Option<f64>,
+    // by the face model, only computed when needed
+    #[serde(default)]
+    pub has_faces: Option<bool>,
+    // mean chroma from 0 to 1, near 0 for black and white, only computed when needed
+    #[serde(default)]
+    pub saturation: Option<f64>,
+    // gif, webp and png with more than one frame, XnView plays them oddly in a slideshow
+    #[serde(default)]
+    pub animated: bool,
+    // false when the file ends before the end marker, e.g. partially copied, only checked when needed
+    #[serde(default)]
+    pub intact: Option<bool>,
+    // videos are read from their track info instead of decoded
+    #[serde(default)]
+    pub is_video: bool,
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+    // of the image file when this was made, the cache entry is stale when they no longer match
+    #[serde(default)]
+    pub source_modified: Option<SystemTime>,
+    #[serde(default)]
+    pub source_size: Option<u64>,
+    // exif orientation, width and height are of the stored pixels, before the rotation
+    #[serde(default)]
+    pub orientation: Option<u16>,
+    #[serde(default)]
+    pub camera_make: Option<String>,
+    #[serde(default)]
+    pub camera_model: Option<String>,
+    #[serde(default)]
+    pub lens_model: Option<String>,
+    #[serde(default)]
+    pub gps_position: Option<GpsPosition>,
+    // of the nearest city to the gps position, the country as the ISO 3166 code, e.g. "JP"
+    #[serde(default)]
+    pub country: Option<String>,
+    #[serde(default)]
+    pub city: Option<String>,
+    // from the xmp sidecar or the embedded xmp
+    #[serde(default)]
+    pub rating: Option<i32>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    // e.g. "Red", of XnView or Lightroom
+    #[serde(default)]
+    pub color_label: Option<String>,
+    // e.g. of Google Takeout
+    #[serde(default)]
+    pub description: Option<String>,
+    // the sidecar is edited without touching the image, so the cache entry is stale when this no longer matches
+    #[serde(default)]
+    pub xmp_sidecar_modified: Option<SystemTime>,
+    // of nom_exif when kamadak-exif couldn't read the exif either
+    #[serde(default)]
+    pub exif_error: Option<String>,
+    // read from the cache in this run, only for the stats
+    #[serde(skip)]
+    pub from_cache: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DateSource {
+    Exif,
+    // the track info of videos
+    Track,
+    Ctime,
+    Mtime,
+    // the file name or the dir names, never cached as the patterns are per slideshow
+    #[serde(alias = "filename")]
+    Path,
+    // photoTakenTime of the json sidecar of Google Takeout
+    Takeout,
+    // the date of the xmp, only read when neither exif parser could read the exif
+    Xmp,
+    // the capture date of XnView.db, of the exif as XnView read it
+    Catalog,
+}
+
+// in degrees, south and west are negative
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct GpsPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DateTimeCandidate {
+    pub source: DateSource,
+    // the wall clock time where it was taken, or of the machine for the file times
+    pub date_time: NaiveDateTime,
+    // from utc in seconds, none when unknown, e.g. exif without OffsetTime
+    #[serde(default)]
+    pub offset_secs: Option<i32>,
+}
+
+impl DateTimeCandidate {
+    pub fn to_fixed_offset(&self) -> Option<DateTime<FixedOffset>> {
+        let offset = FixedOffset::east_opt(self.offset_secs?)?;
+        self.date_time.and_local_timezone(offset).single()
+    }
+}
+
+// what is computed from the decoded pixels, only when a slideshow needs it as it's slow
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AnalysisOptions {
+    pub dhash: bool,
+    pub quality: bool,
+    pub faces: bool,
+    pub saturation: bool,
+    pub verify: bool,
+}
+
+impl AnalysisOptions {
+    // otherwise only the header is read for the size
+    pub fn needs_decode(&self) -> bool {
+        self.dhash || self.quality || self.faces || self.saturation || self.verify
+    }
+}
+
+impl ImageInfo {
+    pub async fn from_path(path: impl AsRef<Path>, cache_options: &CacheOptions, analysis_options: AnalysisOptions) -> Result<Self> {
+        let metadata = tokio::fs::metadata(path.as_ref()).await?;
+        let xmp_sidecar = xmp::find_sidecar(path.as_ref()).await;
+        let xmp_sidecar_modified = xmp_sidecar.as_ref().map(|(_, modified)| *modified);
+        if cache_options.read {
+            if let Some(mut image_info) = cached_image_info(path.as_ref(), cache_options).await {
+                // the content key already means the same content, and mtime differs among copies,
+                // while the fingerprint is of the head only, and a move keeps mtime
+                let check_modified = !matches!(cache_options.key, CacheKey::Content);
+                if image_info.is_usable_cache(analysis_options, &metadata, check_modified) && image_info.xmp_sidecar_modified == xmp_sidecar_modified {
+                    // the entry may have been written for the same file at another path
+                    image_info.path = path.as_ref().to_path_buf();
+                    image_info.from_cache = true;
+                    debug!("cache hit");
+                    return Ok(image_info);
+                }
+                debug!("stale cache entry");
+            }
+        }
+
+        let path = path.as_ref();
+        // use the most old date for the creation date (exif, ctime, mtime)
+        let mut date_time_candidates: Vec<DateTimeCandidate> = Vec::new();
+
+        let creation_time = metadata.created()?;
+        date_time_candidates.push(get_local_date_time_candidate(DateSource::Ctime, creation_time)?);
+
+        let modification_time = metadata.modified()?;
+        date_time_candidates.push(get_local_date_time_candidate(DateSource::Mtime, modification_time)?);
+
+        let mut media_parser = AsyncMediaParser::new();
+        let ms = AsyncMediaSource::file_path(path).await?;
+        let mut track_info: Option<TrackInfo> = None;
+        let mut orientation: Option<u16> = None;
+        let mut camera_make: Option<String> = None;
+        let mut camera_model: Option<String> = None;
+        let mut lens_model: Option<String> = None;
+        let mut gps_position: Option<GpsPosition> = None;
+        // only used for raw files, which are not decoded
+        let mut exif_width: Option<u32> = None;
+        let mut exif_height: Option<u32> = None;
+        // of nom_exif, kept only when the fallback fails too
+        let mut exif_error: Option<String> = None;
+        if ms.has_track() {
+            let info: TrackInfo = media_parser.parse(ms).await?;
+            gps_position = info.get(TrackInfoTag::GpsIso6709).and_then(|value| match value {
+                EntryValue::Text(text) => parse_iso6709(text),
+                _ => None,
+            });
+            if let Some(date_time) = info.get(TrackInfoTag::CreateDate).and_then(|value| value.as_time()) {
+                date_time_candidates.push(DateTimeCandidate {
+                    source: DateSource::Track,
+                    date_time: date_time.naive_local(),
+                    offset_secs: Some(date_time.offset().local_minus_utc()),
+                });
+            }
+            track_info = Some(info);
+        } else if ms.has_exif() {
+            let iter: Result<ExifIter, _> = media_parser.parse(ms).await;
+            match iter {
+                Ok(iter) => {
+                    match iter.parse_gps_info() {
+                        Ok(gps_info) => gps_position = gps_info.as_ref().map(gps_position_from_gps_info),
+                        Err(e) => warn!("Failed to parse gps info, ignore it: {}: {:?}", path.display(), e),
+                    }
+                    // the offsets may come after the dates, so they are paired after all the tags are read
+                    let mut exif_date_times: Vec<(ExifTag, NaiveDateTime)> = Vec::new();
+                    let mut offset_time: Option<i32> = None;
+                    let mut offset_time_original: Option<i32> = None;
+                    let mut offset_time_digitized: Option<i32> = None;
+                    for exif in iter {
+                        let Some(tag) = exif.tag() else {
+                            // unknown tag, not error
+                            continue;
+                        };
+                        match tag {
+                            ExifTag::DateTimeOriginal |
+                                ExifTag::CreateDate |
+                                ExifTag::ModifyDate => {
+
+                                let Some(value) = exif.get_value() else {
+                                    // just empty, not error
+                                    continue;
+                                };
+                                let date_time = value.as_time().ok_or_else(|| Error::ExifTimeError(path.to_path_buf(), tag.to_string(), value.to_string()))?;
+                                exif_date_times.push((tag, date_time.naive_local()));
+                            }
+                            ExifTag::OffsetTime | ExifTag::OffsetTimeOriginal | ExifTag::OffsetTimeDigitized => {
+                                let Some(EntryValue::Text(text)) = exif.get_value() else {
+                                    continue;
+                                };
+                                let offset = parse_offset_secs(text);
+                                match tag {
+                                    ExifTag::OffsetTime => offset_time = offset,
+                                    ExifTag::OffsetTimeOriginal => offset_time_original = offset,
+                                    _ => offset_time_digitized = offset,
+                                }
+                            }
+                            ExifTag::ExifImageWidth | ExifTag::ExifImageHeight => {
+                                let size = match exif.get_value() {
+                                    Some(EntryValue::U16(value)) => Some(*value as u32),
+                                    Some(EntryValue::U32(value)) => Some(*value),
+                                    _ => None,
+                                };
+                                if matches!(tag, ExifTag::ExifImageWidth) {
+                                    exif_width = size;
+                                } else {
+                                    exif_height = size;
+                                }
+                            }
+                            ExifTag::Orientation => {
+                                if let Some(EntryValue::U16(value)) = exif.get_value() {
+                                    orientation = Some(*value);
+                                }
+                            }
+                            ExifTag::Make | ExifTag::Model | ExifTag::LensModel => {
+                                let Some(EntryValue::Text(text)) = exif.get_value() else {
+                                    continue;
+                                };
+                                // padded with spaces or nul by some cameras
+                                let text = text.trim_matches(|c: char| c.is_whitespace() || c == '\0').to_string();
+                                match tag {
+                                    ExifTag::Make => camera_make = Some(text),
+                                    ExifTag::Model => camera_model = Some(text),
+                                    _ => lens_model = Some(text),
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    for (tag, date_time) in exif_date_times {
+                        let offset_secs = match tag {
+                            ExifTag::DateTimeOriginal => offset_time_original,
+                            ExifTag::CreateDate => offset_time_digitized,
+                            _ => None,
+                        };
+                        date_time_candidates.push(DateTimeCandidate {
+                            source: DateSource::Exif,
+                            date_time,
+                            offset_secs: offset_secs.or(offset_time),
+                        });
+                    }
+                },
+                Err(e) => {
+                    // tried again by kamadak-exif, and the xmp after that
+                    let fallback_path = path.to_path_buf();
+                    match task::spawn_blocking(move || read_fallback_exif(&fallback_path)).await? {
+                        Ok(fallback_exif) if !fallback_exif.date_times.is_empty() => {
+                            debug!("exif read by the fallback: {:?}", e);
+                            date_time_candidates.extend(fallback_exif.date_times.into_iter().map(|date_time| DateTimeCandidate {
+                                source: DateSource::Exif,
+                                date_time,
+                                offset_secs: None,
+                            }));
+                            orientation = fallback_exif.orientation;
+                            camera_make = fallback_exif.camera_make;
+                            camera_model = fallback_exif.camera_model;
+                            lens_model = fallback_exif.lens_model;
+                        }
+                        _ => {
+                            warn!("Failed to parse exif, ignore exif info: {}: {:?}", path.display(), e);
+                            exif_error = Some(format!("{:?}", e));
+                        }
+                    }
+                }
+            }
+        }
+
+        if date_time_candidates.is_empty() {
+            return Err(Error::NoCreationDateError(path.to_path_buf()).into());
+        }
+
+        let (rating, keywords, xmp_description, color_label, xmp_date_time) = match xmp::read_xmp(path, xmp_sidecar.as_ref().map(|(sidecar_path, _)| sidecar_path.as_path())).await {
+            Ok(Some(xmp_metadata)) => (xmp_metadata.rating, xmp_metadata.keywords, xmp_metadata.description, xmp_metadata.label, xmp_metadata.date_time),
+            Ok(None) => (None, vec![], None, None, None),
+            Err(e) => {
+                // ignore error
+                warn!("Failed to read xmp, ignore xmp info: {}: {:?}", path.display(), e);
+                (None, vec![], None, None, None)
+            }
+        };
+        // the last of the chain, so that the editors' dates don't compete with a readable exif
+        if let (Some(_), Some(date_time)) = (&exif_error, xmp_date_time) {
+            date_time_candidates.push(DateTimeCandidate {
+                source: DateSource::Xmp,
+                date_time,
+                offset_secs: None,
+            });
+        }
+        // the xmp wins, as the DAMs keep the iptc in sync with it only when writing both
+        let description = match xmp_description {
+            Some(description) => Some(description),
+            None if track_info.is_none() => match iptc::read_iptc_caption(path).await {
+                Ok(description) => description,
+                Err(e) => {
+                    // ignore error
+                    warn!("Failed to read iptc, ignore iptc info: {}: {:?}", path.display(), e);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let creation_date_time = date_time_candidates.iter().map(|candidate| candidate.date_time).min().expect("checked not empty");
+        let mut quality = None;
+        let mut has_faces = None;
+        let mut saturation = None;
+        let mut intact = None;
+        let mut animated = false;
+        let (width, height, dhash, duration_ms) = match &track_info {
+            Some(track_info) => {
+                let width = track_info.get(TrackInfoTag::ImageWidth).and_then(|value| value.as_u32());
+                let height = track_info.get(TrackInfoTag::ImageHeight).and_then(|value| value.as_u32());
+                let (Some(width), Some(height)) = (width, height) else {
+                    return Err(Error::VideoSizeError(path.to_path_buf()).into());
+                };
+                let duration_ms = track_info.get(TrackInfoTag::DurationMs).and_then(|value| value.as_u64());
+                (width, height, None, duration_ms)
+            }
+            // not decoded, so no hash either
+            None if raw::is_raw_path(path) => {
+                let (width, height) = match (exif_width, exif_height) {
+                    (Some(width), Some(height)) => (width, height),
+                    _ => raw::read_raw_preview_size(path).await?,
+                };
+                (width, height, None, None)
+            }
+            None if heif::is_heif_path(path) => {
+                let (width, height) = heif::read_heif_size(path).await?;
+                (width, height, None, None)
+            }
+            None => {
+                let decoded_info = read_decoded_info(path, analysis_options).await?;
+                quality = decoded_info.quality;
+                has_faces = decoded_info.has_faces;
+                saturation = decoded_info.saturation;
+                intact = decoded_info.intact;
+                animated = decoded_info.animated;
+                (decoded_info.width, decoded_info.height, decoded_info.dhash, None)
+            }
+        };
+        let (country, city) = gps_position.map(geocode::place).unzip();
+        let result = Self {
+            path: path.to_path_buf(),
+            width,
+            height,
+            creation_date_time,
+            date_time_candidates,
+            dhash,
+            sharpness: quality.map(|(sharpness, _)| sharpness),
+            brightness: quality.map(|(_, brightness)| brightness),
+            has_faces,
+            saturation,
+            intact,
+            animated,
+            is_video: track_info.is_some(),
+            duration_ms,
+            source_modified: Some(modification_time),
+            source_size: Some(metadata.len()),
+            orientation,
+            camera_make,
+            camera_model,
+            lens_model,
+            gps_position,
+            country,
+            city,
+            rating,
+            keywords,
+            color_label,
+            description,
+            xmp_sidecar_modified,
+            exif_error,
+            from_cache: false,
+        };
+
+        // cache the result to local
+        if cache_options.write {
+            cache_image_info(&result, cache_options).await?;
+        }
+
+        Ok(result)
+    }
+
+    // of the size and the capture date of the catalog without opening the file, none unless it has both
+    // not cached, as the exif of the file has more, e.g. the camera and the gps
+    pub async fn from_catalog_entry(path: impl AsRef<Path>, catalog_entry: &CatalogEntry) -> Result<Option<Self>> {
+        let (Some(width), Some(height), Some(capture_date_time)) = (catalog_entry.width, catalog_entry.height, catalog_entry.capture_date_time) else {
+            return Ok(None);
+        };
+        let path = path.as_ref();
+        let metadata = tokio::fs::metadata(path).await?;
+        let modification_time = metadata.modified()?;
+        let date_time_candidates = vec![
+            get_local_date_time_candidate(DateSource::Ctime, metadata.created()?)?,
+            get_local_date_time_candidate(DateSource::Mtime, modification_time)?,
+            DateTimeCandidate {
+                source: DateSource::Catalog,
+                date_time: capture_date_time,
+                offset_secs: None,
+            },
+        ];
+        Ok(Some(Self {
+            path: path.to_path_buf(),
+            width,
+            height,
+            creation_date_time: capture_date_time,
+            date_time_candidates,
+            dhash: None,
+            sharpness: None,
+            brightness: None,
+            has_faces: None,
+            saturation: None,
+            intact: None,
+            animated: false,
+            is_video: false,
+            duration_ms: None,
+            source_modified: Some(modification_time),
+            source_size: Some(metadata.len()),
+            orientation: None,
+            camera_make: None,
+            camera_model: None,
+            lens_model: None,
+            gps_position: None,
+            country: None,
+            city: None,
+            rating: None,
+            keywords: vec![],
+            color_label: None,
+            description: None,
+            xmp_sidecar_modified: None,
+            exif_error: None,
+            from_cache: false,
+        }))
+    }
+
+    // e.g. of the checkpoint of an interrupted run, unless the file or its sidecar has changed since
+    pub async fn is_unchanged(&self, analysis_options: AnalysisOptions) -> bool {
+        let Ok(metadata) = tokio::fs::metadata(&self.path).await else {
+            return false;
+        };
+        let xmp_sidecar_modified = xmp::find_sidecar(&self.path).await.map(|(_, modified)| modified);
+        self.is_usable_cache(analysis_options, &metadata, true) && self.xmp_sidecar_modified == xmp_sidecar_modified
+    }
+
+    // old cache entries may lack the fields added later
+    fn is_usable_cache(&self, analysis_options: AnalysisOptions, metadata: &Metadata, check_modified: bool) -> bool {
+        if self.date_time_candidates.is_empty() || !self.has_analyses(analysis_options) {
+            return false;
+        }
+        if self.source_size != Some(metadata.len()) {
+            return false;
+        }
+        !check_modified || (self.source_modified.is_some() && self.source_modified == metadata.modified().ok())
+    }
+
+    // as displayed, orientations 5 to 8 rotate by 90 degrees
+    pub fn displayed_size(&self) -> (u32, u32) {
+        match self.orientation {
+            Some(5..=8) => (self.height, self.width),
+            _ => (self.width, self.height),
+        }
+    }
+
+    pub fn aspect_ratio(&self) -> f64 {
+        let (width, height) = self.displayed_size();
+        width as f64 / height as f64
+    }
+
+    // e.g. parsed for a slideshow without dedupe, and needed by another with it
+    pub fn has_analyses(&self, analysis_options: AnalysisOptions) -> bool {
+        // videos, raw and heif images are never decoded
+        if self.is_video || raw::is_raw_path(&self.path) || heif::is_heif_path(&self.path) {
+            return true;
+        }
+        (!analysis_options.dhash || self.dhash.is_some())
+            && (!analysis_options.quality || self.sharpness.is_some())
+            && (!analysis_options.faces || self.has_faces.is_some())
+            && (!analysis_options.saturation || self.saturation.is_some())
+            && (!analysis_options.verify || self.intact.is_some())
+    }
+
+    // the model often repeats the make, e.g. "Canon" and "Canon EOS R5"
+    pub fn camera(&self) -> Option<String> {
+        match (self.camera_make.as_deref(), self.camera_model.as_deref()) {
+            (Some(make), Some(model)) if model.starts_with(make) => Some(model.to_string()),
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+            (Some(make), None) => Some(make.to_string()),
+            (None, Some(model)) => Some(model.to_string()),
+            (None, None) => None,
+        }
+    }
+
+    // the track info of videos and the xmp count too, as they're embedded in the file or its sidecar as well
+    pub fn has_exif_date(&self) -> bool {
+        self.date_time_candidates.iter().any(|candidate| matches!(candidate.source, DateSource::Exif | DateSource::Track | DateSource::Xmp | DateSource::Catalog))
+    }
+
+    // the exif was there but none of the parsers could read it, nor the xmp had a date
+    pub fn lacks_readable_metadata(&self) -> bool {
+        self.exif_error.is_some() && !self.has_exif_date()
+    }
+}
+
+fn gps_position_from_gps_info(gps_info: &GPSInfo) -> GpsPosition {
+    let value = |rational: &URational| if rational.1 == 0 { 0.0 } else { rational.0 as f64 / rational.1 as f64 };
+    // degrees, minutes and seconds
+    let degrees = |lat_lng: &LatLng| value(&lat_lng.0) + value(&lat_lng.1) / 60.0 + value(&lat_lng.2) / 3600.0;
+    let latitude = degrees(&gps_info.latitude);
+    let longitude = degrees(&gps_info.longitude);
+    GpsPosition {
+        latitude: if gps_info.latitude_ref == 'S' { -latitude } else { latitude },
+        longitude: if gps_info.longitude_ref == 'W' { -longitude } else { longitude },
+    }
+}
+
+// videos have it like "+35.0116+135.7681+050.000/", only the decimal degrees form is supported
+fn parse_iso6709(text: &str) -> Option<GpsPosition> {
+    let text = text.trim_end_matches('/');
+    let mut starts = text.char_indices().filter(|(_, c)| *c == '+' || *c == '-').map(|(i, _)| i);
+    let latitude_start = starts.next()?;
+    let longitude_start = starts.next()?;
+    let longitude_end = starts.next().unwrap_or(text.len());
+    let latitude = text[latitude_start..longitude_start].parse().ok()?;
+    let longitude = text[longitude_start..longitude_end].parse().ok()?;
+    Some(GpsPosition { latitude, longitude })
+}
+
+fn get_local_date_time_candidate(source: DateSource, system_time: SystemTime) -> Result<DateTimeCandidate> {
+    let system_time = system_time.duration_since(SystemTime::UNIX_EPOCH)?;
+    let system_time = Local.timestamp_opt(system_time.as_secs() as i64, system_time.subsec_nanos()).earliest().ok_or_else(|| Error::SystemTimeError(system_time.as_secs().to_string()))?;
+    Ok(DateTimeCandidate {
+        source,
+        date_time: system_time.naive_local(),
+        offset_secs: Some(system_time.offset().local_minus_utc()),
+    })
+}
+
+// e.g. "+09:00" or "-05:30"
+fn parse_offset_secs(text: &str) -> Option<i32> {
+    let text = text.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    let sign = match text.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let (hours, minutes) = text[1..].split_once(':')?;
+    Some(sign * (hours.parse::<i32>().ok()? * 3600 + minutes.parse::<i32>().ok()? * 60))
+}
+
+struct DecodedInfo {
+    width: u32,
+    height: u32,
+    dhash: Option<u64>,
+    // (sharpness, brightness)
+    quality: Option<(f64, f64)>,
+    has_faces: Option<bool>,
+    saturation: Option<f64>,
+    intact: Option<bool>,
+    animated: bool,
+}
+
+async fn read_decoded_info(path: impl Into<PathBuf>, analysis_options: AnalysisOptions) -> Result<DecodedInfo> {
+    let path = path.into();
+    task::spawn_blocking(move || {
+        if !analysis_options.needs_decode() {
+            // the full decode is the fallback for the headers the probe can't read
+            let header_size = ImageReader::open(&path)
+                .and_then(|reader| reader.with_guessed_format())
+                .ok()
+                .and_then(|reader| reader.into_dimensions().ok());
+            if let Some((width, height)) = header_size {
+                let animated = is_animated(&path)?;
+                return Ok(DecodedInfo { width, height, dhash: None, quality: None, has_faces: None, saturation: None, intact: None, animated });
+            }
+        }
+        let img = image::open(&path)?;
+        let (width, height) = img.dimensions();
+        // reuse the decoded image, so that the analyses don't need a second decode
+        let dhash = if analysis_options.dhash { Some(dhash(&img)) } else { None };
+        let quality = if analysis_options.quality { Some(quality(&img)) } else { None };
+        let has_faces = if analysis_options.faces { contains_faces(&img)? } else { None };
+        let saturation = if analysis_options.saturation { saturation(&img) } else { None };
+        let intact = if analysis_options.verify { Some(is_intact(&path)?) } else { None };
+        let animated = is_animated(&path)?;
+        Ok(DecodedInfo { width, height, dhash, quality, has_faces, saturation, intact, animated })
+    }).await?
+}
+
+// by the container, without decoding the frames after the second one
+fn is_animated(path: &Path) -> Result<bool> {
+    let extension = path.extension().map(|extension| extension.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+    let reader = || -> Result<std::io::BufReader<std::fs::File>> { Ok(std::io::BufReader::new(std::fs::File::open(path)?)) };
+    let animated = match extension.as_str() {
+        "gif" => GifDecoder::new(reader()?)?.into_frames().take(2).count() > 1,
+        "webp" => WebPDecoder::new(reader()?)?.has_animation(),
+        "png" | "apng" => PngDecoder::new(reader()?)?.is_apng()?,
+        _ => false,
+    };
+    Ok(animated)
+}
+
+// the bytes the end marker is looked for in, after the padding some writers add
+const TAIL_BYTES: u64 = 1024;
+const PNG_IEND: [u8; 8] = [0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82];
+
+// the decoder fills a truncated jpeg with gray instead of failing, so the end marker is checked,
+// the other formats fail to decode when broken
+fn is_intact(path: &Path) -> Result<bool> {
+    let mut file = std::fs::File::open(path)?;
+    let size = file.metadata()?.len();
+    file.seek(SeekFrom::Start(size.saturating_sub(TAIL_BYTES)))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+    let end = tail.iter().rposition(|byte| *byte != 0x00 && !byte.is_ascii_whitespace()).map_or(0, |i| i + 1);
+    let tail = &tail[..end];
+    let extension = path.extension().map(|extension| extension.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+    Ok(match extension.as_str() {
+        "jpg" | "jpeg" | "jpe" => tail.ends_with(&[0xff, 0xd9]),
+        "png" => tail.ends_with(&PNG_IEND),
+        _ => true,
+    })
+}
+
+#[cfg(feature = "faces")]
+fn contains_faces(img: &image::DynamicImage) -> Result<Option<bool>> {
+    crate::faces::contains_faces(img)
+}
+
+// the config problems tell that the build lacks it
+#[cfg(not(feature = "faces"))]
+fn contains_faces(_img: &image::DynamicImage) -> Result<Option<bool>> {
+    Ok(None)
+}
+
+// the longer side the saturation is measured at, the tint of a whole image shows at a small size
+#[cfg(feature = "monochrome")]
+const SATURATION_SIZE: u32 = 256;
+
+// the mean of max(r, g, b) - min(r, g, b) rather than the hsv saturation, which is high for the noise of dark pixels
+#[cfg(feature = "monochrome")]
+fn saturation(img: &image::DynamicImage) -> Option<f64> {
+    // no color to measure
+    if !img.color().has_color() {
+        return Some(0.0);
+    }
+    let rgb = img.resize(SATURATION_SIZE, SATURATION_SIZE, image::imageops::FilterType::Triangle).to_rgb8();
+    let (width, height) = rgb.dimensions();
+    let chroma_sum = rgb.pixels().map(|pixel| {
+        let [r, g, b] = pixel.0;
+        (r.max(g).max(b) - r.min(g).min(b)) as f64
+    }).sum::<f64>();
+    Some(chroma_sum / (width as f64 * height as f64).max(1.0) / 255.0)
+}
+
+// the config problems tell that the build lacks it
+#[cfg(not(feature = "monochrome"))]
+fn saturation(_img: &image::DynamicImage) -> Option<f64> {
+    None
+}
+
+// the longer side the quality is measured at, so that the scores compare across resolutions
+const QUALITY_SIZE: u32 = 1024;
+
+// (sharpness, brightness), the sharpness is the variance of the 4-neighbor laplacian
+pub fn quality(img: &image::DynamicImage) -> (f64, f64) {
+    // only downscaled, as upscaling a small one blurs it to look less sharp
+    let luma = if img.width() > QUALITY_SIZE || img.height() > QUALITY_SIZE {
+        img.resize(QUALITY_SIZE, QUALITY_SIZE, image::imageops::FilterType::Triangle).to_luma8()
+    } else {
+        img.to_luma8()
+    };
+    let (width, height) = luma.dimensions();
+    let brightness = luma.pixels().map(|pixel| pixel[0] as f64).sum::<f64>() / (width as f64 * height as f64).max(1.0) / 255.0;
+    if width < 3 || height < 3 {
+        return (0.0, brightness);
+    }
+    let at = |x: u32, y: u32| luma.get_pixel(x, y)[0] as f64;
+    let mut sum = 0.0;
+    let mut sum_of_squares = 0.0;
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let laplacian = at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1) - 4.0 * at(x, y);
+            sum += laplacian;
+            sum_of_squares += laplacian * laplacian;
+        }
+    }
+    let n = ((width - 2) * (height - 2)) as f64;
+    let mean = sum / n;
+    (sum_of_squares / n - mean * mean, brightness)
+}
+
+pub fn dhash(img: &image::DynamicImage) -> u64 {
+    let small = img.resize_exact(9, 8, image::imageops::FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y)[0];
+            let right = small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | (left > right) as u64;
+        }
+    }
+    hash
+}
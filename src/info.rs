@@ -0,0 +1,30 @@
+use crate::image_info::ImageInfo;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const TIME_FORMAT: &str = "%H:%M";
+
+// e.g. "{date} {folder}" is "2019-07-14 Summer", none when nothing is left, e.g. of "{description}" without it,
+// {country} and {city} are of the gps position, unknown placeholders are left as is, e.g. XnView's own {Filename}
+pub fn info_text(template: &str, image_info: &ImageInfo) -> Option<String> {
+    let date_time = image_info.creation_date_time;
+    let file_name = image_info.path.file_name().map(|file_name| file_name.to_string_lossy().to_string()).unwrap_or_default();
+    let folder = image_info.path.parent()
+        .and_then(|parent| parent.file_name())
+        .map(|folder| folder.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let text = template
+        .replace("{date}", &date_time.format(DATE_FORMAT).to_string())
+        .replace("{time}", &date_time.format(TIME_FORMAT).to_string())
+        .replace("{year}", &date_time.format("%Y").to_string())
+        .replace("{filename}", &file_name)
+        .replace("{folder}", &folder)
+        .replace("{camera}", &image_info.camera().unwrap_or_default())
+        .replace("{country}", image_info.country.as_deref().unwrap_or_default())
+        .replace("{city}", image_info.city.as_deref().unwrap_or_default())
+        .replace("{lens}", image_info.lens_model.as_deref().unwrap_or_default())
+        .replace("{rating}", &image_info.rating.map(|rating| "★".repeat(rating.clamp(0, 5) as usize)).unwrap_or_default())
+        .replace("{keywords}", &image_info.keywords.join(", "))
+        .replace("{description}", image_info.description.as_deref().unwrap_or_default());
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
@@ -0,0 +1,35 @@
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+use anyhow::Result;
+
+// the photoshop segment (APP13) is near the start of the file, next to the exif
+const IPTC_SEARCH_BYTES: u64 = 1024 * 1024;
+// record 2, dataset 120 of IPTC-IIM
+const CAPTION_TAG: [u8; 3] = [0x1c, 0x02, 0x78];
+
+// the Caption/Abstract of the IPTC block, written by older DAMs and Photoshop
+pub async fn read_iptc_caption(path: &Path) -> Result<Option<String>> {
+    let mut head = Vec::new();
+    tokio::fs::File::open(path).await?.take(IPTC_SEARCH_BYTES).read_to_end(&mut head).await?;
+    let Some(photoshop_start) = find_bytes(&head, b"Photoshop 3.0\0") else {
+        return Ok(None);
+    };
+    let iptc = &head[photoshop_start..];
+    let Some(tag_start) = find_bytes(iptc, &CAPTION_TAG) else {
+        return Ok(None);
+    };
+    let Some(length) = iptc.get(tag_start + 3..tag_start + 5) else {
+        return Ok(None);
+    };
+    let length = u16::from_be_bytes([length[0], length[1]]) as usize;
+    let Some(caption) = iptc.get(tag_start + 5..tag_start + 5 + length) else {
+        return Ok(None);
+    };
+    // utf-8 by the newer writers, lossy for the latin-1 of the older ones
+    let caption = String::from_utf8_lossy(caption).trim_matches(|c: char| c.is_whitespace() || c == '\0').to_string();
+    Ok((!caption.is_empty()).then_some(caption))
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
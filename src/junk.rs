@@ -0,0 +1,60 @@
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use crate::filter::FileSize;
+
+// the clutter of nas and os dirs, which junk_file::is_junk misses as it knows only the files
+const DEFAULT_JUNK_NAMES: [&str; 14] = [
+    // synology
+    "@eaDir",
+    "#recycle",
+    "#snapshot",
+    // qnap
+    ".@__thumb",
+    "@Recycle",
+    // linux desktops and the photo managers
+    ".thumbnails",
+    "lost+found",
+    // windows
+    "$RECYCLE.BIN",
+    "System Volume Information",
+    // macos on a share
+    ".AppleDouble",
+    ".AppleDB",
+    ".Trashes",
+    ".Spotlight-V100",
+    ".fseventsd",
+];
+
+// checked in the walk with skip_junk, for both dirs and files, in the config for all the slideshows,
+// and in a slideshow for itself instead
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct JunkRules {
+    // file or dir names compared case-insensitively, the ones above by default
+    pub names: Vec<String>,
+    // on top of names, e.g. to keep the defaults and add a few
+    pub extra_names: Vec<String>,
+    // smaller files are junk, e.g. the thumbnails of the cameras, unless the slideshow has min_file_size
+    pub min_file_size: Option<FileSize>,
+}
+
+impl Default for JunkRules {
+    fn default() -> Self {
+        Self {
+            names: DEFAULT_JUNK_NAMES.iter().map(|name| name.to_string()).collect(),
+            extra_names: vec![],
+            min_file_size: None,
+        }
+    }
+}
+
+impl JunkRules {
+    // lowercase, for is_junk_name
+    pub fn lowercase_names(&self) -> Vec<String> {
+        self.names.iter().chain(&self.extra_names).map(|name| name.to_lowercase()).collect()
+    }
+}
+
+pub fn is_junk_name(path: &Path, lowercase_names: &[String]) -> bool {
+    path.file_name().is_some_and(|file_name| lowercase_names.contains(&file_name.to_string_lossy().to_lowercase()))
+}
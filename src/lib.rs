@@ -0,0 +1,126 @@
+use std::path::PathBuf;
+use thiserror;
+
+pub mod archive;
+pub mod cache;
+pub mod catalog;
+pub mod chapters;
+pub mod clock;
+pub mod collation;
+pub mod config;
+pub mod crop;
+pub mod date;
+pub mod diff;
+pub mod duration;
+pub mod entry_options;
+pub mod exclusive;
+pub mod exif_fallback;
+pub mod export;
+pub mod expression;
+#[cfg(feature = "faces")]
+pub mod faces;
+pub mod ffconcat;
+pub mod filter;
+pub mod format;
+pub mod geocode;
+pub mod heif;
+pub mod hooks;
+pub mod html;
+pub mod image_info;
+pub mod info;
+pub mod iptc;
+pub mod junk;
+pub mod library_stats;
+pub mod live_photo;
+pub mod m3u;
+pub mod monitors;
+pub mod notify;
+pub mod output;
+pub mod output_path;
+pub mod raw;
+pub mod remote;
+pub mod report;
+pub mod scan;
+pub mod schedule;
+#[cfg(all(windows, feature = "screensaver"))]
+pub mod screensaver;
+pub mod selection;
+pub mod slideshow;
+pub mod spill;
+pub mod split;
+pub mod takeout;
+pub mod thumbnails;
+pub mod variants;
+pub mod xmp;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to get exif time: {0} {1} {2}")]
+    ExifTimeError(PathBuf, String, String),
+    #[error("No creation date found: {0}")]
+    NoCreationDateError(PathBuf),
+    #[error("Failed to convert system time to local time: {0}")]
+    SystemTimeError(String),
+    #[error("Failed to get cache dir")]
+    CacheDirError,
+    #[error("Failed to encode in {0}: {1}")]
+    EncodingError(String, String),
+    #[error("No video size found: {0}")]
+    VideoSizeError(PathBuf),
+    #[error("Invalid color, expected \"#RRGGBB\" or \"#RRGGBBAA\": {0}")]
+    ColorError(String),
+    #[error("Unknown timezone, expected a name like \"Asia/Tokyo\": {0}")]
+    TimezoneError(String),
+    #[error("No image size found in the heif container: {0}")]
+    HeifSizeError(PathBuf),
+    #[error("No image size found in the raw file: {0}")]
+    RawSizeError(PathBuf),
+    #[error("Incremental update is not supported by the output format: {0}")]
+    IncrementalUnsupportedError(PathBuf),
+    #[error("ffmpeg failed: {0}")]
+    FfmpegError(String),
+    #[error("Invalid config, {0} problems found")]
+    ConfigError(usize),
+    #[error("Unknown environment variable in the config: {0}")]
+    EnvVarError(String),
+    #[error("Failed to get home dir")]
+    HomeDirError,
+    #[error("Invalid schedule, expected an interval like \"6h\" or a cron expression: {0}: {1}")]
+    ScheduleError(String, String),
+    #[error("Another run holds the lock, remove it if no run is in progress: {0}")]
+    LockedError(PathBuf),
+    #[error("Invalid file size, expected bytes or like \"200KB\" or \"30MB\": {0}")]
+    FileSizeError(String),
+    #[error("Interrupted, continue with --resume")]
+    InterruptedError,
+    #[error("Corrupt or truncated image: {0}")]
+    CorruptImageError(PathBuf),
+    #[error("No slideshow of the name: {0}")]
+    UnknownSlideshowError(String),
+    #[error("The config already exists, overwrite it with --force: {0}")]
+    ConfigExistsError(PathBuf),
+    #[error("Invalid date, expected \"YYYY-MM-DD\" or like \"now-90d\": {0}")]
+    DateBoundError(String),
+    #[error("Hook failed: {0}: {1}")]
+    HookError(String, String),
+    #[error("Unsupported remote url, expected webdav://, s3:// or smb://host/share: {0}")]
+    RemoteUrlError(String),
+    #[error("rclone failed: {0}")]
+    RcloneError(String),
+    #[error("Failed to read the archive: {0}: {1}")]
+    ArchiveError(PathBuf, String),
+    #[error("Invalid collation locale, expected like \"ja\" or \"en-US\": {0}")]
+    LocaleError(String),
+    #[error("No new slideshow given, and the old one is not a backup ending with .bak: {0}")]
+    DiffTargetError(PathBuf),
+    #[error("Invalid filter expression: {0}: {1}")]
+    FilterExpressionError(String, String),
+    #[error("Invalid time of day, expected like \"18:00-23:00\": {0}")]
+    TimeOfDayError(String),
+    #[error("Invalid slideshow at line {1}: {0}: {2}")]
+    SlideshowParseError(PathBuf, usize, String),
+    #[error("Notifier failed: {0}")]
+    NotifierError(String),
+    #[error("Path can't be written in {1}: {0}")]
+    UnencodablePathError(PathBuf, String),
+}
@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+use serde::Serialize;
+use chrono::Datelike;
+use crate::image_info::ImageInfo;
+
+// the upper bounds of the buckets and their names
+const ASPECT_RATIO_BUCKETS: [(f64, &str); 9] = [
+    (0.6, "< 0.6 (tall)"),
+    (0.7, "0.6 - 0.7 (9:16)"),
+    (0.8, "0.7 - 0.8 (2:3)"),
+    (0.95, "0.8 - 0.95 (3:4)"),
+    (1.05, "0.95 - 1.05 (square)"),
+    (1.4, "1.05 - 1.4 (4:3)"),
+    (1.6, "1.4 - 1.6 (3:2)"),
+    (2.0, "1.6 - 2.0 (16:9)"),
+    (f64::INFINITY, ">= 2.0 (panorama)"),
+];
+const MEGAPIXELS_BUCKETS: [(f64, &str); 8] = [
+    (1.0, "< 1 MP"),
+    (4.0, "1 - 4 MP"),
+    (8.0, "4 - 8 MP"),
+    (12.0, "8 - 12 MP"),
+    (16.0, "12 - 16 MP"),
+    (24.0, "16 - 24 MP"),
+    (40.0, "24 - 40 MP"),
+    (f64::INFINITY, ">= 40 MP"),
+];
+// the others are summed up as "others"
+const MAX_CAMERAS: usize = 20;
+
+// what the images of a library look like, for choosing the filter values
+#[derive(Serialize, Debug, Default)]
+pub struct LibraryStats {
+    pub n_images: usize,
+    pub n_videos: usize,
+    pub n_without_exif_date: usize,
+    pub years: BTreeMap<i32, usize>,
+    pub aspect_ratios: BTreeMap<String, usize>,
+    pub cameras: BTreeMap<String, usize>,
+    pub megapixels: BTreeMap<String, usize>,
+}
+
+impl LibraryStats {
+    pub fn add(&mut self, image_info: &ImageInfo) {
+        if image_info.is_video {
+            self.n_videos += 1;
+        } else {
+            self.n_images += 1;
+        }
+        if !image_info.has_exif_date() {
+            self.n_without_exif_date += 1;
+        }
+        *self.years.entry(image_info.creation_date_time.year()).or_default() += 1;
+        *self.aspect_ratios.entry(bucket_name(&ASPECT_RATIO_BUCKETS, image_info.aspect_ratio()).to_string()).or_default() += 1;
+        *self.cameras.entry(image_info.camera().unwrap_or_else(|| "unknown".to_string())).or_default() += 1;
+        let (width, height) = image_info.displayed_size();
+        let megapixels = width as f64 * height as f64 / 1_000_000.0;
+        *self.megapixels.entry(bucket_name(&MEGAPIXELS_BUCKETS, megapixels).to_string()).or_default() += 1;
+    }
+
+    // lines to print, the buckets in their order and the cameras by count
+    pub fn report(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("images: {}", self.n_images),
+            format!("videos: {}", self.n_videos),
+            format!("without exif date: {}", self.n_without_exif_date),
+            "years:".to_string(),
+        ];
+        lines.extend(self.years.iter().map(|(year, count)| format!("  {}: {}", year, count)));
+        lines.push("aspect ratios:".to_string());
+        lines.extend(bucket_lines(&ASPECT_RATIO_BUCKETS, &self.aspect_ratios));
+        lines.push("resolutions:".to_string());
+        lines.extend(bucket_lines(&MEGAPIXELS_BUCKETS, &self.megapixels));
+        lines.push("cameras:".to_string());
+        let mut cameras: Vec<(&String, &usize)> = self.cameras.iter().collect();
+        cameras.sort_by(|(a_name, a_count), (b_name, b_count)| b_count.cmp(a_count).then(a_name.cmp(b_name)));
+        lines.extend(cameras.iter().take(MAX_CAMERAS).map(|(camera, count)| format!("  {}: {}", camera, count)));
+        if cameras.len() > MAX_CAMERAS {
+            let n_others: usize = cameras.iter().skip(MAX_CAMERAS).map(|(_, count)| **count).sum();
+            lines.push(format!("  others: {}", n_others));
+        }
+        lines
+    }
+}
+
+fn bucket_name(buckets: &[(f64, &'static str)], value: f64) -> &'static str {
+    buckets.iter().find(|(upper_bound, _)| value < *upper_bound).map_or(buckets[buckets.len() - 1].1, |(_, name)| name)
+}
+
+// the empty buckets are skipped
+fn bucket_lines(buckets: &[(f64, &'static str)], counts: &BTreeMap<String, usize>) -> Vec<String> {
+    buckets.iter()
+        .filter_map(|(_, name)| counts.get(*name).map(|count| format!("  {}: {}", name, count)))
+        .collect()
+}
@@ -0,0 +1,70 @@
+use std::{collections::HashSet, path::{Path, PathBuf}};
+use serde::{Serialize, Deserialize};
+use regex::bytes::Regex;
+use tokio::io::AsyncReadExt;
+
+const STILL_EXTENSIONS: [&str; 8] = ["heic", "HEIC", "heif", "HEIF", "jpg", "JPG", "jpeg", "JPEG"];
+const MOTION_EXTENSIONS: [&str; 4] = ["mov", "MOV", "mp4", "MP4"];
+// the clips of Live Photos are a few MB, and the content identifier of the stills is in the maker note
+const CONTENT_ID_SEARCH_BYTES: u64 = 16 * 1024 * 1024;
+
+// which one of the still and the motion clip of a Live Photo (or a motion photo) is listed, e.g. IMG_0001.HEIC
+// and IMG_0001.MOV, paired by the stem in the same dir, and by the content identifier of Apple when both have one
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LivePhotoPairing {
+    Still,
+    Motion,
+    #[default]
+    Both,
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension().is_some_and(|extension| extensions.contains(&extension.to_string_lossy().as_ref()))
+}
+
+async fn sibling_path(path: &Path, extensions: &[&str]) -> Option<PathBuf> {
+    for extension in extensions {
+        let sibling_path = path.with_extension(extension);
+        if tokio::fs::try_exists(&sibling_path).await.unwrap_or(false) {
+            return Some(sibling_path);
+        }
+    }
+    None
+}
+
+// whether the path is the one left out of its pair, the motion clips are only listed with include_videos
+pub async fn is_paired_away(path: &Path, live_photo_pairing: LivePhotoPairing) -> bool {
+    let sibling_path = match live_photo_pairing {
+        LivePhotoPairing::Still if has_extension(path, &MOTION_EXTENSIONS) => sibling_path(path, &STILL_EXTENSIONS).await,
+        LivePhotoPairing::Motion if has_extension(path, &STILL_EXTENSIONS) => sibling_path(path, &MOTION_EXTENSIONS).await,
+        _ => None,
+    };
+    match sibling_path {
+        Some(sibling_path) => is_same_content(path, &sibling_path).await,
+        None => false,
+    }
+}
+
+// a plain video next to a photo of the same name has another identifier or none
+async fn is_same_content(path: &Path, sibling_path: &Path) -> bool {
+    let content_ids = read_content_ids(path).await;
+    let sibling_content_ids = read_content_ids(sibling_path).await;
+    if content_ids.is_empty() || sibling_content_ids.is_empty() {
+        return true;
+    }
+    !content_ids.is_disjoint(&sibling_content_ids)
+}
+
+// the uppercase uuids in the file, as the content identifier is written as one by the camera app
+async fn read_content_ids(path: &Path) -> HashSet<Vec<u8>> {
+    let mut data = Vec::new();
+    let Ok(file) = tokio::fs::File::open(path).await else {
+        return HashSet::new();
+    };
+    if file.take(CONTENT_ID_SEARCH_BYTES).read_to_end(&mut data).await.is_err() {
+        return HashSet::new();
+    }
+    let uuid_pattern = Regex::new(r"[0-9A-F]{8}-[0-9A-F]{4}-[0-9A-F]{4}-[0-9A-F]{4}-[0-9A-F]{12}").expect("valid regex");
+    uuid_pattern.find_iter(&data).map(|found| found.as_bytes().to_vec()).collect()
+}
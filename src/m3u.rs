@@ -0,0 +1,83 @@
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use tokio::io::AsyncWriteExt;
+use crate::{format::{FormatHeader, SlideshowFormat}, slideshow::{EntryOptions, ExistingSlideshow, OutputEncoding, UnencodablePaths, encodable_path_text}};
+
+// m3u8 is utf-8 by definition, so there's no encoding option
+pub struct M3uWriter {
+    file: tokio::fs::File,
+    unencodable_paths: UnencodablePaths,
+}
+
+impl M3uWriter {
+    pub async fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let file = tokio::fs::OpenOptions::new().create(true).write(true).truncate(true).open(path.as_ref()).await?;
+        Ok(Self { file, unencodable_paths: UnencodablePaths::default() })
+    }
+
+    // for adding images to an existing playlist, so no header is written
+    pub async fn append_to_path(path: impl AsRef<Path>) -> Result<Self> {
+        let file = tokio::fs::OpenOptions::new().append(true).open(path.as_ref()).await?;
+        Ok(Self { file, unencodable_paths: UnencodablePaths::default() })
+    }
+
+    pub fn with_unencodable_paths(self, unencodable_paths: UnencodablePaths) -> Self {
+        Self { unencodable_paths, ..self }
+    }
+
+    pub async fn write_raw_header(&mut self, header: &str) -> Result<()> {
+        self.file.write_all(header.as_bytes()).await?;
+        Ok(())
+    }
+
+}
+
+impl SlideshowFormat for M3uWriter {
+    async fn write_header(&mut self, _header: &FormatHeader<'_>) -> Result<()> {
+        self.file.write_all(b"#EXTM3U\n").await?;
+        Ok(())
+    }
+
+    // no escaping in m3u, a line is a path as is, preceded by #EXTINF for the duration and the title
+    async fn write_entry(&mut self, path: &Path, entry_options: &EntryOptions<'_>) -> Result<()> {
+        let Some(path) = encodable_path_text(path, OutputEncoding::Utf8, self.unencodable_paths)? else {
+            return Ok(());
+        };
+        let line = if entry_options.duration_secs.is_some() || entry_options.info.is_some() {
+            // -1 is unknown, and a title is a single line
+            let duration = entry_options.duration_secs.map_or("-1".to_string(), |duration_secs| duration_secs.to_string());
+            format!("#EXTINF:{},{}\n{}\n", duration, entry_options.info.unwrap_or_default().replace('\n', " "), path)
+        } else {
+            format!("{}\n", path)
+        };
+        self.file.write_all(line.as_bytes()).await?;
+        Ok(())
+    }
+
+    async fn finish(&mut self) -> Result<()> {
+        self.file.flush().await?;
+        Ok(())
+    }
+}
+
+// the comment lines before the first path are the header, the ones after it are dropped
+pub async fn read_m3u(path: impl AsRef<Path>) -> Result<ExistingSlideshow> {
+    let text = tokio::fs::read_to_string(path).await?;
+    let mut header = String::new();
+    let mut paths = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_start_matches('\u{feff}');
+        if line.starts_with('#') || line.trim().is_empty() {
+            if paths.is_empty() {
+                header.push_str(line);
+                header.push('\n');
+            }
+            continue;
+        }
+        paths.push(PathBuf::from(line));
+    }
+    Ok(ExistingSlideshow {
+        header,
+        paths,
+    })
+}
@@ -1,310 +1,1346 @@
-use std::{path::{Path, PathBuf}, time::SystemTime};
+use std::{collections::HashSet, path::{Path, PathBuf}, sync::{Arc, OnceLock}, time::{Duration, Instant}};
 use jdt;
-use serde::{Serialize, Deserialize};
-use clap::crate_name;
-use chrono::{NaiveDate, NaiveDateTime, Local, TimeZone};
-use nom_exif::{AsyncMediaParser, AsyncMediaSource, ExifIter, ExifTag};
-use tokio::task;
-use image::{self, GenericImageView};
-use dirs::cache_dir;
-use md5;
+use clap::{crate_name, Args, Parser, Subcommand};
 use anyhow::Result;
-use thiserror;
-use junk_file;
-use async_stream::stream;
+use chrono::{DateTime, Datelike, Local, TimeDelta};
 use futures::StreamExt;
+use globset::GlobSet;
+use indicatif::{MultiProgress, ProgressBar};
 use num_cpus;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::Serialize;
+use notify::{EventKind, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+use tracing_subscriber::filter::LevelFilter;
+use make_xnview_slideshow::{
+    Error,
+    cache::{CacheOptions, cache_db_path, cache_stats, clear_cache, flush_cache, prune_cache},
+    catalog::Catalog,
+    chapters::{TitleStyle, render_title},
+    clock,
+    collation::PathComparator,
+    config::{Config, SlideshowConfig},
+    diff::{SlideshowDiff, read_output_paths},
+    exclusive::Claims,
+    export::{ExportFormat, ExportedImage, write_export},
+    hooks::run_hook,
+    library_stats::LibraryStats,
+    monitors::split_by_monitor,
+    notify::{RunSummary, SlideshowResult, notify},
+    heif,
+    image_info::ImageInfo,
+    output::{OutputWriter, read_output},
+    output_path::remove_expired_outputs,
+    raw,
+    remote::fetch_remote_heads,
+    report::{BenchReport, ReportFormat, SlideshowReport},
+    scan::{RateLimiter, ScanMemo, ScanOptions, ScanStats, SkippedFile, build_glob_set, scan_candidates, scan_images},
+    schedule::{RunLock, Schedule},
+    selection::{SortOrder, cap_per_period, dedupe_similar_images, interleave_events, mix_freshness, mix_image_infos, sample_image_infos, sort_image_infos, thin_bursts},
+    slideshow::{OutputEncoding, xnview_path},
+    spill::{SpillSorter, is_spillable},
+    split::{split_image_infos, split_path},
+    thumbnails::{default_thumbnail_dir, pregenerate_thumbnails},
+    variants::{pick_variants, variant_suffix_regex},
+};
 
-#[derive(thiserror::Error, Debug)]
-enum Error {
-    #[error("Failed to get exif time: {0} {1} {2}")]
-    ExifTimeError(PathBuf, String, String),
-    #[error("No creation date found: {0}")]
-    NoCreationDateError(PathBuf),
-    #[error("Failed to convert system time to local time: {0}")]
-    SystemTimeError(String),
-    #[error("Failed to get cache dir")]
-    CacheDirError,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct Config {
-    slideshows: Vec<SlideshowConfig>,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct SlideshowConfig {
-    path: PathBuf,
-    width: u32,
-    height: u32,
-    min_aspect_ratio: f64,
-    max_aspect_ratio: f64,
-    min_creation_date: NaiveDate,
-    max_creation_date: NaiveDate,
-    image_dirs: Vec<PathBuf>,
-}
-
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            slideshows: vec![],
-        }
-    }
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct ImageInfo {
-    path: PathBuf,
-    width: u32,
-    height: u32,
-    creation_date_time: NaiveDateTime,
-}
-
-impl ImageInfo {
-    async fn from_path(path: impl AsRef<Path>) -> Result<Self> {
-        if let Some(image_info) = cached_image_info(path.as_ref()).await {
-            return Ok(image_info);
-        }
-
-        let path = path.as_ref();
-        // use the most old date for the creation date (exif, ctime, mtime)
-        let mut date_time_candidates: Vec<NaiveDateTime> = Vec::new();
-        let metadata = tokio::fs::metadata(path).await?;
-
-        let creation_time = metadata.created()?;
-        date_time_candidates.push(get_local_naive_date_time_from_system_time(creation_time)?);
-
-        let modification_time = metadata.modified()?;
-        date_time_candidates.push(get_local_naive_date_time_from_system_time(modification_time)?);
-
-        let mut media_parser = AsyncMediaParser::new();
-        let ms = AsyncMediaSource::file_path(path).await?;
-        if ms.has_exif() {
-            let iter: Result<ExifIter, _> = media_parser.parse(ms).await;
-            match iter {
-                Ok(iter) => {
-                    for exif in iter {
-                        let Some(tag) = exif.tag() else {
-                            // unknown tag, not error
-                            continue;
-                        };
-                        match tag {
-                            ExifTag::DateTimeOriginal |
-                                ExifTag::CreateDate |
-                                ExifTag::ModifyDate => {
-
-                                let Some(value) = exif.get_value() else {
-                                    // just empty, not error
-                                    continue;
-                                };
-                                let date_time = value.as_time().ok_or_else(|| Error::ExifTimeError(path.to_path_buf(), tag.to_string(), value.to_string()))?;
-                                let date_time = date_time.naive_local();
-                                date_time_candidates.push(date_time);
-                            }
-                            _ => {}
-                        }
-                    }
-                },
-                Err(e) => {
-                    // ignore error
-                    eprintln!("Failed to parse exif, ignore exif info: {}: {:?}", path.display(), e);
-                }
-            }
+// changes are collected until no more come for this long, so that a copy of many files regenerates once
+const WATCH_DEBOUNCE: Duration = Duration::from_secs(2);
+
+// set once from the cli, as it reaches the generation shared by watch and daemon too
+static REPORT_FORMAT: OnceLock<ReportFormat> = OnceLock::new();
+
+// of the slideshows generated at once, so that their spinners don't overwrite each other
+static PROGRESS_BARS: OnceLock<MultiProgress> = OnceLock::new();
+
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Cli {
+    // generate when omitted
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Log why images are skipped or filtered out, -vv for everything
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+    /// Log only errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Print the results as a json object per line, e.g. the counts, the duration and the skipped files of each slideshow
+    #[arg(long, value_enum, global = true, default_value = "text")]
+    output_format: ReportFormat,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Write the slideshows
+    Generate(GenerateArgs),
+    /// Print the images matched by each slideshow without writing it
+    List(ListArgs),
+    /// Manage the image info cache
+    Cache(CacheArgs),
+    /// Regenerate the slideshows whenever their image dirs change
+    Watch(WatchArgs),
+    /// Check the config for the problems which would make empty slideshows
+    Validate(ConfigArgs),
+    /// Stay resident and regenerate each slideshow on its schedule
+    Daemon(DaemonArgs),
+    /// Print the counts by year, aspect ratio, camera and resolution of the images found, ignoring the filters
+    Stats(StatsArgs),
+    /// Write a starter config with one slideshow of the Pictures folder
+    Init(InitArgs),
+    /// Print the images added and removed between two slideshows
+    Diff(DiffArgs),
+}
+
+#[derive(Args, Debug, Default, Clone)]
+struct ConfigArgs {
+    /// Read the config from this json, toml or yaml file instead of the default location
+    #[arg(long)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Default, Clone)]
+struct ScanArgs {
+    /// Neither read nor write the image info cache
+    #[arg(long)]
+    no_cache: bool,
+    /// Ignore existing cache entries, but write fresh ones
+    #[arg(long)]
+    refresh_cache: bool,
+    /// Limit the total size of the images processed at once, instead of just their count
+    #[arg(long)]
+    max_inflight_bytes: Option<u64>,
+    /// Number of images parsed at once, the number of cpus by default
+    #[arg(long)]
+    parse_concurrency: Option<usize>,
+    /// Number of directories read at once, instead of walk_concurrency of each slideshow
+    #[arg(long)]
+    walk_concurrency: Option<usize>,
+    /// Parse at most this many images per second
+    #[arg(long)]
+    max_files_per_sec: Option<f64>,
+    /// Parse at most this many bytes of images per second
+    #[arg(long)]
+    max_bytes_per_sec: Option<u64>,
+    /// Fail on the first unreadable image instead of skipping it
+    #[arg(long)]
+    strict: bool,
+    /// Write the skipped images with the reasons to the file
+    #[arg(long)]
+    error_report: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Default, Clone)]
+struct GenerateArgs {
+    #[command(flatten)]
+    config_args: ConfigArgs,
+    #[command(flatten)]
+    scan_args: ScanArgs,
+    /// Write images in the order they are processed instead of the configured sort order
+    #[arg(long)]
+    fast: bool,
+    /// Print the paths of images whose creation date only comes from file system timestamps
+    #[arg(long)]
+    list_no_exif: bool,
+    /// Append only the images not in the existing slideshow yet, instead of rewriting it
+    #[arg(long)]
+    incremental: bool,
+    /// With --incremental, remove the images deleted from disk from the existing slideshow
+    #[arg(long, requires = "incremental")]
+    prune: bool,
+    /// With --incremental, also remove the images no longer matched, keeping the order and the other edits of the existing slideshow
+    #[arg(long, requires = "incremental")]
+    prune_unmatched: bool,
+    /// Write nothing, but print every image found with the filter which rejected it
+    #[arg(long)]
+    dry_run: bool,
+    /// With --dry-run, print a json object per line instead
+    #[arg(long, requires = "dry_run")]
+    json: bool,
+    /// Write the images written to the slideshows with their metadata to the file
+    #[arg(long, value_name = "FILE")]
+    export: Option<PathBuf>,
+    /// Format of --export, by the extension of the file when omitted
+    #[arg(long, value_enum, requires = "export")]
+    export_format: Option<ExportFormat>,
+    /// Continue an interrupted run without listing the dirs or parsing the images it has read again, unless changed since
+    #[arg(long)]
+    resume: bool,
+    /// Generate only the slideshows of these names, the file stems of their paths unless named
+    #[arg(long, value_name = "NAME")]
+    only: Vec<String>,
+    /// Generate all but the slideshows of these names
+    #[arg(long, value_name = "NAME")]
+    skip: Vec<String>,
+    /// Print the images added and removed since the previous output of each slideshow
+    #[arg(long)]
+    diff: bool,
+    /// Print how long the walk, the parses, the cache hits, the sort and the writes took, to tell which is the bottleneck
+    #[arg(long)]
+    bench: bool,
+    /// After generating, set the newest .sld as the slideshow of the XnView screensaver and the screensaver as the current one
+    #[cfg(all(windows, feature = "screensaver"))]
+    #[arg(long)]
+    install_screensaver: bool,
+}
+
+impl GenerateArgs {
+    // with the exclusive config, the skipped ones no longer take their images from the later ones
+    fn selects(&self, slideshow: &SlideshowConfig) -> bool {
+        let name = slideshow.name();
+        (self.only.is_empty() || self.only.contains(&name)) && !self.skip.contains(&name)
+    }
+}
+
+#[derive(Args, Debug)]
+struct ListArgs {
+    #[command(flatten)]
+    config_args: ConfigArgs,
+    #[command(flatten)]
+    scan_args: ScanArgs,
+}
+
+#[derive(Args, Debug)]
+struct CacheArgs {
+    #[command(flatten)]
+    config_args: ConfigArgs,
+    #[command(subcommand)]
+    command: CacheCommand,
+}
+
+#[derive(Args, Debug)]
+struct WatchArgs {
+    #[command(flatten)]
+    config_args: ConfigArgs,
+    #[command(flatten)]
+    scan_args: ScanArgs,
+}
+
+#[derive(Args, Debug)]
+struct DaemonArgs {
+    #[command(flatten)]
+    config_args: ConfigArgs,
+    #[command(flatten)]
+    scan_args: ScanArgs,
+}
+
+#[derive(Args, Debug)]
+struct StatsArgs {
+    #[command(flatten)]
+    config_args: ConfigArgs,
+    #[command(flatten)]
+    scan_args: ScanArgs,
+    /// Print a json object instead
+    #[arg(long)]
+    json: bool,
+    /// Scan these dirs instead of the image dirs of each slideshow
+    dirs: Vec<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct DiffArgs {
+    /// The older slideshow, e.g. the "family.sld.bak" kept by the backup config
+    old: PathBuf,
+    /// The newer slideshow, the old one without ".bak" when omitted
+    new: Option<PathBuf>,
+    /// Of the .sld files, m3u8 is always utf-8
+    #[arg(long, value_enum, default_value = "utf8")]
+    encoding: OutputEncoding,
+}
+
+#[derive(Args, Debug)]
+struct InitArgs {
+    /// Write the config to this json, toml or yaml file instead of the default location
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// The dir of the images, the Pictures folder by default
+    #[arg(long)]
+    image_dir: Option<PathBuf>,
+    /// The slideshow to write, slideshow.sld in the Pictures folder by default
+    #[arg(long)]
+    output: Option<PathBuf>,
+    /// Of the screen, the primary monitor by default
+    #[arg(long)]
+    width: Option<u32>,
+    #[arg(long)]
+    height: Option<u32>,
+    /// Take the defaults without asking
+    #[arg(long, short)]
+    yes: bool,
+    /// Overwrite the existing config
+    #[arg(long)]
+    force: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+    /// Print the number and the total size of the cache entries
+    #[command(alias = "stats")]
+    Show,
+    /// Remove the entries of the deleted images, and optionally the ones not used for a while
+    Prune {
+        /// Also remove the entries not read for this many days
+        #[arg(long)]
+        unused_days: Option<u64>,
+    },
+    /// Remove all the cache entries
+    Clear,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    init_tracing(cli.verbose, cli.quiet);
+    REPORT_FORMAT.get_or_init(|| cli.output_format);
+    match cli.command.unwrap_or_else(|| Command::Generate(GenerateArgs::default())) {
+        Command::Generate(args) => generate(args).await,
+        Command::List(args) => list(args).await,
+        Command::Cache(args) => cache(args).await,
+        Command::Watch(args) => watch(args).await,
+        Command::Validate(args) => validate(args),
+        Command::Daemon(args) => daemon(args).await,
+        Command::Stats(args) => stats(args).await,
+        Command::Init(args) => init(args),
+        Command::Diff(args) => diff(args).await,
+    }
+}
+
+fn is_json_output() -> bool {
+    REPORT_FORMAT.get() == Some(&ReportFormat::Json)
+}
+
+// a line on stdout
+fn print_json(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
+
+// on stderr, as list prints the paths on stdout
+fn init_tracing(verbose: u8, quiet: bool) {
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::ERROR,
+        (false, 0) => LevelFilter::INFO,
+        (false, 1) => LevelFilter::DEBUG,
+        (false, _) => LevelFilter::TRACE,
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .with_target(false)
+        .without_time()
+        .init();
+}
+
+fn load_config(config_path: Option<&Path>) -> Result<Config> {
+    let mut config = match config_path {
+        Some(config_path) => Config::from_path(config_path)?,
+        None => {
+            let mut config = jdt::project(crate_name!()).config::<Config>();
+            // relative to the working dir, as the default config has no dir of its own to be relative to
+            config.expand_paths(None)?;
+            config.apply_junk_rules();
+            config
         }
+    };
+    config.slideshows.retain(|slideshow| slideshow.enabled);
+    config.resolve_paths(clock::today());
+    Ok(config)
+}
+
+// where jdt reads the config from when --config is omitted
+fn default_config_path() -> Result<PathBuf> {
+    let config_dir = dirs::config_dir().ok_or(Error::HomeDirError)?;
+    Ok(config_dir.join(crate_name!()).join("config.json"))
+}
+
+// the size of the primary monitor in pixels, none when it can't be told, e.g. on a headless machine
+fn primary_monitor_size() -> Option<(u32, u32)> {
+    let display_infos = display_info::DisplayInfo::all().ok()?;
+    let display_info = display_infos.iter().find(|display_info| display_info.is_primary).or(display_infos.first())?;
+    let scale_factor = display_info.scale_factor as f64;
+    Some(((display_info.width as f64 * scale_factor).round() as u32, (display_info.height as f64 * scale_factor).round() as u32))
+}
+
+// the default is taken on an empty answer
+fn ask(question: &str, default: &str) -> Result<String> {
+    eprint!("{} [{}]: ", question, default);
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { default.to_string() } else { answer.to_string() })
+}
+
+fn init(args: InitArgs) -> Result<()> {
+    let config_path = match args.config {
+        Some(config_path) => config_path,
+        None => default_config_path()?,
+    };
+    if config_path.exists() && !args.force {
+        return Err(Error::ConfigExistsError(config_path).into());
+    }
+    let picture_dir = dirs::picture_dir().or_else(|| dirs::home_dir().map(|home_dir| home_dir.join("Pictures"))).ok_or(Error::HomeDirError)?;
+    let (monitor_width, monitor_height) = primary_monitor_size().unwrap_or((1920, 1080));
+    let mut image_dir = args.image_dir.unwrap_or_else(|| picture_dir.clone());
+    let mut output = args.output.unwrap_or_else(|| picture_dir.join("slideshow.sld"));
+    let mut width = args.width.unwrap_or(monitor_width);
+    let mut height = args.height.unwrap_or(monitor_height);
+    if !args.yes {
+        image_dir = PathBuf::from(ask("Image dir", &image_dir.to_string_lossy())?);
+        output = PathBuf::from(ask("Slideshow", &output.to_string_lossy())?);
+        width = ask("Screen width", &width.to_string())?.parse()?;
+        height = ask("Screen height", &height.to_string())?.parse()?;
+    }
+    // only the given fields, the others are the defaults of the config
+    let config = serde_json::json!({
+        "slideshows": [{
+            "path": output,
+            "width": width,
+            "height": height,
+            "image_dirs": [image_dir],
+        }],
+    });
+    let extension = config_path.extension().map(|extension| extension.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+    let text = match extension.as_str() {
+        "toml" => toml::to_string_pretty(&config)?,
+        "yaml" | "yml" => serde_yaml::to_string(&config)?,
+        _ => serde_json::to_string_pretty(&config)?,
+    };
+    if let Some(config_dir) = config_path.parent() {
+        std::fs::create_dir_all(config_dir)?;
+    }
+    std::fs::write(&config_path, text)?;
+    if is_json_output() {
+        print_json(&serde_json::json!({ "config": config_path }))?;
+    } else {
+        println!("Written: {}", config_path.display());
+    }
+    Ok(())
+}
+
+// every problem is logged before failing, so that they can be fixed at once
+fn check_config(config: &Config) -> Result<()> {
+    let problems = config.problems();
+    for problem in &problems {
+        error!("{}", problem);
+    }
+    if !problems.is_empty() {
+        return Err(Error::ConfigError(problems.len()).into());
+    }
+    Ok(())
+}
+
+fn validate(args: ConfigArgs) -> Result<()> {
+    let config = load_config(args.config.as_deref())?;
+    if is_json_output() {
+        print_json(&serde_json::json!({ "n_slideshows": config.slideshows.len(), "problems": config.problems() }))?;
+    }
+    check_config(&config)?;
+    if !is_json_output() {
+        println!("No problems found in {} slideshows", config.slideshows.len());
+    }
+    Ok(())
+}
 
-        if date_time_candidates.is_empty() {
-            return Err(Error::NoCreationDateError(path.to_path_buf()).into());
+fn cache_options(scan_args: &ScanArgs, config: &Config) -> CacheOptions {
+    CacheOptions::new(scan_args.no_cache, scan_args.refresh_cache, config.cache_ttl, config.cache_key)
+        .with_bounded_memory(config.memory_budget_mb.is_some())
+}
+
+// a spinner on stderr, hidden when it's not a terminal
+fn scan_stats(slideshow: &SlideshowConfig, bench: bool) -> Arc<ScanStats> {
+    let progress_bar = PROGRESS_BARS.get_or_init(MultiProgress::new).add(ProgressBar::new_spinner().with_prefix(slideshow.path.display().to_string()));
+    if let Ok(style) = indicatif::ProgressStyle::with_template("{spinner} {prefix}: {msg}") {
+        progress_bar.set_style(style);
+    }
+    let stats = ScanStats::new(progress_bar);
+    Arc::new(if bench { stats.with_timings() } else { stats })
+}
+
+// the options shared by the subcommands scanning images, where the cli wins over the config
+async fn scan_options(slideshow: &SlideshowConfig, scan_args: &ScanArgs, config: &Config, cache_options: &CacheOptions) -> Result<ScanOptions> {
+    #[cfg(feature = "faces")]
+    if let Some(face_model) = &config.face_model {
+        make_xnview_slideshow::faces::load_face_model(face_model)?;
+    }
+    let parse_concurrency = scan_args.parse_concurrency.or(config.parse_concurrency).unwrap_or_else(num_cpus::get);
+    let mut scan_options = slideshow.scan_options(parse_concurrency, cache_options)?;
+    scan_options.max_inflight_bytes = scan_args.max_inflight_bytes;
+    scan_options.catalog = read_catalog(slideshow).await?;
+    for image_dir in slideshow.image_dirs.iter().filter(|image_dir| image_dir.is_remote()) {
+        let mirror_dir = fetch_remote_heads(&image_dir.path.to_string_lossy()).await?;
+        scan_options.remote_mirrors.push((mirror_dir, image_dir.local_path().to_path_buf()));
+    }
+    if let Some(walk_concurrency) = scan_args.walk_concurrency {
+        scan_options.walk_options.concurrency = walk_concurrency;
+    }
+    let max_files_per_sec = scan_args.max_files_per_sec.or(config.max_files_per_sec);
+    let max_bytes_per_sec = scan_args.max_bytes_per_sec.or(config.max_bytes_per_sec);
+    scan_options.rate_limiter = RateLimiter::new(max_files_per_sec, max_bytes_per_sec).map(Arc::new);
+    scan_options.strict = scan_args.strict || config.strict;
+    Ok(scan_options)
+}
+
+// printed after the progress bars, and written to the report file when given, even when empty so that
+// a stale report doesn't stay
+async fn report_skipped_files(skipped_files: &[SkippedFile], error_report: Option<&Path>) -> Result<()> {
+    let mut report = String::new();
+    for skipped_file in skipped_files {
+        let line = format!("{}: {}", skipped_file.path.display(), skipped_file.reason);
+        warn!("Skipped: {}", line);
+        report.push_str(&line);
+        report.push('\n');
+    }
+    if let Some(error_report) = error_report {
+        tokio::fs::write(error_report, report).await?;
+    }
+    Ok(())
+}
+
+async fn read_catalog(slideshow: &SlideshowConfig) -> Result<Option<Arc<Catalog>>> {
+    match &slideshow.catalog {
+        Some(catalog_path) => Ok(Some(Arc::new(Catalog::open(catalog_path, slideshow.image_dir_paths()).await?))),
+        None => Ok(None),
+    }
+}
+
+// dedupe, sample and order the matched images as configured
+fn arrange_images(slideshow: &SlideshowConfig, image_infos: Vec<ImageInfo>, fast: bool) -> Vec<ImageInfo> {
+    // the pinned ones are kept through the thinning and the sampling, an invalid glob is reported by check_config
+    let always_include = build_glob_set(&slideshow.always_include).unwrap_or_else(|_| GlobSet::empty());
+    let (pinned_image_infos, mut image_infos): (Vec<ImageInfo>, Vec<ImageInfo>) = image_infos.into_iter().partition(|image_info| always_include.is_match(&image_info.path));
+    if !slideshow.variant_suffixes.is_empty() {
+        // an invalid one is reported by check_config
+        if let Ok(variant_suffix_regex) = variant_suffix_regex(&slideshow.variant_suffixes) {
+            image_infos = pick_variants(image_infos, &variant_suffix_regex, slideshow.prefer_variant);
         }
+    }
+    if slideshow.dedupe_similar {
+        image_infos = dedupe_similar_images(image_infos, slideshow.hamming_threshold, slideshow.dedupe_time_window_secs);
+    }
+    if let Some(min_seconds_between_shots) = slideshow.min_seconds_between_shots {
+        image_infos = thin_bursts(image_infos, min_seconds_between_shots);
+    }
+    if let Some(max_per_day) = slideshow.max_per_day {
+        image_infos = cap_per_period(image_infos, max_per_day, |image_info| image_info.creation_date_time.date());
+    }
+    if let Some(max_per_month) = slideshow.max_per_month {
+        image_infos = cap_per_period(image_infos, max_per_month, |image_info| (image_info.creation_date_time.year(), image_info.creation_date_time.month()));
+    }
+    let mut rng = slideshow.rng();
+    if let Some(dir_weights) = slideshow.image_dir_weights() {
+        // sampled here too, so that the sample keeps the proportion
+        image_infos = mix_image_infos(image_infos, &dir_weights, slideshow.sample, slideshow.sample_strategy, &mut rng);
+    } else if let Some(freshness) = &slideshow.freshness {
+        image_infos = mix_freshness(image_infos, freshness, slideshow.sample, slideshow.sample_strategy, &mut rng);
+    } else if let Some(sample) = slideshow.sample {
+        image_infos = sample_image_infos(image_infos, sample, slideshow.sample_strategy, &mut rng);
+    }
+    image_infos.extend(pinned_image_infos);
+    // buffer_unordered yields in completion order, so sort for a reproducible output
+    let sort_order = slideshow.sort_order();
+    // chapters are of the sorted images
+    if !fast || matches!(sort_order, SortOrder::Random | SortOrder::InterleaveEvents) || slideshow.chapters.is_some() {
+        // an invalid locale is reported by check_config
+        let path_comparator = slideshow.path_comparator().unwrap_or(PathComparator::Bytewise);
+        sort_image_infos(&mut image_infos, sort_order, &path_comparator, &mut rng);
+    }
+    if sort_order == SortOrder::InterleaveEvents {
+        image_infos = interleave_events(image_infos, slideshow.event_gap_hours);
+    }
+    image_infos
+}
 
-        let creation_date_time = date_time_candidates.iter().min().expect("checked not empty").clone();
-        let (width, height) = read_image_size(path).await?;
-        let result = Self {
-            path: path.to_path_buf(),
-            width,
-            height,
-            creation_date_time,
+async fn generate(args: GenerateArgs) -> Result<()> {
+    let config = load_config(args.config_args.config.as_deref())?;
+    check_config(&config)?;
+    let _run_lock = RunLock::acquire().await?;
+    let mut cache_options = cache_options(&args.scan_args, &config);
+    if args.resume {
+        cache_options.memo = Arc::new(ScanMemo::read_checkpoint().await?);
+    }
+    for name in args.only.iter().chain(&args.skip) {
+        if !config.slideshows.iter().any(|slideshow| slideshow.name() == *name) {
+            return Err(Error::UnknownSlideshowError(name.clone()).into());
+        }
+    }
+    let groups = disjoint_groups(ordered_slideshows(&config).into_iter().map(|(_, slideshow)| slideshow).filter(|slideshow| args.selects(slideshow)).collect());
+    // the parse concurrency is the budget shared by the groups generated at once
+    let parse_concurrency = args.scan_args.parse_concurrency.or(config.parse_concurrency).unwrap_or_else(num_cpus::get);
+    let n_parallel = if config.parallel_slideshows { groups.len().clamp(1, parse_concurrency.max(1)) } else { 1 };
+    let mut group_args = args.clone();
+    group_args.scan_args.parse_concurrency = Some((parse_concurrency / n_parallel).max(1));
+    let mut skipped_files: Vec<SkippedFile> = Vec::new();
+    let mut exported_images: Vec<ExportedImage> = Vec::new();
+    let mut slideshow_results: Vec<SlideshowResult> = Vec::new();
+    let interrupted = {
+        let generate_slideshows = async {
+            // buffered, so that the skipped and the exported images stay in the order of the slideshows
+            let mut group_results = futures::stream::iter(groups)
+                .map(|group| generate_group(group, &config, &group_args, &cache_options))
+                .buffered(n_parallel);
+            while let Some(group_result) = group_results.next().await {
+                let (group_skipped_files, group_exported_images, group_slideshow_results) = match group_result {
+                    Ok(group_result) => group_result,
+                    Err(e) => {
+                        let run_summary = RunSummary { slideshows: std::mem::take(&mut slideshow_results), error: Some(format!("{:#}", e)) };
+                        notify(&config.notifiers, &run_summary).await;
+                        return Err(e);
+                    }
+                };
+                skipped_files.extend(group_skipped_files);
+                exported_images.extend(group_exported_images);
+                slideshow_results.extend(group_slideshow_results);
+            }
+            anyhow::Ok(())
         };
+        tokio::pin!(generate_slideshows);
+        tokio::select! {
+            result = &mut generate_slideshows => {
+                result?;
+                false
+            }
+            _ = tokio::signal::ctrl_c() => true,
+        }
+    };
+    flush_cache().await?;
+    if interrupted {
+        // the outputs are left as they were, as they are renamed into place only when finished
+        cache_options.memo.write_checkpoint().await?;
+        return Err(Error::InterruptedError.into());
+    }
+    ScanMemo::remove_checkpoint().await?;
+    if !args.dry_run {
+        notify(&config.notifiers, &RunSummary { slideshows: slideshow_results, error: None }).await;
+    }
+    report_skipped_files(&skipped_files, args.scan_args.error_report.as_deref()).await?;
+    if let Some(export) = &args.export {
+        let export_format = args.export_format.unwrap_or_else(|| ExportFormat::from_path(export));
+        write_export(export, export_format, &exported_images).await?;
+    }
+    #[cfg(all(windows, feature = "screensaver"))]
+    if args.install_screensaver && !args.dry_run {
+        install_newest_screensaver(&config, &args)?;
+    }
+    Ok(())
+}
 
-        // cache the result to local
-        cache_image_info(&result).await?;
+// of the slideshows of this run, the split ones are left out as their paths are made per bucket
+#[cfg(all(windows, feature = "screensaver"))]
+fn install_newest_screensaver(config: &Config, args: &GenerateArgs) -> Result<()> {
+    use make_xnview_slideshow::{output::OutputFormat, screensaver::{DEFAULT_SCREENSAVER_PATH, install_screensaver, newest_slideshow}};
+    let slideshow_paths = config.slideshows.iter()
+        .filter(|slideshow| args.selects(slideshow) && slideshow.output_format == OutputFormat::Sld && !slideshow.is_split())
+        .map(|slideshow| slideshow.path.as_path());
+    let Some(slideshow_path) = newest_slideshow(slideshow_paths) else {
+        warn!("No .sld written for the screensaver");
+        return Ok(());
+    };
+    let screensaver_path = config.screensaver_path.clone().unwrap_or_else(|| PathBuf::from(DEFAULT_SCREENSAVER_PATH));
+    install_screensaver(&slideshow_path, &screensaver_path)?;
+    info!("Screensaver: {}", slideshow_path.display());
+    Ok(())
+}
+
+// by generation_order, an invalid one is reported by check_config
+fn ordered_slideshows(config: &Config) -> Vec<(usize, &SlideshowConfig)> {
+    let order = config.generation_order().unwrap_or_else(|| (0..config.slideshows.len()).collect());
+    order.into_iter().map(|i| (i, &config.slideshows[i])).collect()
+}
 
-        Ok(result)
+// the slideshows sharing no image dir with the others of the other groups, each in the generation order,
+// so that the groups can be generated at once without the claims of the exclusive config and groups crossing them
+fn disjoint_groups(slideshows: Vec<&SlideshowConfig>) -> Vec<Vec<&SlideshowConfig>> {
+    // with the positions in the order, to keep it when groups are merged
+    let mut groups: Vec<Vec<(usize, &SlideshowConfig)>> = Vec::new();
+    for (position, slideshow) in slideshows.into_iter().enumerate() {
+        let (sharing_groups, other_groups): (Vec<_>, Vec<_>) = groups.into_iter()
+            .partition(|group| group.iter().any(|(_, other)| other.shares_dirs_with(slideshow)));
+        let mut merged_group: Vec<(usize, &SlideshowConfig)> = sharing_groups.into_iter().flatten().collect();
+        merged_group.sort_by_key(|(position, _)| *position);
+        merged_group.push((position, slideshow));
+        groups = other_groups;
+        groups.push(merged_group);
     }
+    groups.sort_by_key(|group| group[0].0);
+    groups.into_iter().map(|group| group.into_iter().map(|(_, slideshow)| slideshow).collect()).collect()
 }
 
-fn get_local_naive_date_time_from_system_time(system_time: SystemTime) -> Result<NaiveDateTime> {
-    let system_time = system_time.duration_since(SystemTime::UNIX_EPOCH)?;
-    let system_time = Local.timestamp_opt(system_time.as_secs() as i64, system_time.subsec_nanos()).earliest().ok_or_else(|| Error::SystemTimeError(system_time.as_secs().to_string()))?;
-    Ok(system_time.naive_local())
+// a group of disjoint_groups, serially, with the results for the notifiers
+async fn generate_group(group: Vec<&SlideshowConfig>, config: &Config, args: &GenerateArgs, cache_options: &CacheOptions) -> Result<(Vec<SkippedFile>, Vec<ExportedImage>, Vec<SlideshowResult>)> {
+    let mut claims = Claims::default();
+    let mut skipped_files: Vec<SkippedFile> = Vec::new();
+    let mut exported_images: Vec<ExportedImage> = Vec::new();
+    let mut slideshow_results: Vec<SlideshowResult> = Vec::new();
+    for slideshow in group {
+        if args.dry_run {
+            dry_run_slideshow(slideshow, config, args, cache_options, &mut skipped_files).await?;
+            continue;
+        }
+        let slideshow_report = generate_slideshow_with_hooks(slideshow, config, args, cache_options, &mut claims, &mut skipped_files, &mut exported_images).await?;
+        slideshow_results.push(SlideshowResult::from_report(&slideshow_report));
+    }
+    Ok((skipped_files, exported_images, slideshow_results))
 }
 
-async fn read_image_size(path: impl Into<PathBuf>) -> Result<(u32, u32)> {
-    let path = path.into();
-    task::spawn_blocking(move || {
-        let img = image::open(path)?;
-        Ok(img.dimensions())
-    }).await?
+// a failed pre_command stops the slideshow from being generated
+async fn generate_slideshow_with_hooks(slideshow: &SlideshowConfig, config: &Config, args: &GenerateArgs, cache_options: &CacheOptions, claims: &mut Claims, skipped_files: &mut Vec<SkippedFile>, exported_images: &mut Vec<ExportedImage>) -> Result<SlideshowReport> {
+    if let Some(pre_command) = &slideshow.pre_command {
+        run_hook(pre_command, slideshow, None).await?;
+    }
+    // the split outputs are named by their buckets, so there may be no previous one to compare with,
+    // and the notifiers tell the new images
+    let previous_paths = if (args.diff || !config.notifiers.is_empty()) && !slideshow.is_split() && slideshow.path.exists() {
+        Some(read_output(slideshow).await?.paths)
+    } else {
+        None
+    };
+    let mut slideshow_report = generate_slideshow(slideshow, config, args, cache_options, claims, skipped_files, exported_images).await?;
+    if let Some(previous_paths) = previous_paths {
+        let slideshow_diff = SlideshowDiff::new(&previous_paths, &read_output(slideshow).await?.paths);
+        if args.diff && !is_json_output() {
+            print_diff(&slideshow.path, &slideshow_diff);
+        }
+        slideshow_report.diff = Some(slideshow_diff);
+    }
+    if let (Some(path_template), Some(retention_days)) = (&slideshow.path_template, slideshow.retention_days) {
+        remove_expired_outputs(path_template, &slideshow.name(), &slideshow.path, retention_days).await?;
+    }
+    if let Some(post_command) = &slideshow.post_command {
+        run_hook(post_command, slideshow, Some(slideshow_report.n_written)).await?;
+    }
+    if is_json_output() {
+        print_json(&slideshow_report)?;
+    }
+    Ok(slideshow_report)
 }
 
-async fn cached_image_info(path: impl AsRef<Path>) -> Option<ImageInfo> {
-    let cache_path = match cache_path(path).await {
-        Ok(cache_path) => cache_path,
-        Err(_) => return None,
+// claims are the images taken by the former slideshows, for the exclusive config and groups,
+// and the report of this run is returned
+#[tracing::instrument(skip_all, fields(slideshow = %slideshow.path.display()))]
+async fn generate_slideshow(slideshow: &SlideshowConfig, config: &Config, args: &GenerateArgs, cache_options: &CacheOptions, claims: &mut Claims, skipped_files: &mut Vec<SkippedFile>, exported_images: &mut Vec<ExportedImage>) -> Result<SlideshowReport> {
+    let started = Instant::now();
+    if args.prune_unmatched && !slideshow.is_split() && slideshow.path.exists() {
+        return sync_slideshow(slideshow, config, args, cache_options, claims, skipped_files, exported_images).await;
+    }
+    // split outputs are always rewritten
+    let existing_slideshow = if args.incremental && !slideshow.is_split() && slideshow.path.exists() {
+        Some(read_output(slideshow).await?)
+    } else {
+        None
     };
-    if cache_path.exists() {
-        let json = match tokio::fs::read_to_string(cache_path).await {
-            Ok(json) => json,
-            Err(e) => {
-                eprintln!("Failed to read cache file: {:?}", e);
-                return None;
+    // none when split, as the writers are made per bucket at the end
+    let (mut slideshow_writer, existing_paths) = match existing_slideshow {
+        None if slideshow.is_split() => (None, HashSet::new()),
+        None => {
+            let mut slideshow_writer = OutputWriter::from_slideshow(slideshow).await?;
+            slideshow_writer.write_header(slideshow).await?;
+            (Some(slideshow_writer), HashSet::new())
+        }
+        Some(existing_slideshow) => {
+            let n_existing = existing_slideshow.paths.len();
+            let kept_paths: Vec<PathBuf> = if args.prune {
+                existing_slideshow.paths.into_iter().filter(|path| path.exists()).collect()
+            } else {
+                existing_slideshow.paths
+            };
+            if kept_paths.len() < n_existing {
+                // pruned, so the file needs to be rewritten
+                let mut slideshow_writer = OutputWriter::from_slideshow(slideshow).await?;
+                slideshow_writer.write_raw_header(&existing_slideshow.header).await?;
+                for path in &kept_paths {
+                    slideshow_writer.write_image_path(path).await?;
+                }
+                (Some(slideshow_writer), kept_paths.into_iter().collect())
+            } else {
+                let slideshow_writer = OutputWriter::append_to_slideshow(slideshow).await?;
+                (Some(slideshow_writer), kept_paths.into_iter().collect())
             }
-        };
-        let image_info: ImageInfo = match serde_json::from_str(&json) {
-            Ok(image_info) => image_info,
-            Err(e) => {
-                eprintln!("Failed to parse cache file: {:?}", e);
-                return None;
+        }
+    };
+    claims.claim(config, slideshow, existing_paths.iter().cloned());
+
+    let stats = scan_stats(slideshow, args.bench);
+    let scan_options = ScanOptions {
+        skip_paths: Arc::new(existing_paths),
+        stats: stats.clone(),
+        ..scan_options(slideshow, &args.scan_args, config, cache_options).await?
+    };
+    let image_info_stream = scan_images(slideshow.image_dir_paths(), scan_options, slideshow.dir_filters()?);
+    tokio::pin!(image_info_stream);
+    let mut image_infos = Vec::new();
+    // only a plain sort can be merged from the runs, the others need all the images at once
+    let mut spill_sorter = config.memory_budget_mb
+        .filter(|_| !args.fast && !slideshow.needs_all_images() && !slideshow.thumbnails && is_spillable(slideshow.sort_order()))
+        .map(|memory_budget_mb| anyhow::Ok(SpillSorter::new(slideshow.sort_order(), slideshow.path_comparator()?, memory_budget_mb * 1024 * 1024)))
+        .transpose()?;
+    let mut n_no_exif = 0;
+    let mut no_exif_paths: Vec<PathBuf> = Vec::new();
+    let mut exif_failures: Vec<PathBuf> = Vec::new();
+    let mut n_matches = 0;
+    while let Some(image_info) = image_info_stream.next().await {
+        let image_info = image_info?;
+        if claims.is_claimed(config, slideshow, &image_info.path) {
+            continue;
+        }
+        if !image_info.has_exif_date() {
+            n_no_exif += 1;
+            if args.list_no_exif {
+                if is_json_output() {
+                    no_exif_paths.push(image_info.path.clone());
+                } else {
+                    println!("{}", image_info.path.display());
+                }
             }
+        }
+        if image_info.lacks_readable_metadata() {
+            exif_failures.push(image_info.path.clone());
+        }
+        if args.fast && !slideshow.needs_all_images() {
+            // never split here, as splitting needs all the images
+            let slideshow_writer = slideshow_writer.as_mut().expect("made unless split");
+            slideshow_writer.write_image(&image_info).await?;
+            export_image(args, slideshow, &image_info, exported_images);
+            n_matches += 1;
+            claims.claim(config, slideshow, [image_info.path]);
+            continue;
+        }
+        if let Some(spill_sorter) = &mut spill_sorter {
+            spill_sorter.push(image_info).await?;
+            n_matches += 1;
+            continue;
+        }
+        image_infos.push(image_info);
+    }
+    stats.finish();
+    skipped_files.extend(stats.skipped_files());
+    let scanned = started.elapsed();
+    if !exif_failures.is_empty() {
+        warn!("{} images whose exif no parser could read: {}", exif_failures.len(), slideshow.path.display());
+    }
+    if let Some(exif_failure_report) = &slideshow.exif_failure_report {
+        // even when empty, so that a stale report doesn't stay
+        let lines: String = exif_failures.iter().map(|path| format!("{}\n", path.display())).collect();
+        tokio::fs::write(exif_failure_report, lines).await?;
+    }
+    if let Some(spill_sorter) = spill_sorter {
+        // never split, as splitting needs all the images
+        let mut slideshow_writer = slideshow_writer.expect("made unless split");
+        for image_info in spill_sorter.finish()? {
+            let image_info = image_info?;
+            slideshow_writer.write_image(&image_info).await?;
+            export_image(args, slideshow, &image_info, exported_images);
+            claims.claim(config, slideshow, [image_info.path]);
+        }
+        slideshow_writer.finish().await?;
+        log_summary(slideshow, n_no_exif, &stats);
+        // sorted while merged into the writes
+        let bench = bench_report(slideshow, &stats, scanned, scanned, started.elapsed());
+        return Ok(SlideshowReport { no_exif_paths, bench, ..SlideshowReport::new(slideshow, &stats, n_matches, started.elapsed()) });
+    }
+    let image_infos = arrange_images(slideshow, image_infos, args.fast);
+    let arranged = started.elapsed();
+    n_matches += image_infos.len();
+    claims.claim(config, slideshow, image_infos.iter().map(|image_info| image_info.path.clone()));
+    if slideshow.thumbnails {
+        let thumbnail_dir = match &config.thumbnail_dir {
+            Some(thumbnail_dir) => thumbnail_dir.clone(),
+            None => default_thumbnail_dir().await?,
         };
-        Some(image_info)
-    } else {
-        None
+        let concurrency = args.scan_args.parse_concurrency.or(config.parse_concurrency).unwrap_or_else(num_cpus::get);
+        pregenerate_thumbnails(&image_infos, &thumbnail_dir, config.thumbnail_size, concurrency).await?;
+    }
+    match (slideshow_writer, slideshow.split_by) {
+        (Some(mut slideshow_writer), _) => {
+            write_images(&mut slideshow_writer, slideshow, image_infos, args, exported_images).await?;
+            slideshow_writer.finish().await?;
+        }
+        (None, Some(split_by)) => {
+            for (bucket_key, image_infos) in split_image_infos(image_infos, split_by) {
+                let path = split_path(&slideshow.path, slideshow.split_path_template.as_deref(), split_by, bucket_key);
+                let mut slideshow_writer = OutputWriter::from_slideshow_to_path(slideshow, &path).await?;
+                slideshow_writer.write_header(slideshow).await?;
+                write_images(&mut slideshow_writer, slideshow, image_infos, args, exported_images).await?;
+                slideshow_writer.finish().await?;
+                info!("Written: {}", path.display());
+            }
+        }
+        (None, None) => {
+            for (monitor, image_infos) in slideshow.monitors.iter().zip(split_by_monitor(image_infos, &slideshow.monitors)) {
+                let mut slideshow_writer = OutputWriter::from_slideshow_to_screen(slideshow, &monitor.path, monitor.width, monitor.height).await?;
+                slideshow_writer.write_header(slideshow).await?;
+                write_images(&mut slideshow_writer, slideshow, image_infos, args, exported_images).await?;
+                slideshow_writer.finish().await?;
+                info!("Written: {}", monitor.path.display());
+            }
+        }
+    }
+    log_summary(slideshow, n_no_exif, &stats);
+    let bench = bench_report(slideshow, &stats, scanned, arranged, started.elapsed());
+    Ok(SlideshowReport { no_exif_paths, bench, ..SlideshowReport::new(slideshow, &stats, n_matches, started.elapsed()) })
+}
+
+// with --bench, printed unless json, as the report has it then
+fn bench_report(slideshow: &SlideshowConfig, stats: &ScanStats, scanned: Duration, arranged: Duration, finished: Duration) -> Option<BenchReport> {
+    let bench_report = BenchReport {
+        scan: stats.timings()?,
+        scan_secs: scanned.as_secs_f64(),
+        sort_secs: arranged.saturating_sub(scanned).as_secs_f64(),
+        write_secs: finished.saturating_sub(arranged).as_secs_f64(),
+    };
+    if !is_json_output() {
+        println!("{}", slideshow.path.display());
+        for line in bench_report.summary() {
+            println!("  {}", line);
+        }
     }
+    Some(bench_report)
 }
 
-async fn cache_image_info(image_info: &ImageInfo) -> Result<()> {
-    let cache_path = cache_path(&image_info.path).await?;
-    let json = serde_json::to_string(image_info)?;
-    tokio::fs::write(cache_path, json).await?;
+fn log_summary(slideshow: &SlideshowConfig, n_no_exif: usize, stats: &ScanStats) {
+    if n_no_exif > 0 {
+        info!("{} images without exif date, dated by file system timestamps: {}", n_no_exif, slideshow.path.display());
+    }
+    info!("{}", slideshow.path.display());
+    for line in stats.summary() {
+        info!("  {}", line);
+    }
+}
+
+// with a title slide before each chapter when chapters are given
+async fn write_images(slideshow_writer: &mut OutputWriter, slideshow: &SlideshowConfig, image_infos: Vec<ImageInfo>, args: &GenerateArgs, exported_images: &mut Vec<ExportedImage>) -> Result<()> {
+    let title_style = slideshow.chapters.as_ref().map(|chapters| {
+        let (width, height) = slideshow_writer.screen_size();
+        let header = slideshow.header();
+        TitleStyle {
+            width,
+            height,
+            background_color: header.background_color,
+            text_color: header.text_color,
+            font: chapters.font.clone(),
+            font_size_ratio: chapters.font_size_ratio,
+        }
+    });
+    let mut current_title = None;
+    for image_info in image_infos {
+        if let (Some(chapters), Some(title_style)) = (&slideshow.chapters, &title_style) {
+            let title = chapters.title(image_info.creation_date_time);
+            if current_title.as_ref() != Some(&title) {
+                let title_path = render_title(&title, title_style).await?;
+                slideshow_writer.write_image_path(&title_path).await?;
+                current_title = Some(title);
+            }
+        }
+        slideshow_writer.write_image(&image_info).await?;
+        export_image(args, slideshow, &image_info, exported_images);
+    }
     Ok(())
 }
 
-async fn cache_path(path: impl AsRef<Path>) -> Result<PathBuf> {
-    let path = path.as_ref();
-    let cache_hash = format!("{:x}", md5::compute(path.as_os_str().as_encoded_bytes()));
-    let cache_parent_dir = cache_parent_dir().await?;
-    Ok(cache_parent_dir.join(cache_hash + ".json"))
-}
-
-async fn cache_parent_dir() -> Result<PathBuf> {
-    let cache_dir = cache_dir().ok_or(Error::CacheDirError)?;
-    let cache_parent_dir = cache_dir.join(crate_name!());
-    if !cache_parent_dir.exists() {
-        tokio::fs::create_dir_all(&cache_parent_dir).await?;
-    }
-    Ok(cache_parent_dir)
-}
-
-#[derive(Debug)]
-struct SlideshowWriter {
-    file: tokio::fs::File,
-}
-
-impl SlideshowWriter {
-    async fn from_path(path: impl AsRef<Path>) -> Result<Self> {
-        let path = path.as_ref();
-        let file = tokio::fs::OpenOptions::new().create(true).write(true).truncate(true).open(path).await?;
-        Ok(Self {
-            file,
-        })
-    }
-
-    async fn write_header(&mut self, width: u32, height: u32) -> Result<()> {
-        let header = format!(r#"# Slide Show Sequence v2
-UseTimer = 1
-Timer = 2
-Loop = 1
-FullScreen = 0
-WinWidth = {width}
-WinHeight = {height}
-Stretch = 1
-RandomOrder = 1
-ShowInfo = 1
-Info = {{Filename}}
-TitleBar = 1
-OnTop = 1
-CursorAutoHide = 0
-BackgroundColor = 0 0 0 255
-TextColor = 255 255 255 255
-UseTextBackColor = 0
-TextPosition = 0
-TextBackColor = 128 128 128 255
-Opacity = 100
-Font = Sans Serif,9,-1,5,50,0,0,0,0,0
-EffectDuration = 1000
-"#, width = width, height = height);
-        tokio::io::AsyncWriteExt::write_all(&mut self.file, header.as_bytes()).await?;
-        Ok(())
-    }
-
-    async fn write_image_path(&mut self, path: impl AsRef<Path>) -> Result<()> {
-        let path = path.as_ref();
-        let path = path.to_string_lossy();
-        let path = path.replace("\\", "\\\\").replace("\"", "\\\"");
-        let line = format!("\"{}\"\n", path);
-        tokio::io::AsyncWriteExt::write_all(&mut self.file, line.as_bytes()).await?;
-        Ok(())
+// only the images written in this run, so the kept ones of --incremental are not in it
+fn export_image(args: &GenerateArgs, slideshow: &SlideshowConfig, image_info: &ImageInfo, exported_images: &mut Vec<ExportedImage>) {
+    if args.export.is_some() {
+        exported_images.push(ExportedImage::new(&slideshow.path, image_info));
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let n_threads = num_cpus::get();
-    let config = jdt::project(crate_name!()).config::<Config>();
-    for slideshow in config.slideshows {
-        let mut slideshow_writer = SlideshowWriter::from_path(&slideshow.path).await?;
-        slideshow_writer.write_header(slideshow.width, slideshow.height).await?;
-
-        let image_path_stream = image_path_stream(slideshow.image_dirs.clone());
-        let image_info_stream = image_info_stream(n_threads, image_path_stream);
+// diffs the existing slideshow against the matched images, so that only the changed entries are touched,
+// and the paths outside the image dirs are kept as they are added by hand
+async fn sync_slideshow(slideshow: &SlideshowConfig, config: &Config, args: &GenerateArgs, cache_options: &CacheOptions, claims: &mut Claims, skipped_files: &mut Vec<SkippedFile>, exported_images: &mut Vec<ExportedImage>) -> Result<SlideshowReport> {
+    let started = Instant::now();
+    let existing_slideshow = read_output(slideshow).await?;
+
+    let stats = scan_stats(slideshow, args.bench);
+    let scan_options = ScanOptions {
+        stats: stats.clone(),
+        ..scan_options(slideshow, &args.scan_args, config, cache_options).await?
+    };
+    let image_info_stream = scan_images(slideshow.image_dir_paths(), scan_options, slideshow.dir_filters()?);
+    tokio::pin!(image_info_stream);
+    let mut image_infos = Vec::new();
+    while let Some(image_info) = image_info_stream.next().await {
+        let image_info = image_info?;
+        if claims.is_claimed(config, slideshow, &image_info.path) {
+            continue;
+        }
+        image_infos.push(image_info);
+    }
+    stats.finish();
+    skipped_files.extend(stats.skipped_files());
+    let scanned = started.elapsed();
+
+    // compared in the written form, as the listed paths lack e.g. the verbatim prefix of the image dirs
+    let matched_paths: HashSet<String> = image_infos.iter().map(|image_info| xnview_path(&image_info.path)).collect();
+    let image_dir_paths: Vec<PathBuf> = slideshow.image_dir_paths().iter().map(|image_dir_path| PathBuf::from(xnview_path(image_dir_path))).collect();
+    let is_added_by_hand = |path: &Path| !image_dir_paths.iter().any(|image_dir_path| path.starts_with(image_dir_path));
+    let n_existing = existing_slideshow.paths.len();
+    let kept_paths: Vec<PathBuf> = existing_slideshow.paths.into_iter()
+        .filter(|path| matched_paths.contains(&xnview_path(path)) || is_added_by_hand(path.as_path()))
+        .collect();
+    let kept_path_set: HashSet<String> = kept_paths.iter().map(|path| xnview_path(path)).collect();
+    let new_image_infos: Vec<ImageInfo> = image_infos.into_iter().filter(|image_info| !kept_path_set.contains(&xnview_path(&image_info.path))).collect();
+    let new_image_infos = arrange_images(slideshow, new_image_infos, args.fast);
+    let arranged = started.elapsed();
+
+    let mut slideshow_writer = if kept_paths.len() < n_existing {
+        // removed some, so the file needs to be rewritten
+        let mut slideshow_writer = OutputWriter::from_slideshow(slideshow).await?;
+        slideshow_writer.write_raw_header(&existing_slideshow.header).await?;
+        for path in &kept_paths {
+            slideshow_writer.write_image_path(path).await?;
+        }
+        slideshow_writer
+    } else {
+        OutputWriter::append_to_slideshow(slideshow).await?
+    };
+    for image_info in &new_image_infos {
+        slideshow_writer.write_image(image_info).await?;
+        export_image(args, slideshow, image_info, exported_images);
+    }
+    slideshow_writer.finish().await?;
+    info!("{}: {} added, {} removed", slideshow.path.display(), new_image_infos.len(), n_existing - kept_paths.len());
+    let n_matches = new_image_infos.len();
+    claims.claim(config, slideshow, kept_paths);
+    claims.claim(config, slideshow, new_image_infos.into_iter().map(|image_info| image_info.path));
+    let bench = bench_report(slideshow, &stats, scanned, arranged, started.elapsed());
+    Ok(SlideshowReport { bench, ..SlideshowReport::new(slideshow, &stats, n_matches, started.elapsed()) })
+}
+
+// the cache is still written, so that tuning the filters by repeated dry runs is fast
+async fn dry_run_slideshow(slideshow: &SlideshowConfig, config: &Config, args: &GenerateArgs, cache_options: &CacheOptions, skipped_files: &mut Vec<SkippedFile>) -> Result<()> {
+    let started = Instant::now();
+    let stats = scan_stats(slideshow, false);
+    let scan_options = ScanOptions {
+        stats: stats.clone(),
+        ..scan_options(slideshow, &args.scan_args, config, cache_options).await?
+    };
+    let candidate_stream = scan_candidates(slideshow.image_dir_paths(), scan_options, slideshow.dir_filters()?);
+    tokio::pin!(candidate_stream);
+    let mut candidates = Vec::new();
+    while let Some(candidate) = candidate_stream.next().await {
+        candidates.push(candidate?);
+    }
+    stats.finish();
+    skipped_files.extend(stats.skipped_files());
+    candidates.sort_by(|(a, _), (b, _)| a.path.cmp(&b.path));
+
+    let json = args.json || is_json_output();
+    if !json {
+        println!("# {}", slideshow.path.display());
+    }
+    for (image_info, rejection) in candidates {
+        if json {
+            let line = serde_json::json!({
+                "slideshow": slideshow.path,
+                "path": image_info.path,
+                "rejected_by": rejection,
+                "creation_date_time": image_info.creation_date_time,
+                "width": image_info.width,
+                "height": image_info.height,
+                "aspect_ratio": image_info.aspect_ratio(),
+            });
+            println!("{}", line);
+        } else {
+            let result = rejection.map_or("matched".to_string(), |reason| format!("filtered out by {}", reason));
+            println!("{}\t{}", image_info.path.display(), result);
+        }
+    }
+    if is_json_output() {
+        print_json(&SlideshowReport::new(slideshow, &stats, 0, started.elapsed()))?;
+    }
+    Ok(())
+}
+
+async fn list(args: ListArgs) -> Result<()> {
+    let config = load_config(args.config_args.config.as_deref())?;
+    check_config(&config)?;
+    let cache_options = cache_options(&args.scan_args, &config);
+    let mut claims = Claims::default();
+    let mut skipped_files: Vec<SkippedFile> = Vec::new();
+    for (_, slideshow) in ordered_slideshows(&config) {
+        let started = Instant::now();
+        let scan_options = scan_options(slideshow, &args.scan_args, &config, &cache_options).await?;
+        let stats = scan_options.stats.clone();
+        let image_info_stream = scan_images(slideshow.image_dir_paths(), scan_options, slideshow.dir_filters()?);
         tokio::pin!(image_info_stream);
+        let mut image_infos = Vec::new();
         while let Some(image_info) = image_info_stream.next().await {
             let image_info = image_info?;
-            if image_info.creation_date_time.date() < slideshow.min_creation_date {
+            if claims.is_claimed(&config, slideshow, &image_info.path) {
                 continue;
             }
-            if image_info.creation_date_time.date() > slideshow.max_creation_date {
-                continue;
+            image_infos.push(image_info);
+        }
+        skipped_files.extend(stats.skipped_files());
+        let image_paths: Vec<PathBuf> = arrange_images(slideshow, image_infos, false).into_iter().map(|image_info| image_info.path).collect();
+        claims.claim(&config, slideshow, image_paths.iter().cloned());
+        if is_json_output() {
+            print_json(&SlideshowReport { images: Some(image_paths), ..SlideshowReport::new(slideshow, &stats, 0, started.elapsed()) })?;
+        } else {
+            println!("# {}", slideshow.path.display());
+            for image_path in &image_paths {
+                println!("{}", image_path.display());
             }
-            let aspect_ratio = image_info.width as f64 / image_info.height as f64;
-            if aspect_ratio < slideshow.min_aspect_ratio || aspect_ratio > slideshow.max_aspect_ratio {
-                continue;
+        }
+    }
+    flush_cache().await?;
+    report_skipped_files(&skipped_files, args.scan_args.error_report.as_deref()).await?;
+    Ok(())
+}
+
+fn print_diff(path: &Path, slideshow_diff: &SlideshowDiff) {
+    for line in slideshow_diff.lines() {
+        println!("{}", line);
+    }
+    info!("{}: {} added, {} removed", path.display(), slideshow_diff.added.len(), slideshow_diff.removed.len());
+}
+
+async fn diff(args: DiffArgs) -> Result<()> {
+    let new = match args.new {
+        Some(new) => new,
+        None if args.old.extension().is_some_and(|extension| extension == "bak") => args.old.with_extension(""),
+        None => return Err(Error::DiffTargetError(args.old).into()),
+    };
+    let old_paths = read_output_paths(&args.old, args.encoding).await?;
+    let new_paths = read_output_paths(&new, args.encoding).await?;
+    let slideshow_diff = SlideshowDiff::new(&old_paths, &new_paths);
+    if is_json_output() {
+        print_json(&serde_json::json!({ "old": args.old, "new": new, "added": slideshow_diff.added, "removed": slideshow_diff.removed }))?;
+    } else {
+        print_diff(&new, &slideshow_diff);
+    }
+    Ok(())
+}
+
+// the filters are not applied, as the stats are for choosing them
+async fn stats(args: StatsArgs) -> Result<()> {
+    let config = load_config(args.config_args.config.as_deref())?;
+    let cache_options = cache_options(&args.scan_args, &config);
+    // the walk options of the slideshows apply, e.g. include_videos
+    let dirs_slideshow = (!args.dirs.is_empty()).then(|| SlideshowConfig::from_image_dirs(&args.dirs)).transpose()?;
+    let slideshows: Vec<&SlideshowConfig> = match &dirs_slideshow {
+        Some(dirs_slideshow) => vec![dirs_slideshow],
+        None => config.slideshows.iter().collect(),
+    };
+    let mut skipped_files: Vec<SkippedFile> = Vec::new();
+    for slideshow in slideshows {
+        let name = match &dirs_slideshow {
+            Some(_) => args.dirs.iter().map(|dir| dir.display().to_string()).collect::<Vec<_>>().join(", "),
+            None => slideshow.path.display().to_string(),
+        };
+        let scan_options = scan_options(slideshow, &args.scan_args, &config, &cache_options).await?;
+        let scan_stats = scan_options.stats.clone();
+        let candidate_stream = scan_candidates(slideshow.image_dir_paths(), scan_options, slideshow.dir_filters()?);
+        tokio::pin!(candidate_stream);
+        let mut library_stats = LibraryStats::default();
+        while let Some(candidate) = candidate_stream.next().await {
+            let (image_info, _) = candidate?;
+            library_stats.add(&image_info);
+        }
+        scan_stats.finish();
+        skipped_files.extend(scan_stats.skipped_files());
+        if args.json || is_json_output() {
+            println!("{}", serde_json::json!({ "name": name, "stats": library_stats }));
+        } else {
+            println!("# {}", name);
+            for line in library_stats.report() {
+                println!("{}", line);
             }
-            slideshow_writer.write_image_path(&image_info.path).await?;
         }
     }
+    flush_cache().await?;
+    report_skipped_files(&skipped_files, args.scan_args.error_report.as_deref()).await?;
     Ok(())
 }
 
-fn image_path_stream(dirs: Vec<PathBuf>) -> impl futures::Stream<Item = Result<PathBuf>> {
-    let mut dir_stack = dirs;
-    stream! {
-        while let Some(dir) = dir_stack.pop() {
-            let mut entries = tokio::fs::read_dir(dir).await?;
-            while let Some(entry) = entries.next_entry().await? {
-                if junk_file::is_junk(entry.path()) {
-                    continue;
+async fn cache(args: CacheArgs) -> Result<()> {
+    let config = load_config(args.config_args.config.as_deref())?;
+    match args.command {
+        CacheCommand::Show => {
+            let cache_options = cache_options(&ScanArgs::default(), &config);
+            let stats = cache_stats(cache_options.ttl).await?;
+            if is_json_output() {
+                print_json(&serde_json::json!({
+                    "database": cache_db_path().await?,
+                    "n_entries": stats.n_entries,
+                    "total_bytes": stats.total_bytes,
+                    "n_expired": cache_options.ttl.map(|_| stats.n_expired),
+                }))?;
+                return Ok(());
+            }
+            println!("database: {}", cache_db_path().await?.display());
+            println!("entries: {}", stats.n_entries);
+            println!("total bytes: {}", stats.total_bytes);
+            if cache_options.ttl.is_some() {
+                println!("expired entries: {}", stats.n_expired);
+            }
+        }
+        CacheCommand::Prune { unused_days } => {
+            let n_removed = prune_cache(unused_days).await?;
+            print_n_removed(n_removed)?;
+        }
+        CacheCommand::Clear => {
+            let n_removed = clear_cache().await?;
+            print_n_removed(n_removed)?;
+        }
+    }
+    Ok(())
+}
+
+fn print_n_removed(n_removed: usize) -> Result<()> {
+    if is_json_output() {
+        return print_json(&serde_json::json!({ "n_removed": n_removed }));
+    }
+    println!("removed entries: {}", n_removed);
+    Ok(())
+}
+
+// dirs have no extension, and may have been removed, so they can't be told by the file type
+fn is_watched_change(path: &Path) -> bool {
+    if path.extension().is_none() || raw::is_raw_path(path) || heif::is_heif_path(path) {
+        return true;
+    }
+    mime_guess::from_path(path).iter().any(|mime| mime.type_() == "image" || mime.type_() == "video")
+}
+
+async fn watch(args: WatchArgs) -> Result<()> {
+    let mut config = load_config(args.config_args.config.as_deref())?;
+    check_config(&config)?;
+    let generate_args = GenerateArgs {
+        scan_args: args.scan_args,
+        ..Default::default()
+    };
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        // the receiver is gone only when exiting
+        let _ = tx.send(event);
+    })?;
+    for slideshow in &config.slideshows {
+        // the remote ones are left to the daemon
+        for image_dir in slideshow.image_dirs.iter().filter(|image_dir| !image_dir.is_remote()) {
+            watcher.watch(&image_dir.path, RecursiveMode::Recursive)?;
+        }
+    }
+    info!("Watching the image dirs, press Ctrl-C to stop");
+
+    while let Some(event) = rx.recv().await {
+        let mut changed_paths: Vec<PathBuf> = Vec::new();
+        let mut event = Some(event);
+        while let Some(result) = event {
+            match result {
+                Ok(event) => if !matches!(event.kind, EventKind::Access(_)) {
+                    // the slideshows and their temp files may be written inside the image dirs
+                    changed_paths.extend(event.paths.into_iter().filter(|path| is_watched_change(path)));
                 }
-                if entry.file_type().await?.is_dir() {
-                    dir_stack.push(entry.path());
-                } else {
-                    let mimes = mime_guess::from_path(entry.path());
-                    let guess_image = mimes.iter().any(|mime| mime.type_() == "image");
-                    if !guess_image {
-                        continue;
-                    }
-                    yield Ok(entry.path());
+                Err(e) => warn!("Failed to watch: {:?}", e),
+            }
+            event = tokio::time::timeout(WATCH_DEBOUNCE, rx.recv()).await.ok().flatten();
+        }
+        if changed_paths.is_empty() {
+            continue;
+        }
+        config.resolve_paths(clock::today());
+
+        let is_affected = |slideshow: &SlideshowConfig| {
+            slideshow.image_dirs.iter().any(|image_dir| changed_paths.iter().any(|path| path.starts_with(&image_dir.path)))
+        };
+        // with the exclusive config and groups, a change in one slideshow may move images from or to the later ones
+        if !config.slideshows.iter().any(is_affected) {
+            continue;
+        }
+        // made per round, as the dirs and the images remembered by the previous one may have changed
+        let cache_options = cache_options(&generate_args.scan_args, &config);
+        let mut claims = Claims::default();
+        let mut skipped_files: Vec<SkippedFile> = Vec::new();
+        let mut run_summary = RunSummary::default();
+        for (_, slideshow) in ordered_slideshows(&config) {
+            if !config.is_exclusive(slideshow) && !is_affected(slideshow) {
+                continue;
+            }
+            match generate_slideshow_with_hooks(slideshow, &config, &generate_args, &cache_options, &mut claims, &mut skipped_files, &mut Vec::new()).await {
+                Ok(slideshow_report) => run_summary.slideshows.push(SlideshowResult::from_report(&slideshow_report)),
+                Err(e) => {
+                    run_summary.slideshows.push(SlideshowResult::failed(slideshow, &e));
+                    notify(&config.notifiers, &run_summary).await;
+                    return Err(e);
                 }
             }
+            info!("Regenerated: {}", slideshow.path.display());
         }
+        notify(&config.notifiers, &run_summary).await;
+        flush_cache().await?;
+        report_skipped_files(&skipped_files, generate_args.scan_args.error_report.as_deref()).await?;
     }
+    Ok(())
 }
 
-fn image_info_stream(n_threads: usize, image_path_stream: impl futures::Stream<Item = Result<PathBuf>>) -> impl futures::Stream<Item = Result<ImageInfo>> {
-    image_path_stream.map(|image_path| async {
-        let image_path = image_path?;
-        let image_info = ImageInfo::from_path(image_path).await?;
-        Ok(image_info)
-    }).buffer_unordered(n_threads)
+// the runs are randomly delayed by schedule_jitter_secs
+fn next_scheduled_run(schedule: &Schedule, after: DateTime<Local>, jitter_secs: u64, rng: &mut StdRng) -> Option<DateTime<Local>> {
+    let jitter = TimeDelta::seconds(rng.gen_range(0..=jitter_secs) as i64);
+    schedule.next_after(after).and_then(|next_run| next_run.checked_add_signed(jitter))
 }
 
+async fn daemon(args: DaemonArgs) -> Result<()> {
+    let mut config = load_config(args.config_args.config.as_deref())?;
+    check_config(&config)?;
+    let generate_args = GenerateArgs {
+        scan_args: args.scan_args,
+        ..Default::default()
+    };
+    let schedules: Vec<Option<Schedule>> = config.slideshows.iter()
+        .map(|slideshow| slideshow.schedule.as_deref().map(Schedule::parse).transpose())
+        .collect::<Result<_>>()?;
+    let mut rng = StdRng::from_entropy();
+    let now = Local::now();
+    // intervals run once at the start, so that the outputs are fresh without waiting a whole interval
+    let mut next_runs: Vec<Option<DateTime<Local>>> = schedules.iter().map(|schedule| match schedule {
+        Some(Schedule::Interval(_)) => Some(now),
+        Some(schedule) => next_scheduled_run(schedule, now, config.schedule_jitter_secs, &mut rng),
+        None => None,
+    }).collect();
+
+    loop {
+        let Some(next_run) = next_runs.iter().flatten().min().copied() else {
+            info!("No scheduled slideshows, exiting");
+            return Ok(());
+        };
+        tokio::time::sleep((next_run - Local::now()).to_std().unwrap_or_default()).await;
+        let now = Local::now();
+        let due: Vec<usize> = (0..next_runs.len()).filter(|i| next_runs[*i].is_some_and(|next_run| next_run <= now)).collect();
+        // e.g. {today} of the path is of the day of the run, not of the start
+        config.resolve_paths(clock::today());
+        match RunLock::acquire().await {
+            Ok(_run_lock) => {
+                let cache_options = cache_options(&generate_args.scan_args, &config);
+                let mut claims = Claims::default();
+                let mut skipped_files: Vec<SkippedFile> = Vec::new();
+                let mut run_summary = RunSummary::default();
+                for (i, slideshow) in ordered_slideshows(&config) {
+                    // with the exclusive config and groups, the others are regenerated too, the same as watch
+                    if !config.is_exclusive(slideshow) && !due.contains(&i) {
+                        continue;
+                    }
+                    // a failed run is retried on the next schedule instead of stopping the daemon
+                    match generate_slideshow_with_hooks(slideshow, &config, &generate_args, &cache_options, &mut claims, &mut skipped_files, &mut Vec::new()).await {
+                        Ok(slideshow_report) => {
+                            info!("Regenerated: {}", slideshow.path.display());
+                            run_summary.slideshows.push(SlideshowResult::from_report(&slideshow_report));
+                        }
+                        Err(e) => {
+                            error!("Failed to regenerate: {}: {:#}", slideshow.path.display(), e);
+                            run_summary.slideshows.push(SlideshowResult::failed(slideshow, &e));
+                        }
+                    }
+                }
+                notify(&config.notifiers, &run_summary).await;
+                flush_cache().await?;
+                report_skipped_files(&skipped_files, generate_args.scan_args.error_report.as_deref()).await?;
+            }
+            Err(e) => warn!("Skipped the scheduled run: {:#}", e),
+        }
+        for i in due {
+            next_runs[i] = schedules[i].as_ref().and_then(|schedule| next_scheduled_run(schedule, now, config.schedule_jitter_secs, &mut rng));
+        }
+    }
+}
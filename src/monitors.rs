@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+use serde::{Serialize, Deserialize};
+use crate::{filter::Orientation, image_info::ImageInfo};
+
+// one output of a multi-monitor slideshow, with the size and the aspect ratios of its screen
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Monitor {
+    pub path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    // on top of the filter of the slideshow, e.g. only the portraits for a rotated screen
+    #[serde(default)]
+    pub min_aspect_ratio: Option<f64>,
+    #[serde(default)]
+    pub max_aspect_ratio: Option<f64>,
+    #[serde(default)]
+    pub orientation: Orientation,
+}
+
+impl Monitor {
+    fn accepts(&self, image_info: &ImageInfo) -> bool {
+        let aspect_ratio = image_info.aspect_ratio();
+        self.min_aspect_ratio.is_none_or(|min_aspect_ratio| aspect_ratio >= min_aspect_ratio)
+            && self.max_aspect_ratio.is_none_or(|max_aspect_ratio| aspect_ratio <= max_aspect_ratio)
+            && self.orientation.accepts(aspect_ratio)
+    }
+
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if self.width == 0 || self.height == 0 {
+            problems.push(format!("size of monitor is zero: {}", self.path.display()));
+        }
+        if let (Some(min_aspect_ratio), Some(max_aspect_ratio)) = (self.min_aspect_ratio, self.max_aspect_ratio) {
+            if min_aspect_ratio > max_aspect_ratio {
+                problems.push(format!("min_aspect_ratio {} is greater than max_aspect_ratio {} of monitor: {}", min_aspect_ratio, max_aspect_ratio, self.path.display()));
+            }
+        }
+        problems
+    }
+}
+
+// each image goes to only one of the monitors, the one with the fewest so far among the ones accepting it,
+// so that the same photo never shows on two screens at once, and the ones no monitor accepts are left out
+pub fn split_by_monitor(image_infos: Vec<ImageInfo>, monitors: &[Monitor]) -> Vec<Vec<ImageInfo>> {
+    let mut outputs: Vec<Vec<ImageInfo>> = vec![Vec::new(); monitors.len()];
+    for image_info in image_infos {
+        let emptiest = monitors.iter().enumerate()
+            .filter(|(_, monitor)| monitor.accepts(&image_info))
+            .min_by_key(|(i, _)| outputs[*i].len())
+            .map(|(i, _)| i);
+        if let Some(i) = emptiest {
+            outputs[i].push(image_info);
+        }
+    }
+    outputs
+}
@@ -0,0 +1,177 @@
+use std::{path::PathBuf, process::Stdio};
+use serde::{Serialize, Deserialize};
+use anyhow::Result;
+use tokio::{io::AsyncWriteExt, process::Command};
+use tracing::{debug, warn};
+use crate::{Error, config::SlideshowConfig, report::SlideshowReport};
+
+const TITLE: &str = "make-xnview-slideshow";
+
+// by a toast of powershell itself, as an unregistered app id shows nothing
+const WINDOWS_TOAST_SCRIPT: &str = r#"
+[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] > $null
+$template = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02)
+$texts = $template.GetElementsByTagName('text')
+$texts.Item(0).AppendChild($template.CreateTextNode($env:NOTIFICATION_TITLE)) > $null
+$texts.Item(1).AppendChild($template.CreateTextNode($env:NOTIFICATION_BODY)) > $null
+$appId = '{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe'
+[Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier($appId).Show([Windows.UI.Notifications.ToastNotification]::new($template))
+"#;
+
+// told after each run, e.g. of the daemon, where nobody sees the log
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Notifier {
+    // notify-send on linux, osascript on macos and a toast by powershell on windows
+    Desktop {
+        #[serde(default)]
+        only_errors: bool,
+    },
+    // the RunSummary posted as json by curl, e.g. to home assistant or a chat relay
+    Webhook {
+        url: String,
+        #[serde(default)]
+        only_errors: bool,
+    },
+}
+
+impl Notifier {
+    pub fn problems(&self) -> Vec<String> {
+        match self {
+            Notifier::Webhook { url, .. } if !(url.starts_with("http://") || url.starts_with("https://")) => {
+                vec![format!("url of the webhook is not http or https: {}", url)]
+            }
+            _ => vec![],
+        }
+    }
+
+    fn only_errors(&self) -> bool {
+        match self {
+            Notifier::Desktop { only_errors } | Notifier::Webhook { only_errors, .. } => *only_errors,
+        }
+    }
+}
+
+// what a run did with a slideshow
+#[derive(Serialize, Debug, Clone)]
+pub struct SlideshowResult {
+    pub name: String,
+    pub output: PathBuf,
+    pub n_matched: usize,
+    pub n_written: usize,
+    // since the previous output, none when there was none
+    pub n_new: Option<usize>,
+    pub error: Option<String>,
+}
+
+impl SlideshowResult {
+    pub fn from_report(report: &SlideshowReport) -> Self {
+        Self {
+            name: report.name.clone(),
+            output: report.output.clone(),
+            n_matched: report.counts.n_matched,
+            n_written: report.n_written,
+            n_new: report.diff.as_ref().map(|diff| diff.added.len()),
+            error: None,
+        }
+    }
+
+    pub fn failed(slideshow: &SlideshowConfig, e: &anyhow::Error) -> Self {
+        Self {
+            name: slideshow.name(),
+            output: slideshow.path.clone(),
+            n_matched: 0,
+            n_written: 0,
+            n_new: None,
+            error: Some(format!("{:#}", e)),
+        }
+    }
+}
+
+// of the slideshows of a run, and the error which stopped it if any
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct RunSummary {
+    pub slideshows: Vec<SlideshowResult>,
+    pub error: Option<String>,
+}
+
+impl RunSummary {
+    pub fn has_errors(&self) -> bool {
+        self.error.is_some() || self.slideshows.iter().any(|result| result.error.is_some())
+    }
+
+    // a line per slideshow, e.g. "family: 120 images, 5 new"
+    fn text(&self) -> String {
+        let mut lines: Vec<String> = self.slideshows.iter().map(|result| match (&result.error, result.n_new) {
+            (Some(error), _) => format!("{}: failed: {}", result.name, error),
+            (None, Some(n_new)) => format!("{}: {} images, {} new", result.name, result.n_written, n_new),
+            (None, None) => format!("{}: {} images", result.name, result.n_written),
+        }).collect();
+        if let Some(error) = &self.error {
+            lines.push(format!("failed: {}", error));
+        }
+        lines.join("\n")
+    }
+}
+
+// a failed notifier is only warned, so that it never fails the run
+pub async fn notify(notifiers: &[Notifier], summary: &RunSummary) {
+    for notifier in notifiers {
+        if notifier.only_errors() && !summary.has_errors() {
+            continue;
+        }
+        let result = match notifier {
+            Notifier::Desktop { .. } => notify_desktop(summary).await,
+            Notifier::Webhook { url, .. } => post_webhook(url, summary).await,
+        };
+        if let Err(e) = result {
+            warn!("Failed to notify: {:?}: {:#}", notifier, e);
+        }
+    }
+}
+
+// the texts are passed in the environment, so that there's nothing to escape
+async fn notify_desktop(summary: &RunSummary) -> Result<()> {
+    let title = if summary.has_errors() { format!("{}: failed", TITLE) } else { TITLE.to_string() };
+    let body = summary.text();
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("powershell");
+        command.args(["-NoProfile", "-NonInteractive", "-Command", WINDOWS_TOAST_SCRIPT]);
+        command
+    } else if cfg!(target_os = "macos") {
+        let mut command = Command::new("osascript");
+        command.args(["-e", r#"display notification (system attribute "NOTIFICATION_BODY") with title (system attribute "NOTIFICATION_TITLE")"#]);
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.arg(&title).arg(&body);
+        command
+    };
+    command.env("NOTIFICATION_TITLE", &title).env("NOTIFICATION_BODY", &body);
+    let status = command.status().await?;
+    if !status.success() {
+        return Err(Error::NotifierError(status.to_string()).into());
+    }
+    Ok(())
+}
+
+// the body on stdin, as it may be longer than a command line
+async fn post_webhook(url: &str, summary: &RunSummary) -> Result<()> {
+    debug!("post: {}", url);
+    let body = serde_json::to_vec(summary)?;
+    let mut curl = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--max-time", "30", "-X", "POST", "-H", "Content-Type: application/json", "--data-binary", "@-"])
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()?;
+    let mut stdin = curl.stdin.take().expect("piped");
+    stdin.write_all(&body).await?;
+    // closed, so that curl sends it
+    drop(stdin);
+    let status = curl.wait().await?;
+    if !status.success() {
+        return Err(Error::NotifierError(format!("curl {}", status)).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,320 @@
+use std::path::{Component, Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use anyhow::Result;
+use tracing::debug;
+use crate::{
+    Error,
+    archive::unpack_archive_entry,
+    config::SlideshowConfig,
+    crop::{cropped_copy, first_frame_copy},
+    ffconcat::{FfconcatWriter, VideoOptions},
+    filter::AnimatedPolicy,
+    format::{FormatHeader, SlideshowFormat},
+    html::HtmlWriter,
+    m3u::{M3uWriter, read_m3u},
+    duration::{DurationRule, display_duration_secs},
+    entry_options::{EntryRule, entry_options},
+    image_info::ImageInfo,
+    info::info_text,
+    slideshow::{EntryOptions, ExistingSlideshow, SlideshowWriter, read_slideshow},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    // xnview slideshow
+    #[default]
+    Sld,
+    #[serde(alias = "m3u")]
+    M3u8,
+    // a single file gallery with the images embedded
+    Html,
+    // an ffmpeg concat script, rendered into a video when video_path is given
+    Ffconcat,
+}
+
+// one of the format backends, picked by the output_format of the slideshow
+enum OutputBackend {
+    Sld(SlideshowWriter),
+    M3u8(M3uWriter),
+    Html(HtmlWriter),
+    Ffconcat(FfconcatWriter),
+}
+
+impl SlideshowFormat for OutputBackend {
+    async fn write_header(&mut self, header: &FormatHeader<'_>) -> Result<()> {
+        match self {
+            Self::Sld(writer) => writer.write_header(header).await,
+            Self::M3u8(writer) => writer.write_header(header).await,
+            Self::Html(writer) => writer.write_header(header).await,
+            Self::Ffconcat(writer) => writer.write_header(header).await,
+        }
+    }
+
+    async fn write_entry(&mut self, path: &Path, entry_options: &EntryOptions<'_>) -> Result<()> {
+        match self {
+            Self::Sld(writer) => writer.write_entry(path, entry_options).await,
+            Self::M3u8(writer) => writer.write_entry(path, entry_options).await,
+            Self::Html(writer) => writer.write_entry(path, entry_options).await,
+            Self::Ffconcat(writer) => writer.write_entry(path, entry_options).await,
+        }
+    }
+
+    async fn finish(&mut self) -> Result<()> {
+        match self {
+            Self::Sld(writer) => writer.finish().await,
+            Self::M3u8(writer) => writer.finish().await,
+            Self::Html(writer) => writer.finish().await,
+            Self::Ffconcat(writer) => writer.finish().await,
+        }
+    }
+}
+
+// written to a temp file next to the output, and renamed into place by finish, so that a failed run
+// leaves the previous output as is
+pub struct OutputWriter {
+    backend: OutputBackend,
+    temp_path: PathBuf,
+    path: PathBuf,
+    backup: bool,
+    // the image paths are written relative to this when given
+    base_dir: Option<PathBuf>,
+    duration_rules: Vec<DurationRule>,
+    entry_rules: Vec<EntryRule>,
+    info_template: Option<String>,
+    // the cropped copies are written instead of the images when given
+    export_dir: Option<PathBuf>,
+    // of the animated images, already a still when cropped
+    first_frame_only: bool,
+    // the images in the archives are written as the unpacked copies
+    unpack_archives: bool,
+    // of the screen, e.g. of the monitor
+    width: u32,
+    height: u32,
+}
+
+impl OutputWriter {
+    pub async fn from_slideshow(slideshow: &SlideshowConfig) -> Result<Self> {
+        Self::from_slideshow_to_path(slideshow, &slideshow.path).await
+    }
+
+    // e.g. for the split outputs
+    pub async fn from_slideshow_to_path(slideshow: &SlideshowConfig, path: &Path) -> Result<Self> {
+        Self::from_slideshow_to_screen(slideshow, path, slideshow.width, slideshow.height).await
+    }
+
+    // e.g. for the outputs of the monitors
+    pub async fn from_slideshow_to_screen(slideshow: &SlideshowConfig, path: &Path, width: u32, height: u32) -> Result<Self> {
+        let temp_path = temp_path(path);
+        let backend = match slideshow.output_format {
+            OutputFormat::Sld => OutputBackend::Sld(SlideshowWriter::from_path(&temp_path, slideshow.encoding).await?.with_unencodable_paths(slideshow.unencodable_paths)),
+            OutputFormat::M3u8 => OutputBackend::M3u8(M3uWriter::from_path(&temp_path).await?.with_unencodable_paths(slideshow.unencodable_paths)),
+            OutputFormat::Html => OutputBackend::Html(HtmlWriter::from_path(&temp_path).await?),
+            OutputFormat::Ffconcat => {
+                let video_options = VideoOptions {
+                    width,
+                    height,
+                    slide_duration_secs: slideshow.slide_duration_secs,
+                    crossfade_secs: slideshow.crossfade_secs,
+                    video_path: slideshow.video_path.clone(),
+                };
+                OutputBackend::Ffconcat(FfconcatWriter::from_path(&temp_path, video_options).await?)
+            }
+        };
+        Ok(Self {
+            backend,
+            temp_path,
+            path: path.to_path_buf(),
+            backup: slideshow.backup,
+            base_dir: base_dir(slideshow, path),
+            duration_rules: slideshow.durations.clone(),
+            entry_rules: slideshow.entry_rules.clone(),
+            info_template: slideshow.info_template(),
+            export_dir: slideshow.export_dir.clone(),
+            first_frame_only: slideshow.filter.animated == AnimatedPolicy::FirstFrameOnly,
+            unpack_archives: slideshow.unpack_archives,
+            width,
+            height,
+        })
+    }
+
+    // the existing output is copied to the temp file first, so that appending is atomic too
+    pub async fn append_to_slideshow(slideshow: &SlideshowConfig) -> Result<Self> {
+        let temp_path = temp_path(&slideshow.path);
+        tokio::fs::copy(&slideshow.path, &temp_path).await?;
+        let backend = match slideshow.output_format {
+            OutputFormat::Sld => OutputBackend::Sld(SlideshowWriter::append_to_path(&temp_path, slideshow.encoding).await?.with_unencodable_paths(slideshow.unencodable_paths)),
+            OutputFormat::M3u8 => OutputBackend::M3u8(M3uWriter::append_to_path(&temp_path).await?.with_unencodable_paths(slideshow.unencodable_paths)),
+            OutputFormat::Html | OutputFormat::Ffconcat => return Err(Error::IncrementalUnsupportedError(slideshow.path.clone()).into()),
+        };
+        Ok(Self {
+            backend,
+            temp_path,
+            path: slideshow.path.clone(),
+            backup: slideshow.backup,
+            base_dir: base_dir(slideshow, &slideshow.path),
+            duration_rules: slideshow.durations.clone(),
+            entry_rules: slideshow.entry_rules.clone(),
+            info_template: slideshow.info_template(),
+            export_dir: slideshow.export_dir.clone(),
+            first_frame_only: slideshow.filter.animated == AnimatedPolicy::FirstFrameOnly,
+            unpack_archives: slideshow.unpack_archives,
+            width: slideshow.width,
+            height: slideshow.height,
+        })
+    }
+
+    pub async fn write_raw_header(&mut self, header: &str) -> Result<()> {
+        match &mut self.backend {
+            OutputBackend::Sld(writer) => writer.write_raw_header(header).await,
+            OutputBackend::M3u8(writer) => writer.write_raw_header(header).await,
+            // read_output already refuses it
+            OutputBackend::Html(_) | OutputBackend::Ffconcat(_) => unreachable!("no existing header to keep"),
+        }
+    }
+
+    pub async fn write_header(&mut self, slideshow: &SlideshowConfig) -> Result<()> {
+        let title = self.path.file_stem().map(|file_stem| file_stem.to_string_lossy().to_string()).unwrap_or_default();
+        let slideshow_header = slideshow.header();
+        let header = FormatHeader { title: &title, width: self.width, height: self.height, slideshow_header: &slideshow_header };
+        self.backend.write_header(&header).await
+    }
+
+    // (width, height) of the screen
+    pub fn screen_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    // shown for the duration of the first matching rule, with the info of the template and the options of the entry rules
+    pub async fn write_image(&mut self, image_info: &ImageInfo) -> Result<()> {
+        let unpacked_path = if self.unpack_archives { unpack_archive_entry(&image_info.path).await? } else { None };
+        let unpacked_image_info;
+        let image_info = match unpacked_path {
+            Some(unpacked_path) => {
+                unpacked_image_info = ImageInfo { path: unpacked_path, ..image_info.clone() };
+                &unpacked_image_info
+            }
+            _ => image_info,
+        };
+        let duration_secs = display_duration_secs(&self.duration_rules, image_info);
+        let info = self.info_template.as_deref().and_then(|info_template| info_text(info_template, image_info));
+        let (effect, stretch) = entry_options(&self.entry_rules, image_info);
+        let path = match &self.export_dir {
+            Some(export_dir) if !image_info.is_video => cropped_copy(image_info, export_dir, self.width, self.height).await?,
+            _ if self.first_frame_only && image_info.animated => first_frame_copy(image_info).await?,
+            _ => image_info.path.clone(),
+        };
+        self.write_image_entry(&path, &EntryOptions { duration_secs, info: info.as_deref(), effect, stretch }).await
+    }
+
+    // e.g. the kept ones of the existing output, which are listed without their image info
+    pub async fn write_image_path(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        self.write_image_entry(path, &EntryOptions::default()).await
+    }
+
+    // the effect and the stretch are of XnView's slideshow only
+    async fn write_image_entry(&mut self, path: impl AsRef<Path>, entry_options: &EntryOptions<'_>) -> Result<()> {
+        debug!("write: {}", path.as_ref().display());
+        let path = match &self.base_dir {
+            Some(base_dir) => relative_path(path.as_ref(), base_dir),
+            None => path.as_ref().to_path_buf(),
+        };
+        self.backend.write_entry(&path, entry_options).await
+    }
+
+    // must be called after the last image, e.g. html closes the tags here, and nothing is in place until then
+    #[tracing::instrument(name = "write", skip_all, fields(path = %self.path.display()))]
+    pub async fn finish(&mut self) -> Result<()> {
+        self.backend.finish().await?;
+        if self.backup && tokio::fs::try_exists(&self.path).await? {
+            // copied rather than renamed, so that there's no moment without the output
+            tokio::fs::copy(&self.path, backup_path(&self.path)).await?;
+        }
+        tokio::fs::rename(&self.temp_path, &self.path).await?;
+        Ok(())
+    }
+}
+
+// e.g. of a failed or interrupted run, nothing is left after finish as the temp file is renamed
+impl Drop for OutputWriter {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.temp_path);
+    }
+}
+
+// in the same dir, as rename is atomic only within a file system
+fn temp_path(path: &Path) -> PathBuf {
+    let file_name = path.file_name().map(|file_name| file_name.to_string_lossy().to_string()).unwrap_or_default();
+    path.with_file_name(format!(".{}.tmp", file_name))
+}
+
+// e.g. "family.sld.bak"
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut backup_path = path.as_os_str().to_owned();
+    backup_path.push(".bak");
+    PathBuf::from(backup_path)
+}
+
+fn base_dir(slideshow: &SlideshowConfig, path: &Path) -> Option<PathBuf> {
+    if !slideshow.relative_paths {
+        return None;
+    }
+    Some(path.parent().map(Path::to_path_buf).unwrap_or_default())
+}
+
+// lexically, as the output may be written before the images are in place, e.g. on another machine,
+// and as is when there's no relative path, e.g. on another drive
+fn relative_path(path: &Path, base_dir: &Path) -> PathBuf {
+    let path = normalize_path(path);
+    let base_dir = normalize_path(base_dir);
+    let mut path_components = path.components().peekable();
+    let mut base_dir_components = base_dir.components().peekable();
+    while let (Some(path_component), Some(base_dir_component)) = (path_components.peek(), base_dir_components.peek()) {
+        if path_component != base_dir_component {
+            break;
+        }
+        path_components.next();
+        base_dir_components.next();
+    }
+    let base_dir_components: Vec<Component> = base_dir_components.collect();
+    let path_components: Vec<Component> = path_components.collect();
+    let is_other_root = base_dir_components.iter().any(|component| !matches!(component, Component::Normal(_)))
+        || matches!(path_components.first(), Some(Component::Prefix(_) | Component::RootDir));
+    if is_other_root {
+        return path.clone();
+    }
+    let mut relative_path: PathBuf = base_dir_components.iter().map(|_| Component::ParentDir).collect();
+    relative_path.extend(path_components);
+    relative_path
+}
+
+// "a/./b/../c" is "a/c", the symlinks are not resolved
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized_path = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(normalized_path.components().next_back(), Some(Component::Normal(_))) => {
+                normalized_path.pop();
+            }
+            component => normalized_path.push(component),
+        }
+    }
+    normalized_path
+}
+
+// relative paths are of the dir of the output
+pub async fn read_output(slideshow: &SlideshowConfig) -> Result<ExistingSlideshow> {
+    let mut existing_slideshow = match slideshow.output_format {
+        OutputFormat::Sld => read_slideshow(&slideshow.path, slideshow.encoding).await?,
+        OutputFormat::M3u8 => read_m3u(&slideshow.path).await?,
+        OutputFormat::Html | OutputFormat::Ffconcat => return Err(Error::IncrementalUnsupportedError(slideshow.path.clone()).into()),
+    };
+    let output_dir = slideshow.path.parent().unwrap_or(Path::new(""));
+    for path in existing_slideshow.paths.iter_mut() {
+        if path.is_relative() {
+            *path = normalize_path(&output_dir.join(&*path));
+        }
+    }
+    Ok(existing_slideshow)
+}
@@ -0,0 +1,59 @@
+use std::{path::{Path, PathBuf}, time::{Duration, SystemTime}};
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate};
+use regex::Regex;
+use tracing::info;
+use crate::clock;
+
+// (placeholder, the pattern it matches in the outputs of the other days)
+const PLACEHOLDERS: [(&str, &str); 4] = [
+    ("{today}", r"\d{4}-\d{2}-\d{2}"),
+    ("{year}", r"\d{4}"),
+    ("{month}", r"\d{2}"),
+    ("{name}", ".+"),
+];
+
+pub fn has_placeholders(path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    PLACEHOLDERS.iter().any(|(placeholder, _)| path.contains(placeholder))
+}
+
+// e.g. "D:/slides/{name}-{today}.sld" of family is "D:/slides/family-2024-06-15.sld"
+pub fn resolve_path_template(path_template: &Path, name: &str, today: NaiveDate) -> PathBuf {
+    let path = path_template.to_string_lossy()
+        .replace("{today}", &today.format("%Y-%m-%d").to_string())
+        .replace("{year}", &today.year().to_string())
+        .replace("{month}", &format!("{:02}", today.month()))
+        .replace("{name}", name);
+    PathBuf::from(path)
+}
+
+// the outputs of the other days in the dir of the template, by the modified time, where the placeholders of the file
+// name match the dates, e.g. "family-2024-06-01.sld" of "{name}-{today}.sld", and the current output is always kept
+pub async fn remove_expired_outputs(path_template: &Path, name: &str, current_path: &Path, retention_days: u64) -> Result<usize> {
+    let (Some(dir), Some(file_name_template)) = (path_template.parent(), path_template.file_name()) else {
+        return Ok(0);
+    };
+    let mut pattern = regex::escape(&file_name_template.to_string_lossy().replace("{name}", name));
+    for (placeholder, placeholder_pattern) in PLACEHOLDERS {
+        pattern = pattern.replace(&regex::escape(placeholder), placeholder_pattern);
+    }
+    let file_name_regex = Regex::new(&format!("^{}$", pattern))?;
+    let expiry = SystemTime::from(clock::now()) - Duration::from_secs(retention_days * 24 * 60 * 60);
+    let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    let mut n_removed = 0;
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path == current_path || !file_name_regex.is_match(&entry.file_name().to_string_lossy()) {
+            continue;
+        }
+        let metadata = entry.metadata().await?;
+        if metadata.is_file() && metadata.modified()? < expiry {
+            info!("Remove the expired output: {}", path.display());
+            tokio::fs::remove_file(&path).await?;
+            n_removed += 1;
+        }
+    }
+    Ok(n_removed)
+}
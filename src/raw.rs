@@ -0,0 +1,87 @@
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use anyhow::Result;
+use crate::Error;
+
+const RAW_EXTENSIONS: [&str; 9] = ["cr2", "cr3", "nef", "nrw", "arw", "raf", "orf", "rw2", "dng"];
+const JPEG_EXTENSIONS: [&str; 4] = ["jpg", "jpeg", "JPG", "JPEG"];
+
+// which one of a raw and a jpeg of the same stem in the same dir is listed, so that each shot shows once
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RawPairing {
+    PreferJpeg,
+    PreferRaw,
+    #[default]
+    Both,
+}
+
+pub fn is_raw_path(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+        .is_some_and(|extension| RAW_EXTENSIONS.contains(&extension.as_str()))
+}
+
+pub fn is_jpeg_path(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| JPEG_EXTENSIONS.contains(&extension.to_string_lossy().as_ref()))
+}
+
+// whether the path is the one left out of its pair
+pub async fn is_paired_away(path: &Path, raw_pairing: RawPairing) -> bool {
+    match raw_pairing {
+        RawPairing::PreferJpeg => is_raw_path(path) && sibling_jpeg_path(path).await.is_some(),
+        RawPairing::PreferRaw => is_jpeg_path(path) && sibling_raw_path(path).await.is_some(),
+        RawPairing::Both => false,
+    }
+}
+
+pub async fn sibling_raw_path(path: &Path) -> Option<PathBuf> {
+    for extension in RAW_EXTENSIONS.iter().flat_map(|extension| [extension.to_string(), extension.to_ascii_uppercase()]) {
+        let sibling_path = path.with_extension(extension);
+        if tokio::fs::try_exists(&sibling_path).await.unwrap_or(false) {
+            return Some(sibling_path);
+        }
+    }
+    None
+}
+
+// the jpeg with the same stem in the same dir, e.g. DSC0001.JPG of DSC0001.ARW
+pub async fn sibling_jpeg_path(path: &Path) -> Option<PathBuf> {
+    for extension in JPEG_EXTENSIONS {
+        let sibling_path = path.with_extension(extension);
+        if tokio::fs::try_exists(&sibling_path).await.unwrap_or(false) {
+            return Some(sibling_path);
+        }
+    }
+    None
+}
+
+// when the exif has no size, the biggest embedded jpeg preview is the closest, as it's usually full size
+pub async fn read_raw_preview_size(path: &Path) -> Result<(u32, u32)> {
+    let data = tokio::fs::read(path).await?;
+    let size = embedded_jpeg_sizes(&data).max_by_key(|(width, height)| *width as u64 * *height as u64);
+    size.ok_or_else(|| Error::RawSizeError(path.to_path_buf()).into())
+}
+
+// the sizes in the start of frame segments, without decoding the jpegs
+fn embedded_jpeg_sizes(data: &[u8]) -> impl Iterator<Item = (u32, u32)> + '_ {
+    let starts = data.windows(3).enumerate().filter(|(_, window)| *window == [0xff, 0xd8, 0xff]).map(|(i, _)| i);
+    starts.filter_map(|start| {
+        let mut i = start + 2;
+        // walk the segments until the start of frame
+        loop {
+            if *data.get(i)? != 0xff {
+                return None;
+            }
+            let marker = *data.get(i + 1)?;
+            let length = u16::from_be_bytes([*data.get(i + 2)?, *data.get(i + 3)?]) as usize;
+            // SOF0 to SOF15, except DHT, JPG and DAC
+            if (0xc0..=0xcf).contains(&marker) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+                let height = u16::from_be_bytes([*data.get(i + 5)?, *data.get(i + 6)?]) as u32;
+                let width = u16::from_be_bytes([*data.get(i + 7)?, *data.get(i + 8)?]) as u32;
+                return (width > 0 && height > 0).then_some((width, height));
+            }
+            i += 2 + length;
+        }
+    })
+}
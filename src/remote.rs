@@ -0,0 +1,115 @@
+use std::{collections::HashSet, path::{Path, PathBuf}, process::Stdio, time::SystemTime};
+use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, stream};
+use tokio::process::Command;
+use anyhow::Result;
+use tracing::debug;
+use crate::{Error, cache::cache_parent_dir, raw};
+
+// enough for the exif, the xmp and the size in the headers of the photos, the pixels are never fetched
+const HEAD_BYTES: u64 = 256 * 1024;
+// files fetched at once, each is a round trip to the server
+const FETCH_CONCURRENCY: usize = 8;
+
+// e.g. "webdav://nas.local/photos", "s3://bucket/photos" or "smb://nas.local/share/photos"
+pub fn is_remote(path: &Path) -> bool {
+    path.to_string_lossy().contains("://")
+}
+
+// an on-the-fly remote of rclone, which does the listing and the fetching, with the credentials of its env vars,
+// e.g. RCLONE_WEBDAV_USER or AWS_ACCESS_KEY_ID
+fn rclone_remote(url: &str) -> Result<String> {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return Err(Error::RemoteUrlError(url.to_string()).into());
+    };
+    let remote = match scheme {
+        "webdav" => format!(":webdav,url='https://{}':", rest),
+        "s3" => format!(":s3:{}", rest),
+        "smb" => match rest.split_once('/') {
+            Some((host, share_path)) => format!(":smb,host={}:{}", host, share_path),
+            None => return Err(Error::RemoteUrlError(url.to_string()).into()),
+        },
+        _ => return Err(Error::RemoteUrlError(url.to_string()).into()),
+    };
+    Ok(remote)
+}
+
+pub fn check_remote_url(url: &str) -> Result<()> {
+    rclone_remote(url).map(|_| ())
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct ListedFile {
+    path: String,
+    mod_time: DateTime<Utc>,
+}
+
+// the heads of the remote images are kept in a dir of the cache under the same relative paths, so that the scan
+// reads them as local files, and only the changed ones are fetched again by the modified times
+pub async fn fetch_remote_heads(url: &str) -> Result<PathBuf> {
+    let remote = rclone_remote(url)?;
+    let mirror_dir = cache_parent_dir().await?.join("remote").join(format!("{:016x}", xxhash_rust::xxh3::xxh3_64(url.as_bytes())));
+    tokio::fs::create_dir_all(&mirror_dir).await?;
+    let output = Command::new("rclone").args(["lsjson", "-R", "--files-only"]).arg(&remote).stderr(Stdio::inherit()).output().await?;
+    if !output.status.success() {
+        return Err(Error::RcloneError(output.status.to_string()).into());
+    }
+    let listed_files: Vec<ListedFile> = serde_json::from_slice(&output.stdout)?;
+    let listed_files: Vec<ListedFile> = listed_files.into_iter().filter(|listed_file| is_media_path(Path::new(&listed_file.path))).collect();
+    let listed_paths: HashSet<PathBuf> = listed_files.iter().map(|listed_file| mirror_dir.join(&listed_file.path)).collect();
+    let mut fetches = stream::iter(listed_files)
+        .map(|listed_file| fetch_head(&remote, &mirror_dir, listed_file))
+        .buffer_unordered(FETCH_CONCURRENCY);
+    while let Some(result) = fetches.next().await {
+        result?;
+    }
+    remove_unlisted(&mirror_dir, &listed_paths).await?;
+    Ok(mirror_dir)
+}
+
+pub fn is_media_path(path: &Path) -> bool {
+    raw::is_raw_path(path) || mime_guess::from_path(path).iter().any(|mime| mime.type_() == "image" || mime.type_() == "video")
+}
+
+async fn fetch_head(remote: &str, mirror_dir: &Path, listed_file: ListedFile) -> Result<()> {
+    let head_path = mirror_dir.join(&listed_file.path);
+    let mod_time = SystemTime::from(listed_file.mod_time);
+    if let Ok(metadata) = tokio::fs::metadata(&head_path).await {
+        if metadata.modified().ok() == Some(mod_time) {
+            return Ok(());
+        }
+    }
+    debug!("fetch: {}", listed_file.path);
+    let remote_path = format!("{}/{}", remote.trim_end_matches('/'), listed_file.path);
+    let output = Command::new("rclone").args(["cat", "--count", &HEAD_BYTES.to_string()]).arg(&remote_path).stderr(Stdio::inherit()).output().await?;
+    if !output.status.success() {
+        return Err(Error::RcloneError(output.status.to_string()).into());
+    }
+    if let Some(parent) = head_path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(&head_path, &output.stdout).await?;
+    // the modified time of the remote, for the next run and the file-time dates
+    let head_file = std::fs::File::options().write(true).open(&head_path)?;
+    head_file.set_modified(mod_time)?;
+    Ok(())
+}
+
+// of the files deleted on the remote, so that they don't stay in the slideshows
+pub async fn remove_unlisted(mirror_dir: &Path, listed_paths: &HashSet<PathBuf>) -> Result<()> {
+    let mut dir_stack = vec![mirror_dir.to_path_buf()];
+    while let Some(dir) = dir_stack.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if entry.file_type().await?.is_dir() {
+                dir_stack.push(path);
+            } else if !listed_paths.contains(&path) {
+                tokio::fs::remove_file(&path).await?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use std::{path::PathBuf, time::Duration};
+use serde::Serialize;
+use crate::{config::SlideshowConfig, diff::SlideshowDiff, scan::{ScanCounts, ScanStats, ScanTimings, SkippedFile}};
+
+// of what the subcommands print on stdout, where json is an object per line,
+// so that the runs of watch and daemon can be read as they finish
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+// what a run did with a slideshow
+#[derive(Serialize, Debug)]
+pub struct SlideshowReport {
+    pub name: String,
+    pub output: PathBuf,
+    // of this run, so the kept ones of --incremental are not counted
+    pub n_written: usize,
+    #[serde(flatten)]
+    pub counts: ScanCounts,
+    pub cache_hit_rate: Option<f64>,
+    pub duration_secs: f64,
+    pub skipped_files: Vec<SkippedFile>,
+    // with --list-no-exif
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub no_exif_paths: Vec<PathBuf>,
+    // with --diff or the notifiers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<SlideshowDiff>,
+    // of list
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub images: Option<Vec<PathBuf>>,
+    // with --bench
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bench: Option<BenchReport>,
+}
+
+// of --bench, the stages of the scan with the ones after it
+#[derive(Serialize, Debug, Clone)]
+pub struct BenchReport {
+    #[serde(flatten)]
+    pub scan: ScanTimings,
+    pub scan_secs: f64,
+    pub sort_secs: f64,
+    // with the thumbnails, and with --fast the writes are in the scan as they are interleaved
+    pub write_secs: f64,
+}
+
+impl BenchReport {
+    // one line per stage, to print at the end
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = vec![format!("scan: {:.3}s", self.scan_secs)];
+        lines.extend(self.scan.summary().into_iter().map(|line| format!("  {}", line)));
+        lines.push(format!("sort: {:.3}s", self.sort_secs));
+        lines.push(format!("write: {:.3}s", self.write_secs));
+        lines
+    }
+}
+
+impl SlideshowReport {
+    pub fn new(slideshow: &SlideshowConfig, stats: &ScanStats, n_written: usize, duration: Duration) -> Self {
+        let counts = stats.counts();
+        Self {
+            name: slideshow.name(),
+            output: slideshow.path.clone(),
+            n_written,
+            cache_hit_rate: counts.cache_hit_rate(),
+            counts,
+            duration_secs: duration.as_secs_f64(),
+            skipped_files: stats.skipped_files(),
+            no_exif_paths: Vec::new(),
+            diff: None,
+            images: None,
+            bench: None,
+        }
+    }
+}
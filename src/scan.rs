@@ -0,0 +1,964 @@
+use std::{collections::{BTreeMap, HashMap, HashSet}, path::{Path, PathBuf}, sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}}, time::{Duration, SystemTime}};
+use tokio::{sync::Semaphore, time::Instant};
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use junk_file;
+use async_stream::stream;
+use futures::{future, StreamExt, stream::FuturesUnordered};
+use indicatif::ProgressBar;
+use tracing::{Instrument, debug, debug_span, warn};
+use crate::{Error, archive::{self, archive_entry_path}, cache::{CacheOptions, cache_parent_dir, cached_image_info}, catalog::Catalog, config::SlideshowConfig, date::{DateOptions, apply_date_options}, filter::{DirFilters, FilterReason}, image_info::{AnalysisOptions, ImageInfo}, junk::is_junk_name, live_photo::{self, LivePhotoPairing}, raw::{self, RawPairing}, takeout::apply_takeout_metadata};
+
+#[derive(Debug, Clone)]
+pub struct WalkOptions {
+    pub skip_junk: bool,
+    // lowercase, only with skip_junk
+    pub junk_names: Vec<String>,
+    pub include_hidden: bool,
+    pub include_videos: bool,
+    pub include_raw: bool,
+    // only with include_raw, as otherwise there are no pairs
+    pub raw_pairing: RawPairing,
+    // only with include_videos, for the same reason
+    pub live_photo_pairing: LivePhotoPairing,
+    // excluded dirs are not descended into
+    pub exclude_globs: GlobSet,
+    // only for files, empty means all
+    pub include_globs: GlobSet,
+    // lowercase without the dot
+    pub extensions: Vec<String>,
+    pub excluded_extensions: Vec<String>,
+    // dirs read at once, apart from the images parsed at once
+    pub concurrency: usize,
+    // descend into symlinked dirs, each real dir is read once so that cycles end
+    pub follow_symlinks: bool,
+    // in bytes
+    pub min_file_size: Option<u64>,
+    pub max_file_size: Option<u64>,
+    // the images in the zip files are listed too, by their heads written to the cache
+    pub include_archives: bool,
+    // with include_archives, the whole images instead of the heads, e.g. for the analysis decoding the pixels
+    pub whole_archive_entries: bool,
+}
+
+impl WalkOptions {
+    pub fn from_slideshow(slideshow: &SlideshowConfig) -> Result<Self> {
+        let junk = slideshow.junk.clone().unwrap_or_default();
+        Ok(Self {
+            skip_junk: slideshow.skip_junk,
+            junk_names: junk.lowercase_names(),
+            include_hidden: slideshow.include_hidden,
+            include_videos: slideshow.include_videos,
+            include_raw: slideshow.include_raw,
+            raw_pairing: if slideshow.include_raw { slideshow.raw_pairing() } else { RawPairing::Both },
+            live_photo_pairing: if slideshow.include_videos { slideshow.live_photos } else { LivePhotoPairing::Both },
+            exclude_globs: build_glob_set(&slideshow.exclude_globs)?,
+            include_globs: build_glob_set(&slideshow.include_globs)?,
+            extensions: normalize_extensions(&slideshow.extensions),
+            excluded_extensions: normalize_extensions(&slideshow.excluded_extensions),
+            concurrency: slideshow.walk_concurrency,
+            follow_symlinks: slideshow.follow_symlinks,
+            min_file_size: slideshow.min_file_size.or(junk.min_file_size.filter(|_| slideshow.skip_junk)).map(|file_size| file_size.0),
+            max_file_size: slideshow.max_file_size.map(|file_size| file_size.0),
+            include_archives: slideshow.include_archives,
+            whole_archive_entries: slideshow.analysis_options().needs_decode(),
+        })
+    }
+}
+
+// e.g. ".JPG" is "jpg"
+fn normalize_extensions(extensions: &[String]) -> Vec<String> {
+    extensions.iter().map(|extension| extension.trim().trim_start_matches('.').to_ascii_lowercase()).collect()
+}
+
+pub fn build_glob_set(globs: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob)?);
+    }
+    Ok(builder.build()?)
+}
+
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    pub n_threads: usize,
+    pub cache_options: CacheOptions,
+    pub analysis_options: AnalysisOptions,
+    pub walk_options: WalkOptions,
+    pub date_options: DateOptions,
+    // limits the total size of the files processed at once, instead of just the count
+    pub max_inflight_bytes: Option<u64>,
+    // paths not to process at all, e.g. the ones already in the slideshow
+    pub skip_paths: Arc<HashSet<PathBuf>>,
+    pub stats: Arc<ScanStats>,
+    // read instead of walking the dirs when given
+    pub catalog: Option<Arc<Catalog>>,
+    pub takeout: bool,
+    // e.g. not to saturate a nas shared with others
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    // a file failing to be parsed fails the whole scan, instead of being skipped and reported
+    pub strict: bool,
+    // the dirs of the fetched heads of the remote dirs and their mapped paths, the heads are scanned for the mapped ones
+    pub remote_mirrors: Vec<(PathBuf, PathBuf)>,
+}
+
+// spaces out the starts of the files, by the count or the size whichever is slower
+#[derive(Debug)]
+pub struct RateLimiter {
+    max_files_per_sec: Option<f64>,
+    max_bytes_per_sec: Option<u64>,
+    next_start: tokio::sync::Mutex<Instant>,
+}
+
+impl RateLimiter {
+    // none when unlimited
+    pub fn new(max_files_per_sec: Option<f64>, max_bytes_per_sec: Option<u64>) -> Option<Self> {
+        let max_files_per_sec = max_files_per_sec.filter(|max_files_per_sec| *max_files_per_sec > 0.0);
+        let max_bytes_per_sec = max_bytes_per_sec.filter(|max_bytes_per_sec| *max_bytes_per_sec > 0);
+        if max_files_per_sec.is_none() && max_bytes_per_sec.is_none() {
+            return None;
+        }
+        Some(Self {
+            max_files_per_sec,
+            max_bytes_per_sec,
+            next_start: tokio::sync::Mutex::new(Instant::now()),
+        })
+    }
+
+    pub async fn wait(&self, size: u64) {
+        let interval_secs = f64::max(
+            self.max_files_per_sec.map_or(0.0, |max_files_per_sec| 1.0 / max_files_per_sec),
+            self.max_bytes_per_sec.map_or(0.0, |max_bytes_per_sec| size as f64 / max_bytes_per_sec as f64),
+        );
+        let start = {
+            let mut next_start = self.next_start.lock().await;
+            // no burst after an idle time
+            let start = (*next_start).max(Instant::now());
+            *next_start = start + Duration::from_secs_f64(interval_secs);
+            start
+        };
+        tokio::time::sleep_until(start).await;
+    }
+}
+
+// shared by the slideshows of a run, so that the dirs and the images under more than one of them are read once
+#[derive(Debug, Default)]
+pub struct ScanMemo {
+    // with the modified times of the dirs as of the listings
+    dir_entries: Mutex<HashMap<PathBuf, ListedDir>>,
+    // as parsed, before the dates and the metadata of each slideshow are applied
+    image_infos: Mutex<HashMap<PathBuf, ImageInfo>>,
+    // of the checkpoint, used only while the dirs and the files are unchanged since the interrupted run
+    checkpoint_dir_entries: Mutex<HashMap<PathBuf, ListedDir>>,
+    checkpoint_image_infos: Mutex<HashMap<PathBuf, ImageInfo>>,
+}
+
+#[derive(Debug, Clone)]
+struct ListedDir {
+    modified: Option<SystemTime>,
+    entries: Arc<Vec<(PathBuf, EntryKind)>>,
+}
+
+// the partial manifest of an interrupted run, the dirs listed and the images parsed so far
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Checkpoint {
+    dirs: Vec<CheckpointDir>,
+    image_infos: Vec<ImageInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CheckpointDir {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    entries: Vec<(PathBuf, EntryKind)>,
+}
+
+impl ScanMemo {
+    // the dirs and the images of an interrupted run, so that --resume doesn't read them again, unless changed since
+    pub async fn read_checkpoint() -> Result<Self> {
+        let checkpoint_path = checkpoint_path().await?;
+        if !tokio::fs::try_exists(&checkpoint_path).await? {
+            return Ok(Self::default());
+        }
+        let checkpoint: Checkpoint = serde_json::from_slice(&tokio::fs::read(&checkpoint_path).await?)?;
+        debug!("resume from {} dirs and {} images", checkpoint.dirs.len(), checkpoint.image_infos.len());
+        let checkpoint_dir_entries = checkpoint.dirs.into_iter()
+            .map(|dir| (dir.path, ListedDir { modified: dir.modified, entries: Arc::new(dir.entries) }))
+            .collect();
+        let checkpoint_image_infos = checkpoint.image_infos.into_iter().map(|image_info| (image_info.path.clone(), image_info)).collect();
+        Ok(Self {
+            checkpoint_dir_entries: Mutex::new(checkpoint_dir_entries),
+            checkpoint_image_infos: Mutex::new(checkpoint_image_infos),
+            ..Default::default()
+        })
+    }
+
+    // the ones of the checkpoint not read again by this run are kept for the next --resume
+    pub async fn write_checkpoint(&self) -> Result<()> {
+        let json = {
+            let mut dir_entries = self.checkpoint_dir_entries.lock().expect("not poisoned").clone();
+            dir_entries.extend(self.dir_entries.lock().expect("not poisoned").iter().map(|(dir, listed_dir)| (dir.clone(), listed_dir.clone())));
+            let mut image_infos = self.checkpoint_image_infos.lock().expect("not poisoned").clone();
+            image_infos.extend(self.image_infos.lock().expect("not poisoned").iter().map(|(path, image_info)| (path.clone(), image_info.clone())));
+            let checkpoint = Checkpoint {
+                dirs: dir_entries.into_iter()
+                    .map(|(path, listed_dir)| CheckpointDir { path, modified: listed_dir.modified, entries: listed_dir.entries.to_vec() })
+                    .collect(),
+                image_infos: image_infos.into_values().collect(),
+            };
+            serde_json::to_vec(&checkpoint)?
+        };
+        tokio::fs::write(checkpoint_path().await?, json).await?;
+        Ok(())
+    }
+
+    // after a run to the end, so that the next --resume doesn't read the dirs of the past
+    pub async fn remove_checkpoint() -> Result<()> {
+        let checkpoint_path = checkpoint_path().await?;
+        if tokio::fs::try_exists(&checkpoint_path).await? {
+            tokio::fs::remove_file(&checkpoint_path).await?;
+        }
+        Ok(())
+    }
+
+    fn image_info(&self, path: &Path, analysis_options: AnalysisOptions) -> Option<ImageInfo> {
+        let image_infos = self.image_infos.lock().expect("not poisoned");
+        let image_info = image_infos.get(path)?;
+        if !image_info.has_analyses(analysis_options) {
+            return None;
+        }
+        Some(image_info.clone())
+    }
+
+    fn insert_image_info(&self, image_info: &ImageInfo) {
+        self.image_infos.lock().expect("not poisoned").insert(image_info.path.clone(), image_info.clone());
+    }
+
+    // of the checkpoint, moved into the memo once found unchanged
+    async fn checkpoint_image_info(&self, path: &Path, analysis_options: AnalysisOptions) -> Option<ImageInfo> {
+        let image_info = self.checkpoint_image_infos.lock().expect("not poisoned").remove(path)?;
+        if !image_info.is_unchanged(analysis_options).await {
+            return None;
+        }
+        self.insert_image_info(&image_info);
+        Some(image_info)
+    }
+}
+
+async fn checkpoint_path() -> Result<PathBuf> {
+    Ok(cache_parent_dir().await?.join("checkpoint.json"))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum EntryKind {
+    Dir,
+    SymlinkedDir,
+    File,
+}
+
+// counted while scanning, the progress bar shows them as they change
+#[derive(Debug)]
+pub struct ScanStats {
+    n_discovered: AtomicUsize,
+    n_skipped: AtomicUsize,
+    n_parsed: AtomicUsize,
+    n_cache_hits: AtomicUsize,
+    n_matched: AtomicUsize,
+    n_filtered_out: Mutex<BTreeMap<FilterReason, usize>>,
+    skipped_files: Mutex<Vec<SkippedFile>>,
+    // only with --bench, as every read is kept
+    timings: Option<Mutex<Timings>>,
+    progress_bar: ProgressBar,
+}
+
+#[derive(Debug, Default)]
+struct Timings {
+    walk: Option<Duration>,
+    decoded: bool,
+    parses: Vec<Duration>,
+    cache_hits: Vec<Duration>,
+}
+
+// a file which failed to be parsed, unless strict
+#[derive(Serialize, Debug, Clone)]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl Default for ScanStats {
+    fn default() -> Self {
+        Self::new(ProgressBar::hidden())
+    }
+}
+
+impl ScanStats {
+    pub fn new(progress_bar: ProgressBar) -> Self {
+        Self {
+            n_discovered: AtomicUsize::new(0),
+            n_skipped: AtomicUsize::new(0),
+            n_parsed: AtomicUsize::new(0),
+            n_cache_hits: AtomicUsize::new(0),
+            n_matched: AtomicUsize::new(0),
+            n_filtered_out: Mutex::new(BTreeMap::new()),
+            skipped_files: Mutex::new(vec![]),
+            timings: None,
+            progress_bar,
+        }
+    }
+
+    pub fn with_timings(mut self) -> Self {
+        self.timings = Some(Mutex::new(Timings::default()));
+        self
+    }
+
+    fn record_walk(&self, walk: Duration) {
+        if let Some(timings) = &self.timings {
+            timings.lock().expect("not poisoned").walk = Some(walk);
+        }
+    }
+
+    fn record_read(&self, from_cache: bool, decoded: bool, read: Duration) {
+        if let Some(timings) = &self.timings {
+            let mut timings = timings.lock().expect("not poisoned");
+            if from_cache {
+                timings.cache_hits.push(read);
+            } else {
+                timings.decoded = decoded;
+                timings.parses.push(read);
+            }
+        }
+    }
+
+    // none unless with_timings
+    pub fn timings(&self) -> Option<ScanTimings> {
+        let timings = self.timings.as_ref()?.lock().expect("not poisoned");
+        Some(ScanTimings {
+            walk_secs: timings.walk.map(|walk| walk.as_secs_f64()),
+            decoded: timings.decoded,
+            parse: Latencies::new(&timings.parses),
+            cache_hit: Latencies::new(&timings.cache_hits),
+        })
+    }
+
+    fn count(&self, counter: &AtomicUsize) {
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.update_progress_bar();
+    }
+
+    fn count_filtered_out(&self, reason: FilterReason) {
+        *self.n_filtered_out.lock().expect("not poisoned").entry(reason).or_default() += 1;
+        self.update_progress_bar();
+    }
+
+    fn skip_file(&self, path: PathBuf, error: &anyhow::Error) {
+        self.skipped_files.lock().expect("not poisoned").push(SkippedFile {
+            path,
+            reason: format!("{:#}", error),
+        });
+        self.update_progress_bar();
+    }
+
+    pub fn skipped_files(&self) -> Vec<SkippedFile> {
+        self.skipped_files.lock().expect("not poisoned").clone()
+    }
+
+    fn update_progress_bar(&self) {
+        self.progress_bar.set_message(format!(
+            "discovered {}, parsed {}, cache hits {}, matched {}, filtered out {}, failed {}",
+            self.n_discovered.load(Ordering::Relaxed),
+            self.n_parsed.load(Ordering::Relaxed),
+            self.n_cache_hits.load(Ordering::Relaxed),
+            self.n_matched.load(Ordering::Relaxed),
+            self.n_filtered_out.lock().expect("not poisoned").values().sum::<usize>(),
+            self.skipped_files.lock().expect("not poisoned").len(),
+        ));
+        self.progress_bar.tick();
+    }
+
+    pub fn finish(&self) {
+        self.progress_bar.finish_and_clear();
+    }
+
+    pub fn counts(&self) -> ScanCounts {
+        ScanCounts {
+            n_scanned: self.n_discovered.load(Ordering::Relaxed),
+            n_already_in_slideshow: self.n_skipped.load(Ordering::Relaxed),
+            n_parsed: self.n_parsed.load(Ordering::Relaxed),
+            n_cache_hits: self.n_cache_hits.load(Ordering::Relaxed),
+            n_matched: self.n_matched.load(Ordering::Relaxed),
+            n_failed: self.skipped_files.lock().expect("not poisoned").len(),
+            n_filtered_out: self.n_filtered_out.lock().expect("not poisoned").clone(),
+        }
+    }
+
+    // one line per count, to print at the end
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("scanned: {}", self.n_discovered.load(Ordering::Relaxed)),
+            format!("already in the slideshow: {}", self.n_skipped.load(Ordering::Relaxed)),
+            format!("parsed: {}", self.n_parsed.load(Ordering::Relaxed)),
+            format!("cache hits: {}", self.n_cache_hits.load(Ordering::Relaxed)),
+            format!("matched: {}", self.n_matched.load(Ordering::Relaxed)),
+            format!("failed: {}", self.skipped_files.lock().expect("not poisoned").len()),
+        ];
+        for (reason, n_filtered_out) in self.n_filtered_out.lock().expect("not poisoned").iter() {
+            lines.push(format!("filtered out by {}: {}", reason, n_filtered_out));
+        }
+        lines
+    }
+}
+
+// the counts of the summary, for the json output
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ScanCounts {
+    pub n_scanned: usize,
+    pub n_already_in_slideshow: usize,
+    pub n_parsed: usize,
+    pub n_cache_hits: usize,
+    pub n_matched: usize,
+    pub n_failed: usize,
+    pub n_filtered_out: BTreeMap<FilterReason, usize>,
+}
+
+// of --bench, to tell whether the storage, the exif parsing or the decoding is the bottleneck
+#[derive(Serialize, Debug, Clone)]
+pub struct ScanTimings {
+    // until the last file is listed, so it includes the waits for the parses when they are slower
+    pub walk_secs: Option<f64>,
+    // whether the parses include decoding the pixels, e.g. for the analysis
+    pub decoded: bool,
+    pub parse: Option<Latencies>,
+    pub cache_hit: Option<Latencies>,
+}
+
+impl ScanTimings {
+    // one line per stage, to print at the end
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(walk_secs) = self.walk_secs {
+            lines.push(format!("walk: {:.3}s", walk_secs));
+        }
+        if let Some(parse) = &self.parse {
+            lines.push(format!("{}: {}", if self.decoded { "parse with decoding" } else { "parse" }, parse));
+        }
+        if let Some(cache_hit) = &self.cache_hit {
+            lines.push(format!("cache hit: {}", cache_hit));
+        }
+        lines
+    }
+}
+
+// percentiles of the time each file took, in milliseconds
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Latencies {
+    pub n: usize,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+    // summed over the files, so more than the wall time when they run at once
+    pub total_secs: f64,
+}
+
+impl Latencies {
+    // none for no durations
+    pub fn new(durations: &[Duration]) -> Option<Self> {
+        if durations.is_empty() {
+            return None;
+        }
+        let mut durations = durations.to_vec();
+        durations.sort();
+        let percentile = |p: f64| durations[((durations.len() - 1) as f64 * p).round() as usize].as_secs_f64() * 1000.0;
+        Some(Self {
+            n: durations.len(),
+            p50_ms: percentile(0.5),
+            p90_ms: percentile(0.9),
+            p99_ms: percentile(0.99),
+            max_ms: percentile(1.0),
+            total_secs: durations.iter().sum::<Duration>().as_secs_f64(),
+        })
+    }
+}
+
+impl std::fmt::Display for Latencies {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} files, p50 {:.1}ms, p90 {:.1}ms, p99 {:.1}ms, max {:.1}ms, total {:.3}s", self.n, self.p50_ms, self.p90_ms, self.p99_ms, self.max_ms, self.total_secs)
+    }
+}
+
+impl ScanCounts {
+    // of the images read, none when none was
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let n_read = self.n_cache_hits + self.n_parsed;
+        (n_read > 0).then(|| self.n_cache_hits as f64 / n_read as f64)
+    }
+}
+
+// images under the dirs which the filter accepts, in the order they are processed
+pub fn scan_images(dirs: Vec<PathBuf>, scan_options: ScanOptions, dir_filters: DirFilters) -> impl futures::Stream<Item = Result<ImageInfo>> {
+    scan_candidates(dirs, scan_options, dir_filters).filter_map(|candidate| future::ready(match candidate {
+        Ok((image_info, None)) => Some(Ok(image_info)),
+        Ok((_, Some(_))) => None,
+        Err(e) => Some(Err(e)),
+    }))
+}
+
+// all the images under the dirs, with the filter which rejected each of them, e.g. for a dry run
+pub fn scan_candidates(dirs: Vec<PathBuf>, scan_options: ScanOptions, dir_filters: DirFilters) -> impl futures::Stream<Item = Result<(ImageInfo, Option<FilterReason>)>> {
+    let remote_mirrors = Arc::new(scan_options.remote_mirrors.clone());
+    let dirs = dirs.into_iter()
+        .map(|dir| remote_mirrors.iter().find(|(_, mapped_dir)| *mapped_dir == dir).map_or(dir.clone(), |(mirror_dir, _)| mirror_dir.clone()))
+        .collect();
+    let skip_paths = scan_options.skip_paths.clone();
+    let stats = scan_options.stats.clone();
+    let skip_remote_mirrors = remote_mirrors.clone();
+    let image_path_stream = match scan_options.catalog.clone() {
+        Some(catalog) => catalog_path_stream(catalog, scan_options.walk_options.clone()).left_stream(),
+        None => image_path_stream(dirs, scan_options.walk_options.clone(), scan_options.cache_options.memo.clone(), scan_options.stats.clone(), scan_options.strict).right_stream(),
+    };
+    let walk_stats = stats.clone();
+    let image_path_stream = stream! {
+        let started = Instant::now();
+        tokio::pin!(image_path_stream);
+        while let Some(image_path) = image_path_stream.next().await {
+            yield image_path;
+        }
+        walk_stats.record_walk(started.elapsed());
+    };
+    let image_path_stream = image_path_stream
+        .filter(move |image_path| future::ready(match image_path {
+            Ok((image_path, _)) => {
+                stats.count(&stats.n_discovered);
+                let skipped = skip_paths.contains(&mapped_path(&skip_remote_mirrors, image_path));
+                if skipped {
+                    stats.count(&stats.n_skipped);
+                }
+                !skipped
+            }
+            Err(_) => true,
+        }));
+    let stats = scan_options.stats.clone();
+    image_info_stream(&scan_options, image_path_stream)
+        .map(move |image_info| -> Result<(ImageInfo, Option<FilterReason>)> {
+            let mut image_info = image_info?;
+            image_info.path = mapped_path(&remote_mirrors, &image_info.path);
+            stats.count(if image_info.from_cache { &stats.n_cache_hits } else { &stats.n_parsed });
+            let _span = debug_span!("filter", path = %image_info.path.display()).entered();
+            let rejection = dir_filters.rejection(&image_info);
+            match rejection {
+                Some(reason) => {
+                    debug!("filtered out by {}", reason);
+                    stats.count_filtered_out(reason);
+                }
+                None => {
+                    debug!("matched");
+                    stats.count(&stats.n_matched);
+                }
+            }
+            Ok((image_info, rejection))
+        })
+}
+
+// from the heads of a remote dir to where the machine showing the slideshow sees it,
+// and from the heads of an archive to the path in it
+fn mapped_path(remote_mirrors: &[(PathBuf, PathBuf)], path: &Path) -> PathBuf {
+    if let Some(entry_path) = archive_entry_path(path) {
+        return entry_path;
+    }
+    remote_mirrors.iter()
+        .find_map(|(mirror_dir, mapped_dir)| path.strip_prefix(mirror_dir).ok().map(|relative_path| mapped_dir.join(relative_path)))
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+// image paths with their file sizes
+// up to walk_options.concurrency dirs are read at once, as each read_dir is slow on network shares
+// an unreadable dir or file is skipped into the stats unless strict, the same as an unparsable image
+pub fn image_path_stream(dirs: Vec<PathBuf>, walk_options: WalkOptions, memo: Arc<ScanMemo>, stats: Arc<ScanStats>, strict: bool) -> impl futures::Stream<Item = Result<(PathBuf, u64)>> {
+    let walk_options = Arc::new(walk_options);
+    // overlapping roots, e.g. a dir and its sub dir, or two symlinks to the same dir, would yield the images twice
+    let dedupes_dirs = walk_options.follow_symlinks || dirs.len() > 1;
+    let mut dir_stack = dirs;
+    let visited_dirs: Arc<Mutex<HashSet<PathBuf>>> = Arc::new(Mutex::new(HashSet::new()));
+    stream! {
+        let mut reading_dirs = FuturesUnordered::new();
+        loop {
+            while reading_dirs.len() < walk_options.concurrency.max(1) {
+                let Some(dir) = dir_stack.pop() else {
+                    break;
+                };
+                let walk_options = walk_options.clone();
+                let visited_dirs = visited_dirs.clone();
+                let memo = memo.clone();
+                let span = debug_span!("walk", dir = %dir.display());
+                reading_dirs.push(async move {
+                    // without following and with a single root, the same dir can't be reached twice
+                    if dedupes_dirs && !visit_dir(&dir, &visited_dirs).await.map_err(|e| (dir.clone(), e))? {
+                        debug!("skip visited: {}", dir.display());
+                        return Ok((vec![], vec![], vec![]));
+                    }
+                    read_dir_entries(&dir, &walk_options, &memo).await.map_err(|e| (dir, e))
+                }.instrument(span));
+            }
+            let Some(result) = reading_dirs.next().await else {
+                break;
+            };
+            let (sub_dirs, image_paths, failures) = match result {
+                Ok(read_entries) => read_entries,
+                Err((dir, e)) if strict => {
+                    yield Err(e.context(format!("Failed to read: {}", dir.display())));
+                    break;
+                }
+                Err((dir, e)) => {
+                    debug!("failed to read: {}: {:#}", dir.display(), e);
+                    stats.skip_file(dir, &e);
+                    continue;
+                }
+            };
+            let mut failed = false;
+            for (path, e) in failures {
+                if strict {
+                    yield Err(e.context(format!("Failed to read: {}", path.display())));
+                    failed = true;
+                    break;
+                }
+                debug!("failed to read: {}: {:#}", path.display(), e);
+                stats.skip_file(path, &e);
+            }
+            if failed {
+                break;
+            }
+            dir_stack.extend(sub_dirs);
+            for image_path in image_paths {
+                yield Ok(image_path);
+            }
+        }
+    }
+}
+
+// (sub dirs, image paths with their sizes, entries failed to be read), where the failed ones don't stop the others
+async fn read_dir_entries(dir: &Path, walk_options: &WalkOptions, memo: &ScanMemo) -> Result<(Vec<PathBuf>, Vec<(PathBuf, u64)>, Vec<(PathBuf, anyhow::Error)>)> {
+    let mut sub_dirs = Vec::new();
+    let mut image_paths = Vec::new();
+    let (entries, mut failures) = list_dir(dir, memo).await?;
+    for (path, kind) in entries.iter() {
+        // hidden and excluded dirs are never pushed, so they are not descended into
+        if !accepts_entry(walk_options, path) {
+            continue;
+        }
+        match kind {
+            EntryKind::SymlinkedDir if !walk_options.follow_symlinks => debug!("skip symlinked dir: {}", path.display()),
+            EntryKind::Dir | EntryKind::SymlinkedDir => sub_dirs.push(path.clone()),
+            EntryKind::File if walk_options.include_archives && archive::is_archive_path(path) => {
+                // a broken archive is skipped the same as a broken image
+                let head_paths = match archive::extract_archive_heads(path, walk_options.whole_archive_entries).await {
+                    Ok(head_paths) => head_paths,
+                    Err(e) => {
+                        warn!("Failed to read the archive, skip it: {}: {:#}", path.display(), e);
+                        continue;
+                    }
+                };
+                for (head_path, size) in head_paths {
+                    // the globs and the extensions are of the paths in the archive, while the siblings of the pairs
+                    // are looked up among the extracted ones, which are in the same dirs as in the archive
+                    let entry_path = archive_entry_path(&head_path).unwrap_or_else(|| head_path.clone());
+                    if accepts_entry(walk_options, &entry_path) && accepts_file_name(walk_options, &entry_path) && !is_paired_away(walk_options, &head_path).await && accepts_file_size(walk_options, &entry_path, size) {
+                        image_paths.push((head_path, size));
+                    }
+                }
+            }
+            EntryKind::File => {
+                if !accepts_file(walk_options, path).await {
+                    continue;
+                }
+                // followed, as symlinked files are always read, and may be gone since the listing
+                let size = match tokio::fs::metadata(path).await {
+                    Ok(metadata) => metadata.len(),
+                    Err(e) => {
+                        failures.push((path.clone(), e.into()));
+                        continue;
+                    }
+                };
+                if !accepts_file_size(walk_options, path, size) {
+                    continue;
+                }
+                image_paths.push((path.clone(), size));
+            }
+        }
+    }
+    Ok((sub_dirs, image_paths, failures))
+}
+
+// all the entries regardless of the walk options, so that the other slideshows can reuse them,
+// with the ones whose types failed to be read
+async fn list_dir(dir: &Path, memo: &ScanMemo) -> Result<(Arc<Vec<(PathBuf, EntryKind)>>, Vec<(PathBuf, anyhow::Error)>)> {
+    if let Some(listed_dir) = memo.dir_entries.lock().expect("not poisoned").get(dir) {
+        return Ok((listed_dir.entries.clone(), vec![]));
+    }
+    // before listing, so that a change while listing is listed again by the next --resume
+    let modified = tokio::fs::metadata(dir).await?.modified().ok();
+    let checkpoint_dir = memo.checkpoint_dir_entries.lock().expect("not poisoned").remove(dir);
+    if let Some(checkpoint_dir) = checkpoint_dir {
+        // a file added or removed since the interrupted run changes the modified time of the dir
+        if modified.is_some() && checkpoint_dir.modified == modified {
+            memo.dir_entries.lock().expect("not poisoned").insert(dir.to_path_buf(), checkpoint_dir.clone());
+            return Ok((checkpoint_dir.entries, vec![]));
+        }
+        debug!("list again as changed: {}", dir.display());
+    }
+    let mut listed_entries = Vec::new();
+    let mut failures = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let file_type = match entry.file_type().await {
+            Ok(file_type) => file_type,
+            Err(e) => {
+                failures.push((entry.path(), e.into()));
+                continue;
+            }
+        };
+        let kind = if file_type.is_symlink() {
+            match tokio::fs::metadata(entry.path()).await {
+                Ok(metadata) if metadata.is_dir() => EntryKind::SymlinkedDir,
+                Ok(_) => EntryKind::File,
+                Err(_) => {
+                    debug!("skip broken symlink: {}", entry.path().display());
+                    continue;
+                }
+            }
+        } else if file_type.is_dir() {
+            EntryKind::Dir
+        } else {
+            EntryKind::File
+        };
+        listed_entries.push((entry.path(), kind));
+    }
+    let listed_entries = Arc::new(listed_entries);
+    memo.dir_entries.lock().expect("not poisoned").insert(dir.to_path_buf(), ListedDir { modified, entries: listed_entries.clone() });
+    Ok((listed_entries, failures))
+}
+
+// false when the real dir has been read already
+async fn visit_dir(dir: &Path, visited_dirs: &Mutex<HashSet<PathBuf>>) -> Result<bool> {
+    let canonical_dir = tokio::fs::canonicalize(dir).await?;
+    Ok(visited_dirs.lock().expect("not poisoned").insert(canonical_dir))
+}
+
+// of the size and the capture date of the catalog without opening the file, unless the pixels are analyzed
+// or the cache has the exif of an earlier run, e.g. without the catalog
+async fn catalog_image_info(catalog: Option<&Catalog>, image_path: &Path, cache_options: &CacheOptions, analysis_options: AnalysisOptions) -> Option<ImageInfo> {
+    let catalog_entry = catalog?.entries.get(image_path)?;
+    // videos have the durations of their tracks
+    let is_image = mime_guess::from_path(image_path).iter().any(|mime| mime.type_() == "image");
+    if analysis_options.needs_decode() || !is_image {
+        return None;
+    }
+    if cache_options.read && cached_image_info(image_path, cache_options).await.is_some() {
+        return None;
+    }
+    // the file is parsed instead, e.g. the one removed since the listing is reported there
+    ImageInfo::from_catalog_entry(image_path, catalog_entry).await.ok().flatten()
+}
+
+// the image paths of the catalog, checked the same as walking, except that the dirs are not looked at
+pub fn catalog_path_stream(catalog: Arc<Catalog>, walk_options: WalkOptions) -> impl futures::Stream<Item = Result<(PathBuf, u64)>> {
+    stream! {
+        for path in catalog.entries.keys() {
+            if !accepts_entry(&walk_options, path) || !accepts_file(&walk_options, path).await {
+                continue;
+            }
+            // the catalog may be older than the files
+            let Ok(metadata) = tokio::fs::metadata(path).await else {
+                continue;
+            };
+            if !accepts_file_size(&walk_options, path, metadata.len()) {
+                continue;
+            }
+            yield Ok((path.clone(), metadata.len()));
+        }
+    }
+}
+
+// for both dirs and files
+fn accepts_entry(walk_options: &WalkOptions, path: &Path) -> bool {
+    if walk_options.skip_junk && (junk_file::is_junk(path) || is_junk_name(path, &walk_options.junk_names)) {
+        debug!("skip junk: {}", path.display());
+        return false;
+    }
+    let is_hidden = path.file_name().is_some_and(|file_name| file_name.to_string_lossy().starts_with('.'));
+    if !walk_options.include_hidden && is_hidden {
+        debug!("skip hidden: {}", path.display());
+        return false;
+    }
+    if walk_options.exclude_globs.is_match(path) {
+        debug!("skip excluded: {}", path.display());
+        return false;
+    }
+    true
+}
+
+fn accepts_file_size(walk_options: &WalkOptions, path: &Path, size: u64) -> bool {
+    if walk_options.min_file_size.is_some_and(|min_file_size| size < min_file_size) || walk_options.max_file_size.is_some_and(|max_file_size| size > max_file_size) {
+        debug!("skip by file size: {}: {} bytes", path.display(), size);
+        return false;
+    }
+    true
+}
+
+async fn accepts_file(walk_options: &WalkOptions, path: &Path) -> bool {
+    accepts_file_name(walk_options, path) && !is_paired_away(walk_options, path).await
+}
+
+// by the globs and the extensions only
+fn accepts_file_name(walk_options: &WalkOptions, path: &Path) -> bool {
+    if !walk_options.include_globs.is_empty() && !walk_options.include_globs.is_match(path) {
+        debug!("skip not included: {}", path.display());
+        return false;
+    }
+    let extension = path.extension().map(|extension| extension.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+    if walk_options.excluded_extensions.contains(&extension) || (!walk_options.extensions.is_empty() && !walk_options.extensions.contains(&extension)) {
+        debug!("skip by extension: {}", path.display());
+        return false;
+    }
+    // mime_guess knows only some raw formats, and as images, though they can't be decoded
+    if raw::is_raw_path(path) {
+        if !walk_options.include_raw {
+            debug!("skip raw: {}", path.display());
+            return false;
+        }
+    } else if walk_options.extensions.is_empty() {
+        let mimes = mime_guess::from_path(path);
+        let guess_image = mimes.iter().any(|mime| mime.type_() == "image" || (walk_options.include_videos && mime.type_() == "video"));
+        if !guess_image {
+            return false;
+        }
+    }
+    true
+}
+
+async fn is_paired_away(walk_options: &WalkOptions, path: &Path) -> bool {
+    if raw::is_paired_away(path, walk_options.raw_pairing).await || live_photo::is_paired_away(path, walk_options.live_photo_pairing).await {
+        debug!("skip paired away: {}", path.display());
+        return true;
+    }
+    false
+}
+
+// with the byte budget, the count is no longer the limit, but still bounded
+const MAX_INFLIGHT_FILES_PER_THREAD: usize = 16;
+// without decoding, the parses wait on the disk more than the cpu, e.g. of a nas, so more of them are in flight
+// to keep the disk busy, while few enough that a slow disk isn't flooded with the reads of the heads
+const HEADER_ONLY_FILES_PER_THREAD: usize = 4;
+
+fn image_info_stream(scan_options: &ScanOptions, image_path_stream: impl futures::Stream<Item = Result<(PathBuf, u64)>>) -> impl futures::Stream<Item = Result<ImageInfo>> {
+    let cache_options = scan_options.cache_options.clone();
+    let analysis_options = scan_options.analysis_options;
+    let date_options = Arc::new(scan_options.date_options.clone());
+    let catalog = scan_options.catalog.clone();
+    let takeout = scan_options.takeout;
+    let rate_limiter = scan_options.rate_limiter.clone();
+    let strict = scan_options.strict;
+    let stats = scan_options.stats.clone();
+    // counted in KiB, as semaphore permits are u32
+    let inflight_budget = scan_options.max_inflight_bytes.map(|max_inflight_bytes| {
+        let budget = (max_inflight_bytes / 1024).clamp(1, u32::MAX as u64) as u32;
+        (Arc::new(Semaphore::new(budget as usize)), budget)
+    });
+    let n_inflight = if inflight_budget.is_some() {
+        scan_options.n_threads * MAX_INFLIGHT_FILES_PER_THREAD
+    } else if !analysis_options.needs_decode() {
+        scan_options.n_threads * HEADER_ONLY_FILES_PER_THREAD
+    } else {
+        scan_options.n_threads
+    };
+    image_path_stream.map(move |image_path| {
+        let cache_options = cache_options.clone();
+        let inflight_budget = inflight_budget.clone();
+        let date_options = date_options.clone();
+        let catalog = catalog.clone();
+        let rate_limiter = rate_limiter.clone();
+        let stats = stats.clone();
+        async move {
+            let (image_path, size) = image_path?;
+            let mut read_started = Instant::now();
+            // already parsed for another slideshow, without reading the file again
+            // not kept at all with the memory budget
+            let memoized = if cache_options.bounded_memory {
+                None
+            } else if let Some(image_info) = cache_options.memo.image_info(&image_path, analysis_options) {
+                Some(image_info)
+            } else {
+                cache_options.memo.checkpoint_image_info(&image_path, analysis_options).await
+            };
+            let memoized = memoized.map(|mut image_info| {
+                image_info.from_cache = true;
+                image_info
+            });
+            let mut image_info = if let Some(image_info) = memoized {
+                image_info
+            } else if let Some(image_info) = catalog_image_info(catalog.as_deref(), &image_path, &cache_options, analysis_options).await {
+                if !cache_options.bounded_memory {
+                    cache_options.memo.insert_image_info(&image_info);
+                }
+                image_info
+            } else {
+                let _permit = match inflight_budget {
+                    Some((semaphore, budget)) => {
+                        // a file bigger than the whole budget just runs alone
+                        let weight = (size / 1024).clamp(1, budget as u64) as u32;
+                        Some(semaphore.acquire_many_owned(weight).await?)
+                    }
+                    None => None,
+                };
+                if let Some(rate_limiter) = rate_limiter {
+                    rate_limiter.wait(size).await;
+                }
+                // without the waits for the budget and the rate
+                read_started = Instant::now();
+                let span = debug_span!("parse", path = %image_path.display());
+                match ImageInfo::from_path(&image_path, &cache_options, analysis_options).instrument(span).await {
+                    Ok(image_info) => {
+                        if !cache_options.bounded_memory {
+                            cache_options.memo.insert_image_info(&image_info);
+                        }
+                        image_info
+                    }
+                    Err(e) if strict => return Err(e.context(format!("Failed to parse: {}", image_path.display()))),
+                    Err(e) => {
+                        debug!("failed to parse: {}: {:#}", image_path.display(), e);
+                        stats.skip_file(image_path, &e);
+                        return Ok(None);
+                    }
+                }
+            };
+            stats.record_read(image_info.from_cache, analysis_options.needs_decode(), read_started.elapsed());
+            // reported the same as the unreadable ones
+            if analysis_options.verify && image_info.intact == Some(false) {
+                let e: anyhow::Error = Error::CorruptImageError(image_info.path.clone()).into();
+                if strict {
+                    return Err(e);
+                }
+                debug!("corrupt: {}", image_info.path.display());
+                stats.skip_file(image_info.path, &e);
+                return Ok(None);
+            }
+            if takeout {
+                apply_takeout_metadata(&mut image_info).await;
+            }
+            apply_date_options(&mut image_info, &date_options);
+            if let Some(catalog_entry) = catalog.as_ref().and_then(|catalog| catalog.entries.get(&image_info.path)) {
+                if catalog_entry.rating.is_some() {
+                    image_info.rating = catalog_entry.rating;
+                }
+                if catalog_entry.color_label.is_some() {
+                    image_info.color_label = catalog_entry.color_label.clone();
+                }
+                // categories are filtered the same as the keywords
+                image_info.keywords.extend(catalog_entry.categories.iter().cloned());
+            }
+            Ok(Some(image_info))
+        }
+    }).buffer_unordered(n_inflight).filter_map(|image_info| future::ready(image_info.transpose()))
+}
@@ -0,0 +1,91 @@
+use std::{path::{Path, PathBuf}, str::FromStr, time::{Duration, SystemTime}};
+use anyhow::Result;
+use chrono::{DateTime, Local, TimeDelta};
+use crate::{Error, cache::cache_parent_dir};
+
+// a lock left for this long is of a crashed run
+const STALE_LOCK_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+// an interval like "90s", "30m", "6h" or "1d", or a cron expression with the seconds like "0 0 3 * * *"
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    Interval(Duration),
+    Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+    pub fn parse(schedule: &str) -> Result<Self> {
+        if let Some(interval) = parse_interval(schedule.trim()) {
+            // otherwise the next run is now, over and over
+            if interval.is_zero() {
+                return Err(Error::ScheduleError(schedule.to_string(), "the interval is 0".to_string()).into());
+            }
+            return Ok(Schedule::Interval(interval));
+        }
+        match cron::Schedule::from_str(schedule) {
+            Ok(cron_schedule) => Ok(Schedule::Cron(Box::new(cron_schedule))),
+            Err(e) => Err(Error::ScheduleError(schedule.to_string(), e.to_string()).into()),
+        }
+    }
+
+    // none when the cron expression never fires again
+    pub fn next_after(&self, date_time: DateTime<Local>) -> Option<DateTime<Local>> {
+        match self {
+            Schedule::Interval(interval) => date_time.checked_add_signed(TimeDelta::from_std(*interval).ok()?),
+            Schedule::Cron(cron_schedule) => cron_schedule.after(&date_time).next(),
+        }
+    }
+}
+
+fn parse_interval(interval: &str) -> Option<Duration> {
+    let (unit_index, _) = interval.char_indices().last()?;
+    let (n, unit) = interval.split_at(unit_index);
+    let n: u64 = n.parse().ok()?;
+    let secs = match unit {
+        "s" => n,
+        "m" => n.checked_mul(60)?,
+        "h" => n.checked_mul(60 * 60)?,
+        "d" => n.checked_mul(24 * 60 * 60)?,
+        _ => return None,
+    };
+    Some(Duration::from_secs(secs))
+}
+
+// held while generating, so that the scheduled runs and the manual ones don't write the same outputs at once
+pub struct RunLock {
+    path: PathBuf,
+}
+
+impl RunLock {
+    pub async fn acquire() -> Result<Self> {
+        let path = cache_parent_dir().await?.join("run.lock");
+        if is_stale_lock(&path).await {
+            tokio::fs::remove_file(&path).await?;
+        }
+        let created = tokio::fs::OpenOptions::new().write(true).create_new(true).open(&path).await;
+        match created {
+            Ok(_) => {
+                tokio::fs::write(&path, std::process::id().to_string()).await?;
+                Ok(Self { path })
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Err(Error::LockedError(path).into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        // sync, as drop can't await, and it's a single small file
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+async fn is_stale_lock(path: &Path) -> bool {
+    let Ok(metadata) = tokio::fs::metadata(path).await else {
+        return false;
+    };
+    metadata.modified().ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age > STALE_LOCK_AGE)
+}
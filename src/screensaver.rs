@@ -0,0 +1,27 @@
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use winreg::{RegKey, enums::HKEY_CURRENT_USER};
+
+// the screensaver installed with XnView Classic
+pub const DEFAULT_SCREENSAVER_PATH: &str = r"C:\Program Files\XnView\xnview.scr";
+// where the screensaver of XnView reads the slideshow to play, as its settings dialog writes it
+const XNVIEW_SCREENSAVER_KEY: &str = r"Software\XnView\Screensaver";
+
+// the newest of the slideshows, so that a scheduled run keeps the screensaver fresh
+pub fn newest_slideshow<'a>(slideshow_paths: impl IntoIterator<Item = &'a Path>) -> Option<PathBuf> {
+    slideshow_paths.into_iter()
+        .filter_map(|path| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok().map(|modified| (modified, path)))
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path.to_path_buf())
+}
+
+// of the current user, so that no admin rights are needed
+pub fn install_screensaver(slideshow_path: &Path, screensaver_path: &Path) -> Result<()> {
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+    let (xnview_key, _) = hkcu.create_subkey(XNVIEW_SCREENSAVER_KEY)?;
+    xnview_key.set_value("SlideShow", &slideshow_path.to_string_lossy().to_string())?;
+    let (desktop_key, _) = hkcu.create_subkey(r"Control Panel\Desktop")?;
+    desktop_key.set_value("SCRNSAVE.EXE", &screensaver_path.to_string_lossy().to_string())?;
+    desktop_key.set_value("ScreenSaveActive", &"1")?;
+    Ok(())
+}
@@ -0,0 +1,352 @@
+use std::{collections::{BTreeMap, VecDeque}, path::PathBuf};
+use serde::{Serialize, Deserialize};
+use chrono::{Datelike, Days, NaiveDate, TimeDelta};
+use rand::{Rng, seq::SliceRandom};
+use tracing::warn;
+use crate::{clock, collation::PathComparator, image_info::ImageInfo};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleStrategy {
+    #[default]
+    #[serde(alias = "random")]
+    Uniform,
+    // evenly across years (or months), so that busy years are not over-represented
+    PerYear,
+    PerMonth,
+    Newest,
+    Oldest,
+    // evenly spaced in time across the date range of the images
+    UniformOverTime,
+}
+
+pub fn sample_image_infos(mut image_infos: Vec<ImageInfo>, sample: usize, sample_strategy: SampleStrategy, rng: &mut impl Rng) -> Vec<ImageInfo> {
+    // the same seed must pick the same images regardless of the processing order
+    image_infos.sort_by(|a, b| a.path.cmp(&b.path));
+    let bucket_key: fn(&ImageInfo) -> (i32, u32) = match sample_strategy {
+        SampleStrategy::Uniform => return reservoir_sample(image_infos, sample, rng),
+        SampleStrategy::Newest => {
+            image_infos.sort_by_key(|image_info| std::cmp::Reverse(image_info.creation_date_time));
+            image_infos.truncate(sample);
+            return image_infos;
+        }
+        SampleStrategy::Oldest => {
+            image_infos.sort_by_key(|image_info| image_info.creation_date_time);
+            image_infos.truncate(sample);
+            return image_infos;
+        }
+        SampleStrategy::UniformOverTime => return sample_uniform_over_time(image_infos, sample),
+        SampleStrategy::PerYear => |image_info| (image_info.creation_date_time.year(), 0),
+        SampleStrategy::PerMonth => |image_info| (image_info.creation_date_time.year(), image_info.creation_date_time.month()),
+    };
+    let mut buckets: BTreeMap<(i32, u32), Vec<ImageInfo>> = BTreeMap::new();
+    for image_info in image_infos {
+        buckets.entry(bucket_key(&image_info)).or_default().push(image_info);
+    }
+
+    // visit smaller buckets first, so that the quota they can't fill is redistributed to the bigger ones
+    let mut buckets: Vec<Vec<ImageInfo>> = buckets.into_values().collect();
+    buckets.sort_by_key(|bucket| bucket.len());
+    let n_buckets = buckets.len();
+    let mut remaining = sample;
+    let mut sampled_image_infos = Vec::new();
+    for (i, bucket) in buckets.into_iter().enumerate() {
+        let quota = remaining / (n_buckets - i);
+        let bucket_sample = reservoir_sample(bucket, quota, rng);
+        remaining -= bucket_sample.len();
+        sampled_image_infos.extend(bucket_sample);
+    }
+    sampled_image_infos
+}
+
+// from each dir in proportion to its weight, as many as the scarcest dir allows and at most sample,
+// where the dirs without any matched image are left out of the proportion
+pub fn mix_image_infos(image_infos: Vec<ImageInfo>, dir_weights: &[(PathBuf, f64)], sample: Option<usize>, sample_strategy: SampleStrategy, rng: &mut impl Rng) -> Vec<ImageInfo> {
+    let mut groups: Vec<Vec<ImageInfo>> = vec![Vec::new(); dir_weights.len()];
+    for image_info in image_infos {
+        // the deepest dir, as the dirs may be nested
+        let dir_index = dir_weights.iter().enumerate()
+            .filter(|(_, (dir, _))| image_info.path.starts_with(dir))
+            .max_by_key(|(_, (dir, _))| dir.components().count())
+            .map(|(i, _)| i);
+        if let Some(dir_index) = dir_index {
+            groups[dir_index].push(image_info);
+        }
+    }
+    mix_groups(groups.into_iter().zip(dir_weights.iter().map(|(_, weight)| *weight)).collect(), sample, sample_strategy, rng)
+}
+
+// e.g. {recent_days = 7, recent_ratio = 0.3} for 3 of 10 slides from this week and the rest from the archive,
+// re-sampled each run unless seeded
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Freshness {
+    #[serde(default = "default_recent_days")]
+    pub recent_days: u64,
+    #[serde(default = "default_recent_ratio")]
+    pub recent_ratio: f64,
+}
+
+fn default_recent_days() -> u64 {
+    7
+}
+
+fn default_recent_ratio() -> f64 {
+    0.3
+}
+
+impl Freshness {
+    // the same as last_n_days, so the recent ones are since the day recent_days ago
+    pub fn is_recent(&self, image_info: &ImageInfo, today: NaiveDate) -> bool {
+        let since = today.checked_sub_days(Days::new(self.recent_days)).unwrap_or(NaiveDate::MIN);
+        image_info.creation_date_time.date() >= since
+    }
+
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !(0.0..=1.0).contains(&self.recent_ratio) {
+            problems.push(format!("recent_ratio of freshness is not between 0 and 1: {}", self.recent_ratio));
+        }
+        problems
+    }
+}
+
+// the recent ones and the archive in the ratio of freshness, sample of them, or as many as the archive allows,
+// where the recent ones short of their share are made up for by the archive, so that a quiet week doesn't shrink the show
+pub fn mix_freshness(image_infos: Vec<ImageInfo>, freshness: &Freshness, sample: Option<usize>, sample_strategy: SampleStrategy, rng: &mut impl Rng) -> Vec<ImageInfo> {
+    let today = clock::today();
+    let (recent_image_infos, archive_image_infos): (Vec<ImageInfo>, Vec<ImageInfo>) = image_infos.into_iter().partition(|image_info| freshness.is_recent(image_info, today));
+    let n_images = recent_image_infos.len() + archive_image_infos.len();
+    let archive_ratio = 1.0 - freshness.recent_ratio;
+    let n_total = match sample {
+        Some(sample) => sample,
+        None if archive_ratio > 0.0 && !archive_image_infos.is_empty() => (archive_image_infos.len() as f64 / archive_ratio) as usize,
+        None => recent_image_infos.len(),
+    }.min(n_images);
+    let weights = [freshness.recent_ratio, archive_ratio];
+    let n_recent_wanted = apportion(n_total, &weights, &[n_total, n_total])[0];
+    let quotas = apportion(n_total, &weights, &[recent_image_infos.len(), archive_image_infos.len()]);
+    if quotas[0] < n_recent_wanted {
+        warn!("only {} recent images of the last {} days for {} slides of freshness, the rest is from the archive", quotas[0], freshness.recent_days, n_recent_wanted);
+    }
+    let mut mixed_image_infos = sample_image_infos(recent_image_infos, quotas[0], sample_strategy, rng);
+    mixed_image_infos.extend(sample_image_infos(archive_image_infos, quotas[1], sample_strategy, rng));
+    mixed_image_infos
+}
+
+// from each group in proportion to its weight
+fn mix_groups(groups: Vec<(Vec<ImageInfo>, f64)>, sample: Option<usize>, sample_strategy: SampleStrategy, rng: &mut impl Rng) -> Vec<ImageInfo> {
+    let total_weight: f64 = groups.iter().filter(|(group, _)| !group.is_empty()).map(|(_, weight)| weight).sum();
+    if total_weight <= 0.0 {
+        return vec![];
+    }
+    let n_feasible = groups.iter()
+        .filter(|(group, weight)| !group.is_empty() && *weight > 0.0)
+        .map(|(group, weight)| (group.len() as f64 * total_weight / weight) as usize)
+        .min()
+        .unwrap_or(0);
+    let n_total = sample.map_or(n_feasible, |sample| sample.min(n_feasible));
+    // the empty ones are left out of the proportion
+    let weights: Vec<f64> = groups.iter().map(|(group, weight)| if group.is_empty() { 0.0 } else { *weight }).collect();
+    let sizes: Vec<usize> = groups.iter().map(|(group, _)| group.len()).collect();
+    let quotas = apportion(n_total, &weights, &sizes);
+    let mut mixed_image_infos = Vec::new();
+    for ((group, _), quota) in groups.into_iter().zip(quotas) {
+        if quota > 0 {
+            mixed_image_infos.extend(sample_image_infos(group, quota, sample_strategy, rng));
+        }
+    }
+    mixed_image_infos
+}
+
+// n_total split by the weights, each floored and the rest one by one to the largest remainders,
+// so that they sum up to n_total unless all the groups are used up, as rounding each may exceed it
+fn apportion(n_total: usize, weights: &[f64], sizes: &[usize]) -> Vec<usize> {
+    let total_weight: f64 = weights.iter().sum();
+    if total_weight <= 0.0 {
+        return vec![0; weights.len()];
+    }
+    let exact_quotas: Vec<f64> = weights.iter().map(|weight| n_total as f64 * weight / total_weight).collect();
+    let mut quotas: Vec<usize> = exact_quotas.iter().zip(sizes).map(|(exact_quota, size)| (*exact_quota as usize).min(*size)).collect();
+    let mut by_remainder: Vec<usize> = (0..weights.len()).filter(|i| weights[*i] > 0.0).collect();
+    by_remainder.sort_by(|a, b| (exact_quotas[*b] - quotas[*b] as f64).total_cmp(&(exact_quotas[*a] - quotas[*a] as f64)));
+    let mut remaining = n_total.saturating_sub(quotas.iter().sum());
+    while remaining > 0 {
+        let mut n_given = 0;
+        for &i in &by_remainder {
+            if remaining > 0 && quotas[i] < sizes[i] {
+                quotas[i] += 1;
+                remaining -= 1;
+                n_given += 1;
+            }
+        }
+        if n_given == 0 {
+            break;
+        }
+    }
+    quotas
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    CreationDateAsc,
+    CreationDateDesc,
+    #[default]
+    Path,
+    // shuffled once at generation time with the seed, and RandomOrder of XnView is turned off
+    #[serde(alias = "shuffle")]
+    Random,
+    // one of each event in turn, where an event is a run of photos without a gap of event_gap_hours
+    InterleaveEvents,
+}
+
+pub fn sort_image_infos(image_infos: &mut [ImageInfo], sort_order: SortOrder, path_comparator: &PathComparator, rng: &mut impl Rng) {
+    // by path first, as ties are broken by path, and the same seed must give the same order regardless of the processing order
+    image_infos.sort_by(|a, b| path_comparator.compare(&a.path, &b.path));
+    match sort_order {
+        // interleaved by interleave_events after this
+        SortOrder::CreationDateAsc | SortOrder::InterleaveEvents => image_infos.sort_by_key(|image_info| image_info.creation_date_time),
+        SortOrder::CreationDateDesc => image_infos.sort_by_key(|image_info| std::cmp::Reverse(image_info.creation_date_time)),
+        SortOrder::Path => {}
+        SortOrder::Random => image_infos.shuffle(rng),
+    }
+}
+
+// at most max_per_period of the images in each period, e.g. a day or a month by the key, spread evenly in time
+// across the period, so that a trip of thousands of frames doesn't take over the show
+pub fn cap_per_period<K: Ord>(image_infos: Vec<ImageInfo>, max_per_period: usize, period_key: impl Fn(&ImageInfo) -> K) -> Vec<ImageInfo> {
+    let mut periods: BTreeMap<K, Vec<ImageInfo>> = BTreeMap::new();
+    for image_info in image_infos {
+        periods.entry(period_key(&image_info)).or_default().push(image_info);
+    }
+    periods.into_values().flat_map(|mut period_image_infos| {
+        if period_image_infos.len() <= max_per_period {
+            return period_image_infos;
+        }
+        period_image_infos.sort_by(|a, b| a.creation_date_time.cmp(&b.creation_date_time).then_with(|| a.path.cmp(&b.path)));
+        let n = period_image_infos.len();
+        period_image_infos.into_iter().enumerate()
+            .filter(|(i, _)| (i * max_per_period / n) != ((i + 1) * max_per_period / n))
+            .map(|(_, image_info)| image_info)
+            .collect()
+    }).collect()
+}
+
+// of the images sorted by the creation dates, the events are taken in turn in their order until all are used up,
+// so that a show isn't a whole wedding followed by a whole trip
+pub fn interleave_events(image_infos: Vec<ImageInfo>, event_gap_hours: u64) -> Vec<ImageInfo> {
+    let event_gap = TimeDelta::hours(event_gap_hours as i64);
+    let mut events: Vec<VecDeque<ImageInfo>> = Vec::new();
+    for image_info in image_infos {
+        let continues_event = events.last()
+            .and_then(|event| event.back())
+            .is_some_and(|last| image_info.creation_date_time - last.creation_date_time <= event_gap);
+        if continues_event {
+            events.last_mut().expect("not empty").push_back(image_info);
+        } else {
+            events.push(VecDeque::from([image_info]));
+        }
+    }
+    let mut interleaved_image_infos = Vec::new();
+    while !events.is_empty() {
+        for event in &mut events {
+            interleaved_image_infos.extend(event.pop_front());
+        }
+        events.retain(|event| !event.is_empty());
+    }
+    interleaved_image_infos
+}
+
+fn sample_uniform_over_time(mut image_infos: Vec<ImageInfo>, sample: usize) -> Vec<ImageInfo> {
+    if image_infos.len() <= sample {
+        return image_infos;
+    }
+    image_infos.sort_by_key(|image_info| image_info.creation_date_time);
+    let timestamps: Vec<i64> = image_infos.iter().map(|image_info| image_info.creation_date_time.and_utc().timestamp()).collect();
+    let first = timestamps[0];
+    let last = timestamps[timestamps.len() - 1];
+
+    // for each evenly spaced target time, pick the nearest image not picked yet
+    let mut picked = vec![false; image_infos.len()];
+    for i in 0..sample {
+        let target = first + ((last - first) as f64 * (i as f64 + 0.5) / sample as f64) as i64;
+        let index = timestamps.partition_point(|timestamp| *timestamp < target);
+        let mut above = index;
+        while above < picked.len() && picked[above] {
+            above += 1;
+        }
+        let mut below = index;
+        while below > 0 && picked[below - 1] {
+            below -= 1;
+        }
+        // there's always one not picked yet, as sample is less than the images
+        let nearest = match (below.checked_sub(1), (above < picked.len()).then_some(above)) {
+            (Some(below), Some(above)) => if target - timestamps[below] <= timestamps[above] - target { below } else { above },
+            (Some(below), None) => below,
+            (None, Some(above)) => above,
+            (None, None) => unreachable!("less picked than images"),
+        };
+        picked[nearest] = true;
+    }
+    image_infos.into_iter().zip(picked).filter_map(|(image_info, picked)| picked.then_some(image_info)).collect()
+}
+
+fn reservoir_sample<T>(items: impl IntoIterator<Item = T>, k: usize, rng: &mut impl Rng) -> Vec<T> {
+    let mut reservoir = Vec::with_capacity(k);
+    for (i, item) in items.into_iter().enumerate() {
+        if i < k {
+            reservoir.push(item);
+        } else {
+            let j = rng.gen_range(0..=i);
+            if j < k {
+                reservoir[j] = item;
+            }
+        }
+    }
+    reservoir
+}
+
+// without the time window, similar images taken years apart are clustered too
+pub fn dedupe_similar_images(mut image_infos: Vec<ImageInfo>, hamming_threshold: u32, time_window_secs: Option<u64>) -> Vec<ImageInfo> {
+    // visit the highest-resolution image of each cluster first, so that it's the one kept
+    // ties are broken by path, so that the result is reproducible
+    image_infos.sort_by(|a, b| {
+        let a_resolution = a.width as u64 * a.height as u64;
+        let b_resolution = b.width as u64 * b.height as u64;
+        b_resolution.cmp(&a_resolution).then_with(|| a.path.cmp(&b.path))
+    });
+    let mut kept_image_infos: Vec<ImageInfo> = Vec::new();
+    for image_info in image_infos {
+        if let Some(dhash) = image_info.dhash {
+            let is_similar = kept_image_infos.iter()
+                .filter(|kept_image_info| time_window_secs.is_none_or(|time_window_secs| {
+                    let secs = (kept_image_info.creation_date_time - image_info.creation_date_time).num_seconds().unsigned_abs();
+                    secs <= time_window_secs
+                }))
+                .filter_map(|kept_image_info| kept_image_info.dhash)
+                .any(|kept_dhash| (kept_dhash ^ dhash).count_ones() <= hamming_threshold);
+            if is_similar {
+                continue;
+            }
+        }
+        kept_image_infos.push(image_info);
+    }
+    kept_image_infos
+}
+
+// keeps the first of each burst, where a photo within the seconds of the last kept one is dropped,
+// in the order of the creation dates regardless of the sort order
+pub fn thin_bursts(mut image_infos: Vec<ImageInfo>, min_seconds_between_shots: u64) -> Vec<ImageInfo> {
+    image_infos.sort_by(|a, b| a.creation_date_time.cmp(&b.creation_date_time).then_with(|| a.path.cmp(&b.path)));
+    let mut kept_image_infos: Vec<ImageInfo> = Vec::new();
+    for image_info in image_infos {
+        if let Some(last_kept_image_info) = kept_image_infos.last() {
+            let secs = (image_info.creation_date_time - last_kept_image_info.creation_date_time).num_seconds().unsigned_abs();
+            if secs < min_seconds_between_shots {
+                continue;
+            }
+        }
+        kept_image_infos.push(image_info);
+    }
+    kept_image_infos
+}
@@ -0,0 +1,508 @@
+use std::{fmt, path::{Path, PathBuf}};
+use serde::{Serialize, Deserialize};
+use anyhow::Result;
+use tracing::warn;
+use crate::{Error, format::{FormatHeader, SlideshowFormat}};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum OutputEncoding {
+    #[default]
+    #[serde(rename = "utf8")]
+    Utf8,
+    #[serde(rename = "utf8-bom")]
+    Utf8Bom,
+    #[serde(rename = "shift_jis")]
+    ShiftJis,
+}
+
+impl OutputEncoding {
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>> {
+        match self {
+            Self::Utf8 | Self::Utf8Bom => Ok(text.as_bytes().to_vec()),
+            Self::ShiftJis => {
+                let (bytes, _, has_unmappable) = encoding_rs::SHIFT_JIS.encode(text);
+                if has_unmappable {
+                    return Err(Error::EncodingError("shift_jis".to_string(), text.to_string()).into());
+                }
+                Ok(bytes.into_owned())
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf8",
+            Self::Utf8Bom => "utf8-bom",
+            Self::ShiftJis => "shift_jis",
+        }
+    }
+
+    pub fn can_encode(&self, text: &str) -> bool {
+        match self {
+            Self::Utf8 | Self::Utf8Bom => true,
+            Self::ShiftJis => !encoding_rs::SHIFT_JIS.encode(text).2,
+        }
+    }
+
+    // the bom is removed if any
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        let encoding = match self {
+            Self::Utf8 | Self::Utf8Bom => encoding_rs::UTF_8,
+            Self::ShiftJis => encoding_rs::SHIFT_JIS,
+        };
+        let (text, _, _) = encoding.decode(bytes);
+        text.into_owned()
+    }
+}
+
+// what is written for a path the output encoding can't hold, e.g. an emoji in shift_jis, or a name which is not unicode
+// at all, e.g. of an unpaired surrogate on windows
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnencodablePaths {
+    // left out with a warning
+    #[default]
+    Skip,
+    // the generation fails
+    Error,
+    // e.g. "é" is "e" and the others are "_", for the copies renamed the same way, e.g. on a fat usb stick
+    Transliterate,
+}
+
+// the latin letters with the diacritics, and the ones without
+const TRANSLITERATED_FROM: &str = "ÀÁÂÃÄÅàáâãäåÇçÈÉÊËèéêëÌÍÎÏìíîïÑñÒÓÔÕÖØòóôõöøÙÚÛÜùúûüÝýÿ";
+const TRANSLITERATED_TO: &str = "AAAAAAaaaaaaCcEEEEeeeeIIIIiiiiNnOOOOOOooooooUUUUuuuuYyy";
+
+fn transliterate(c: char, encoding: OutputEncoding) -> char {
+    if c != char::REPLACEMENT_CHARACTER && encoding.can_encode(c.encode_utf8(&mut [0; 4])) {
+        return c;
+    }
+    TRANSLITERATED_FROM.chars().position(|from| from == c).and_then(|i| TRANSLITERATED_TO.chars().nth(i)).unwrap_or('_')
+}
+
+// the name as the os has it, where the bytes of a non-utf-8 name on unix may be shift_jis of an old disk
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn os_path_text(path: &Path, encoding: OutputEncoding) -> Option<String> {
+    if let Some(text) = path.to_str() {
+        return Some(text.to_string());
+    }
+    #[cfg(unix)]
+    if let OutputEncoding::ShiftJis = encoding {
+        use std::os::unix::ffi::OsStrExt;
+        return encoding_rs::SHIFT_JIS.decode_without_bom_handling_and_without_replacement(path.as_os_str().as_bytes()).map(|text| text.into_owned());
+    }
+    None
+}
+
+// the path to write in the output encoding, none when it's left out
+pub fn encodable_path_text(path: &Path, encoding: OutputEncoding, unencodable_paths: UnencodablePaths) -> Result<Option<String>> {
+    let text = os_path_text(path, encoding);
+    if let Some(text) = text.as_deref().filter(|text| encoding.can_encode(text)) {
+        return Ok(Some(text.to_string()));
+    }
+    match unencodable_paths {
+        UnencodablePaths::Skip => {
+            warn!("Skip the path which can't be written in {}: {}", encoding.name(), path.display());
+            Ok(None)
+        }
+        UnencodablePaths::Error => Err(Error::UnencodablePathError(path.to_path_buf(), encoding.name().to_string()).into()),
+        UnencodablePaths::Transliterate => {
+            let text = text.unwrap_or_else(|| path.to_string_lossy().into_owned());
+            Ok(Some(text.chars().map(|c| transliterate(c, encoding)).collect()))
+        }
+    }
+}
+
+// RGBA, written as XnView's space-separated "R G B A"
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "ColorValue", into = "ColorValue")]
+pub struct Color(pub [u8; 4]);
+
+// in the config, either [R, G, B, A] or "#RRGGBB" (or "#RRGGBBAA")
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ColorValue {
+    Rgba([u8; 4]),
+    Hex(String),
+}
+
+impl TryFrom<ColorValue> for Color {
+    type Error = Error;
+
+    fn try_from(value: ColorValue) -> Result<Self, Self::Error> {
+        match value {
+            ColorValue::Rgba(rgba) => Ok(Self(rgba)),
+            ColorValue::Hex(hex) => {
+                let invalid = || Error::ColorError(hex.clone());
+                let digits = hex.strip_prefix('#').ok_or_else(invalid)?;
+                if !(digits.len() == 6 || digits.len() == 8) || !digits.is_ascii() {
+                    return Err(invalid());
+                }
+                let mut rgba = [255; 4];
+                for (i, component) in rgba.iter_mut().enumerate().take(digits.len() / 2) {
+                    *component = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+                }
+                Ok(Self(rgba))
+            }
+        }
+    }
+}
+
+impl From<Color> for ColorValue {
+    fn from(color: Color) -> Self {
+        Self::Rgba(color.0)
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [r, g, b, a] = self.0;
+        write!(f, "{} {} {} {}", r, g, b, a)
+    }
+}
+
+// the defaults are what XnView writes for a new slideshow
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SlideshowHeader {
+    pub use_timer: bool,
+    // in seconds
+    pub timer: u32,
+    #[serde(rename = "loop")]
+    pub loop_: bool,
+    pub full_screen: bool,
+    pub stretch: u32,
+    pub random_order: bool,
+    pub show_info: bool,
+    // XnView's own placeholders, e.g. {Filename}
+    pub info: String,
+    pub title_bar: bool,
+    pub on_top: bool,
+    pub cursor_auto_hide: bool,
+    pub background_color: Color,
+    pub text_color: Color,
+    pub use_text_back_color: bool,
+    pub text_position: u32,
+    pub text_back_color: Color,
+    // in percent
+    pub opacity: u32,
+    pub font: String,
+    // in milliseconds
+    pub effect_duration: u32,
+}
+
+impl Default for SlideshowHeader {
+    fn default() -> Self {
+        Self {
+            use_timer: true,
+            timer: 2,
+            loop_: true,
+            full_screen: false,
+            stretch: 1,
+            random_order: true,
+            show_info: true,
+            info: "{Filename}".to_string(),
+            title_bar: true,
+            on_top: true,
+            cursor_auto_hide: false,
+            background_color: Color([0, 0, 0, 255]),
+            text_color: Color([255, 255, 255, 255]),
+            use_text_back_color: false,
+            text_position: 0,
+            text_back_color: Color([128, 128, 128, 255]),
+            opacity: 100,
+            font: "Sans Serif,9,-1,5,50,0,0,0,0,0".to_string(),
+            effect_duration: 1000,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SlideshowWriter {
+    file: tokio::fs::File,
+    encoding: OutputEncoding,
+    unencodable_paths: UnencodablePaths,
+}
+
+impl SlideshowWriter {
+    pub async fn from_path(path: impl AsRef<Path>, encoding: OutputEncoding) -> Result<Self> {
+        let path = path.as_ref();
+        let file = tokio::fs::OpenOptions::new().create(true).write(true).truncate(true).open(path).await?;
+        Ok(Self {
+            file,
+            encoding,
+            unencodable_paths: UnencodablePaths::default(),
+        })
+    }
+
+    // for adding images to an existing slideshow, so no header is written
+    pub async fn append_to_path(path: impl AsRef<Path>, encoding: OutputEncoding) -> Result<Self> {
+        let path = path.as_ref();
+        let file = tokio::fs::OpenOptions::new().append(true).open(path).await?;
+        Ok(Self {
+            file,
+            encoding,
+            unencodable_paths: UnencodablePaths::default(),
+        })
+    }
+
+    pub fn with_unencodable_paths(self, unencodable_paths: UnencodablePaths) -> Self {
+        Self { unencodable_paths, ..self }
+    }
+
+    async fn write_str(&mut self, text: &str) -> Result<()> {
+        let bytes = self.encoding.encode(text)?;
+        tokio::io::AsyncWriteExt::write_all(&mut self.file, &bytes).await?;
+        Ok(())
+    }
+
+    async fn write_bom_if_needed(&mut self) -> Result<()> {
+        if let OutputEncoding::Utf8Bom = self.encoding {
+            tokio::io::AsyncWriteExt::write_all(&mut self.file, "\u{feff}".as_bytes()).await?;
+        }
+        Ok(())
+    }
+
+    // e.g. the header of an existing slideshow, as is
+    pub async fn write_raw_header(&mut self, header: &str) -> Result<()> {
+        self.write_bom_if_needed().await?;
+        self.write_str(header).await?;
+        Ok(())
+    }
+
+    pub async fn flush(&mut self) -> Result<()> {
+        tokio::io::AsyncWriteExt::flush(&mut self.file).await?;
+        Ok(())
+    }
+
+    // the duration and the info override the timer and the info of the header for this image
+    pub async fn write_image_path(&mut self, path: impl AsRef<Path>, duration_secs: Option<f64>, info: Option<&str>) -> Result<()> {
+        self.write_entry(path.as_ref(), &EntryOptions { duration_secs, info, ..Default::default() }).await
+    }
+}
+
+impl SlideshowFormat for SlideshowWriter {
+    async fn write_header(&mut self, header: &FormatHeader<'_>) -> Result<()> {
+        let (width, height) = (header.width, header.height);
+        let header = header.slideshow_header;
+        self.write_bom_if_needed().await?;
+        let header = format!(r#"# Slide Show Sequence v2
+UseTimer = {use_timer}
+Timer = {timer}
+Loop = {loop_}
+FullScreen = {full_screen}
+WinWidth = {width}
+WinHeight = {height}
+Stretch = {stretch}
+RandomOrder = {random_order}
+ShowInfo = {show_info}
+Info = {info}
+TitleBar = {title_bar}
+OnTop = {on_top}
+CursorAutoHide = {cursor_auto_hide}
+BackgroundColor = {background_color}
+TextColor = {text_color}
+UseTextBackColor = {use_text_back_color}
+TextPosition = {text_position}
+TextBackColor = {text_back_color}
+Opacity = {opacity}
+Font = {font}
+EffectDuration = {effect_duration}
+"#,
+            use_timer = header.use_timer as u8,
+            timer = header.timer,
+            loop_ = header.loop_ as u8,
+            full_screen = header.full_screen as u8,
+            width = width,
+            height = height,
+            stretch = header.stretch,
+            random_order = header.random_order as u8,
+            show_info = header.show_info as u8,
+            info = header.info,
+            title_bar = header.title_bar as u8,
+            on_top = header.on_top as u8,
+            cursor_auto_hide = header.cursor_auto_hide as u8,
+            background_color = header.background_color,
+            text_color = header.text_color,
+            use_text_back_color = header.use_text_back_color as u8,
+            text_position = header.text_position,
+            text_back_color = header.text_back_color,
+            opacity = header.opacity,
+            font = header.font,
+            effect_duration = header.effect_duration,
+        );
+        self.write_str(&header).await?;
+        Ok(())
+    }
+
+    async fn write_entry(&mut self, path: &Path, entry_options: &EntryOptions<'_>) -> Result<()> {
+        let Some(path) = encodable_path_text(path, self.encoding, self.unencodable_paths)? else {
+            return Ok(());
+        };
+        let path = xnview_path_text(&path);
+        // escape before transcoding
+        let mut line = format!("\"{}\"", escape(&path));
+        if let Some(duration_secs) = entry_options.duration_secs {
+            line.push_str(&format!(" Timer={}", duration_secs));
+        }
+        if let Some(info) = entry_options.info {
+            line.push_str(&format!(" Info=\"{}\"", escape(info)));
+        }
+        if let Some(effect) = entry_options.effect {
+            line.push_str(&format!(" Effect={}", effect));
+        }
+        if let Some(stretch) = entry_options.stretch {
+            line.push_str(&format!(" Stretch={}", stretch));
+        }
+        line.push('\n');
+        self.write_str(&line).await?;
+        Ok(())
+    }
+
+    async fn finish(&mut self) -> Result<()> {
+        self.flush().await
+    }
+}
+
+// the parameters after the path of an entry, each overriding the one of the header for the image
+#[derive(Debug, Clone, Default)]
+pub struct EntryOptions<'a> {
+    pub duration_secs: Option<f64>,
+    pub info: Option<&'a str>,
+    pub effect: Option<u32>,
+    pub stretch: Option<u32>,
+}
+
+fn escape(text: &str) -> String {
+    text.replace("\\", "\\\\").replace("\"", "\\\"")
+}
+
+// the form XnView lists itself, e.g. \\NAS\photos\a.jpg for \\?\UNC\NAS\photos\a.jpg of canonicalize,
+// the verbatim prefix is dropped even for the long paths, as XnView opens files through Qt, which adds it back
+pub fn xnview_path(path: &Path) -> String {
+    xnview_path_text(&path.to_string_lossy())
+}
+
+fn xnview_path_text(path: &str) -> String {
+    let path = if let Some(unc_path) = path.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", unc_path)
+    } else if let Some(verbatim_path) = path.strip_prefix(r"\\?\") {
+        verbatim_path.to_string()
+    } else {
+        path.to_string()
+    };
+    // e.g. "//NAS/photos" in the config
+    if cfg!(windows) {
+        path.replace('/', "\\")
+    } else {
+        path
+    }
+}
+
+#[derive(Debug)]
+pub struct ExistingSlideshow {
+    // all the lines other than image paths
+    pub header: String,
+    pub paths: Vec<PathBuf>,
+}
+
+// an entry with the parameters of SlideshowWriter::write_entry
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SlideshowEntry {
+    pub path: PathBuf,
+    pub duration_secs: Option<f64>,
+    pub info: Option<String>,
+    pub effect: Option<u32>,
+    pub stretch: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ParsedSlideshow {
+    // all the lines other than entries, as is
+    pub header: String,
+    // of the "Key = Value" lines, e.g. ("Timer", "2")
+    pub header_values: Vec<(String, String)>,
+    pub entries: Vec<SlideshowEntry>,
+}
+
+impl ParsedSlideshow {
+    pub fn header_value(&self, key: &str) -> Option<&str> {
+        self.header_values.iter().find(|(k, _)| k == key).map(|(_, value)| value.as_str())
+    }
+}
+
+pub async fn read_slideshow(path: impl AsRef<Path>, encoding: OutputEncoding) -> Result<ExistingSlideshow> {
+    let parsed_slideshow = read_parsed_slideshow(path, encoding).await?;
+    Ok(ExistingSlideshow {
+        header: parsed_slideshow.header,
+        paths: parsed_slideshow.entries.into_iter().map(|entry| entry.path).collect(),
+    })
+}
+
+pub async fn read_parsed_slideshow(path: impl AsRef<Path>, encoding: OutputEncoding) -> Result<ParsedSlideshow> {
+    let path = path.as_ref();
+    let bytes = tokio::fs::read(path).await?;
+    parse_slideshow(path, &encoding.decode(&bytes))
+}
+
+// a line is a comment, a "Key = Value" of the header or a quoted path with the parameters, and anything else is
+// refused, so that a broken file isn't taken for a slideshow of fewer images, e.g. by the incremental update
+pub fn parse_slideshow(path: &Path, text: &str) -> Result<ParsedSlideshow> {
+    let mut parsed_slideshow = ParsedSlideshow::default();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_start_matches('\u{feff}');
+        let invalid = |message: String| Error::SlideshowParseError(path.to_path_buf(), i + 1, message);
+        if line.starts_with('"') {
+            parsed_slideshow.entries.push(parse_entry(line).map_err(invalid)?);
+            continue;
+        }
+        if !(line.trim().is_empty() || line.starts_with('#')) {
+            let (key, value) = line.split_once('=').ok_or_else(|| invalid(format!("expected \"Key = Value\": {}", line)))?;
+            parsed_slideshow.header_values.push((key.trim().to_string(), value.trim().to_string()));
+        }
+        parsed_slideshow.header.push_str(line);
+        parsed_slideshow.header.push('\n');
+    }
+    Ok(parsed_slideshow)
+}
+
+// reverses the escaping of SlideshowWriter::write_entry, the unknown parameters are dropped
+fn parse_entry(line: &str) -> Result<SlideshowEntry, String> {
+    let (path, mut parameters) = unescape_quoted(line).ok_or("unterminated quote of the path")?;
+    let mut entry = SlideshowEntry { path: PathBuf::from(path), ..Default::default() };
+    while !parameters.is_empty() {
+        let parameter = parameters.strip_prefix(' ').ok_or_else(|| format!("expected a space before: {}", parameters))?;
+        let (key, value) = parameter.split_once('=').ok_or_else(|| format!("expected Key=Value: {}", parameter))?;
+        let (value, rest) = if value.starts_with('"') {
+            unescape_quoted(value).ok_or_else(|| format!("unterminated quote of {}", key))?
+        } else {
+            let (value, rest) = value.split_at(value.find(' ').unwrap_or(value.len()));
+            (value.to_string(), rest)
+        };
+        let invalid_value = || format!("invalid {}: {}", key, value);
+        match key {
+            "Timer" => entry.duration_secs = Some(value.parse().map_err(|_| invalid_value())?),
+            "Info" => entry.info = Some(value),
+            "Effect" => entry.effect = Some(value.parse().map_err(|_| invalid_value())?),
+            "Stretch" => entry.stretch = Some(value.parse().map_err(|_| invalid_value())?),
+            _ => warn!("Unknown parameter of the slideshow entry, dropped: {}", key),
+        }
+        parameters = rest;
+    }
+    Ok(entry)
+}
+
+// of a text starting with a quote, the unescaped text up to the closing quote and the rest after it
+fn unescape_quoted(text: &str) -> Option<(String, &str)> {
+    let quoted = text.strip_prefix('"')?;
+    let mut unescaped = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(chars.next()?),
+            '"' => return Some((unescaped, chars.as_str())),
+            c => unescaped.push(c),
+        }
+    }
+    None
+}
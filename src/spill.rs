@@ -0,0 +1,155 @@
+use std::{cmp::Ordering, fs::File, io::{BufRead, BufReader, BufWriter, Write}, path::PathBuf, sync::atomic::{self, AtomicUsize}};
+use anyhow::Result;
+use crate::{cache::cache_parent_dir, collation::PathComparator, image_info::ImageInfo, selection::SortOrder};
+
+// the heap of an ImageInfo besides its own size, roughly, as the runs are cut by the estimate
+fn estimated_size(image_info: &ImageInfo) -> usize {
+    std::mem::size_of::<ImageInfo>()
+        + image_info.path.as_os_str().len()
+        + image_info.keywords.iter().map(|keyword| keyword.len() + std::mem::size_of::<String>()).sum::<usize>()
+        + image_info.description.as_ref().map_or(0, |description| description.len())
+}
+
+// the same order as sort_image_infos, where ties are broken by path
+fn compare(sort_order: SortOrder, path_comparator: &PathComparator, a: &ImageInfo, b: &ImageInfo) -> Ordering {
+    match sort_order {
+        SortOrder::CreationDateAsc => a.creation_date_time.cmp(&b.creation_date_time),
+        SortOrder::CreationDateDesc => b.creation_date_time.cmp(&a.creation_date_time),
+        // not spilled, see is_spillable
+        SortOrder::Path | SortOrder::Random | SortOrder::InterleaveEvents => Ordering::Equal,
+    }.then_with(|| path_comparator.compare(&a.path, &b.path))
+}
+
+// of the sorters of the process, as the slideshows may be generated at once
+static N_SORTERS: AtomicUsize = AtomicUsize::new(0);
+
+// the others need all the images at once
+pub fn is_spillable(sort_order: SortOrder) -> bool {
+    matches!(sort_order, SortOrder::Path | SortOrder::CreationDateAsc | SortOrder::CreationDateDesc)
+}
+
+// when the images kept in memory reach the budget, they are sorted and written into a run of json lines,
+// and the runs are merged when written, so that a library of millions of photos fits a small machine
+pub struct SpillSorter {
+    sort_order: SortOrder,
+    path_comparator: PathComparator,
+    budget_bytes: usize,
+    image_infos: Vec<ImageInfo>,
+    n_bytes: usize,
+    spill_dir: Option<PathBuf>,
+    run_paths: Vec<PathBuf>,
+}
+
+impl SpillSorter {
+    pub fn new(sort_order: SortOrder, path_comparator: PathComparator, budget_bytes: usize) -> Self {
+        Self { sort_order, path_comparator, budget_bytes, image_infos: Vec::new(), n_bytes: 0, spill_dir: None, run_paths: Vec::new() }
+    }
+
+    pub async fn push(&mut self, image_info: ImageInfo) -> Result<()> {
+        self.n_bytes += estimated_size(&image_info);
+        self.image_infos.push(image_info);
+        if self.n_bytes >= self.budget_bytes {
+            self.spill().await?;
+        }
+        Ok(())
+    }
+
+    async fn spill(&mut self) -> Result<()> {
+        let spill_dir = match &self.spill_dir {
+            Some(spill_dir) => spill_dir.clone(),
+            None => {
+                // by the process and the sorter, so that a crashed run's leftovers and the other sorters don't mix in
+                let sorter_id = N_SORTERS.fetch_add(1, atomic::Ordering::Relaxed);
+                let spill_dir = cache_parent_dir().await?.join("spill").join(format!("{}-{}", std::process::id(), sorter_id));
+                tokio::fs::create_dir_all(&spill_dir).await?;
+                self.spill_dir = Some(spill_dir.clone());
+                spill_dir
+            }
+        };
+        let run_path = spill_dir.join(format!("run-{}.jsonl", self.run_paths.len()));
+        let mut image_infos = std::mem::take(&mut self.image_infos);
+        self.n_bytes = 0;
+        image_infos.sort_by(|a, b| compare(self.sort_order, &self.path_comparator, a, b));
+        let path = run_path.clone();
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let mut writer = BufWriter::new(File::create(&path)?);
+            for image_info in &image_infos {
+                serde_json::to_writer(&mut writer, image_info)?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()?;
+            Ok(())
+        }).await??;
+        self.run_paths.push(run_path);
+        Ok(())
+    }
+
+    // in the sort order, without touching the disk when nothing was spilled
+    pub fn finish(mut self) -> Result<SortedImages> {
+        let mut image_infos = std::mem::take(&mut self.image_infos);
+        image_infos.sort_by(|a, b| compare(self.sort_order, &self.path_comparator, a, b));
+        let mut runs = vec![Run::new(Box::new(image_infos.into_iter().map(Ok)))?];
+        for run_path in &self.run_paths {
+            let lines = BufReader::new(File::open(run_path)?).lines();
+            runs.push(Run::new(Box::new(lines.map(|line| Ok(serde_json::from_str(&line?)?))))?);
+        }
+        // the dir is removed by the sorted images from here on, and by the sorter until then, e.g. on an error
+        let path_comparator = std::mem::replace(&mut self.path_comparator, PathComparator::Bytewise);
+        Ok(SortedImages { sort_order: self.sort_order, path_comparator, runs, spill_dir: self.spill_dir.take() })
+    }
+}
+
+// e.g. failed or interrupted before finishing, otherwise the runs are left in the cache for good
+impl Drop for SpillSorter {
+    fn drop(&mut self) {
+        if let Some(spill_dir) = &self.spill_dir {
+            let _ = std::fs::remove_dir_all(spill_dir);
+        }
+    }
+}
+
+struct Run {
+    head: Option<ImageInfo>,
+    rest: Box<dyn Iterator<Item = Result<ImageInfo>> + Send>,
+}
+
+impl Run {
+    fn new(mut rest: Box<dyn Iterator<Item = Result<ImageInfo>> + Send>) -> Result<Self> {
+        let head = rest.next().transpose()?;
+        Ok(Self { head, rest })
+    }
+}
+
+// the runs are few, a run per budget, so the smallest head is found by a scan
+pub struct SortedImages {
+    sort_order: SortOrder,
+    path_comparator: PathComparator,
+    runs: Vec<Run>,
+    spill_dir: Option<PathBuf>,
+}
+
+impl Iterator for SortedImages {
+    type Item = Result<ImageInfo>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (sort_order, path_comparator) = (self.sort_order, &self.path_comparator);
+        let run = self.runs.iter_mut()
+            .filter(|run| run.head.is_some())
+            .min_by(|a, b| compare(sort_order, path_comparator, a.head.as_ref().expect("filtered"), b.head.as_ref().expect("filtered")))?;
+        let next_head = match run.rest.next().transpose() {
+            Ok(next_head) => next_head,
+            Err(e) => return Some(Err(e)),
+        };
+        std::mem::replace(&mut run.head, next_head).map(Ok)
+    }
+}
+
+impl Drop for SortedImages {
+    fn drop(&mut self) {
+        // closed first, as windows can't remove the open files
+        self.runs.clear();
+        if let Some(spill_dir) = &self.spill_dir {
+            let _ = std::fs::remove_dir_all(spill_dir);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
+use serde::{Serialize, Deserialize};
+use chrono::{Datelike, NaiveDateTime};
+use crate::image_info::ImageInfo;
+
+// one output per bucket of the creation date
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SplitBy {
+    Year,
+    Quarter,
+    Month,
+}
+
+impl SplitBy {
+    // (year, month or quarter)
+    fn bucket_key(&self, date_time: NaiveDateTime) -> (i32, u32) {
+        match self {
+            SplitBy::Year => (date_time.year(), 0),
+            SplitBy::Quarter => (date_time.year(), (date_time.month() - 1) / 3 + 1),
+            SplitBy::Month => (date_time.year(), date_time.month()),
+        }
+    }
+
+    fn default_template(&self) -> &'static str {
+        match self {
+            SplitBy::Year => "{name}-{year}.{ext}",
+            SplitBy::Quarter => "{name}-{year}-Q{quarter}.{ext}",
+            SplitBy::Month => "{name}-{year}-{month}.{ext}",
+        }
+    }
+}
+
+// each bucket keeps the order of the images
+pub fn split_image_infos(image_infos: Vec<ImageInfo>, split_by: SplitBy) -> BTreeMap<(i32, u32), Vec<ImageInfo>> {
+    let mut buckets: BTreeMap<(i32, u32), Vec<ImageInfo>> = BTreeMap::new();
+    for image_info in image_infos {
+        buckets.entry(split_by.bucket_key(image_info.creation_date_time)).or_default().push(image_info);
+    }
+    buckets
+}
+
+// the template is a file name in the dir of the slideshow path, e.g. "{name}-{year}-{month}.sld",
+// where name and ext are of the slideshow path
+pub fn split_path(slideshow_path: &Path, template: Option<&str>, split_by: SplitBy, bucket_key: (i32, u32)) -> PathBuf {
+    let (year, month_or_quarter) = bucket_key;
+    let name = slideshow_path.file_stem().map(|file_stem| file_stem.to_string_lossy().to_string()).unwrap_or_default();
+    let ext = slideshow_path.extension().map(|extension| extension.to_string_lossy().to_string()).unwrap_or_default();
+    let file_name = template.unwrap_or(split_by.default_template())
+        .replace("{name}", &name)
+        .replace("{ext}", &ext)
+        .replace("{year}", &year.to_string())
+        .replace("{month}", &format!("{:02}", month_or_quarter))
+        .replace("{quarter}", &month_or_quarter.to_string());
+    slideshow_path.with_file_name(file_name)
+}
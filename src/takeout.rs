@@ -0,0 +1,86 @@
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use tracing::warn;
+use crate::{geocode, image_info::{DateSource, DateTimeCandidate, GpsPosition, ImageInfo}};
+
+// the fields of the json sidecars of Google Takeout, the others are ignored
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct TakeoutMetadata {
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    photo_taken_time: Option<TakeoutTime>,
+    #[serde(default)]
+    geo_data: Option<TakeoutGeoData>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TakeoutTime {
+    // seconds since the epoch as a string
+    timestamp: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct TakeoutGeoData {
+    latitude: f64,
+    longitude: f64,
+}
+
+// "IMG_0001.jpg.json", or "IMG_0001.jpg.supplemental-metadata.json" of the newer exports
+fn sidecar_paths(path: &Path) -> [PathBuf; 2] {
+    let mut json = path.as_os_str().to_owned();
+    json.push(".json");
+    let mut supplemental_json = path.as_os_str().to_owned();
+    supplemental_json.push(".supplemental-metadata.json");
+    [PathBuf::from(json), PathBuf::from(supplemental_json)]
+}
+
+// not cached, as it's per slideshow and the sidecar is small
+pub async fn apply_takeout_metadata(image_info: &mut ImageInfo) {
+    let mut metadata: Option<TakeoutMetadata> = None;
+    for sidecar_path in sidecar_paths(&image_info.path) {
+        let Ok(json) = tokio::fs::read_to_string(&sidecar_path).await else {
+            continue;
+        };
+        match serde_json::from_str(&json) {
+            Ok(parsed) => {
+                metadata = Some(parsed);
+                break;
+            }
+            Err(e) => warn!("Failed to parse takeout metadata, ignore it: {}: {:?}", sidecar_path.display(), e),
+        }
+    }
+    let Some(metadata) = metadata else {
+        return;
+    };
+    let taken_time = metadata.photo_taken_time
+        .and_then(|photo_taken_time| photo_taken_time.timestamp.parse::<i64>().ok())
+        .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0));
+    if let Some(taken_time) = taken_time {
+        // an instant, so dated in the local timezone the same as the file times
+        let taken_time = taken_time.with_timezone(&Local);
+        image_info.date_time_candidates.push(DateTimeCandidate {
+            source: DateSource::Takeout,
+            date_time: taken_time.naive_local(),
+            offset_secs: Some(taken_time.offset().local_minus_utc()),
+        });
+    }
+    if let Some(description) = metadata.description.filter(|description| !description.is_empty()) {
+        image_info.description = Some(description);
+    }
+    // takeout writes 0, 0 for unknown
+    if let Some(geo_data) = metadata.geo_data.filter(|geo_data| geo_data.latitude != 0.0 || geo_data.longitude != 0.0) {
+        if image_info.gps_position.is_none() {
+            let gps_position = GpsPosition {
+                latitude: geo_data.latitude,
+                longitude: geo_data.longitude,
+            };
+            let (country, city) = geocode::place(gps_position);
+            image_info.gps_position = Some(gps_position);
+            image_info.country = Some(country);
+            image_info.city = Some(city);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::path::{Path, PathBuf};
+use futures::{StreamExt, stream};
+use image::{codecs::jpeg::JpegEncoder, metadata::Orientation};
+use tokio::task;
+use anyhow::Result;
+use tracing::{info, warn};
+use crate::{cache::cache_parent_dir, image_info::ImageInfo};
+
+const THUMBNAIL_QUALITY: u8 = 85;
+
+pub async fn default_thumbnail_dir() -> Result<PathBuf> {
+    Ok(cache_parent_dir().await?.join("thumbnails"))
+}
+
+// named by the source, so that a changed image gets a new one
+pub fn thumbnail_path(thumbnail_dir: &Path, image_info: &ImageInfo) -> PathBuf {
+    let key = format!("{}\0{:?}\0{:?}", image_info.path.display(), image_info.source_modified, image_info.source_size);
+    thumbnail_dir.join(format!("{:x}.jpg", md5::compute(key.as_bytes())))
+}
+
+// downscaled previews of the matched images, read through once so that the nas has them warm too,
+// the existing ones and the videos are skipped, and a failed one is only logged
+pub async fn pregenerate_thumbnails(image_infos: &[ImageInfo], thumbnail_dir: &Path, size: u32, concurrency: usize) -> Result<()> {
+    tokio::fs::create_dir_all(thumbnail_dir).await?;
+    let jobs: Vec<(PathBuf, Option<u16>, PathBuf)> = image_infos.iter()
+        .filter(|image_info| !image_info.is_video)
+        .map(|image_info| (image_info.path.clone(), image_info.orientation, thumbnail_path(thumbnail_dir, image_info)))
+        .filter(|(_, _, thumbnail_path)| !thumbnail_path.exists())
+        .collect();
+    let n_jobs = jobs.len();
+    stream::iter(jobs).map(|(source_path, orientation, thumbnail_path)| async move {
+        let result = task::spawn_blocking({
+            let source_path = source_path.clone();
+            move || write_thumbnail(&source_path, orientation, size, &thumbnail_path)
+        }).await;
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("Failed to make thumbnail: {}: {:#}", source_path.display(), e),
+            Err(e) => warn!("Failed to make thumbnail: {}: {}", source_path.display(), e),
+        }
+    }).buffer_unordered(concurrency.max(1)).collect::<Vec<()>>().await;
+    if n_jobs > 0 {
+        info!("{} thumbnails made: {}", n_jobs, thumbnail_dir.display());
+    }
+    Ok(())
+}
+
+fn write_thumbnail(source_path: &Path, orientation: Option<u16>, size: u32, path: &Path) -> Result<()> {
+    let mut img = image::open(source_path)?;
+    if let Some(orientation) = orientation.and_then(|orientation| Orientation::from_exif(orientation as u8)) {
+        img.apply_orientation(orientation);
+    }
+    let thumbnail = img.thumbnail(size, size).to_rgb8();
+    let temp_path = path.with_extension("jpg.tmp");
+    let mut file = std::fs::File::create(&temp_path)?;
+    thumbnail.write_with_encoder(JpegEncoder::new_with_quality(&mut file, THUMBNAIL_QUALITY))?;
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use std::{collections::BTreeMap, path::PathBuf};
+use serde::{Serialize, Deserialize};
+use regex::Regex;
+use anyhow::Result;
+use crate::image_info::ImageInfo;
+
+// which one of the variants of a shot in the same dir is kept, e.g. IMG_1234.jpg and IMG_1234-edited.jpg
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PreferVariant {
+    // the last modified one of the suffixed ones
+    #[default]
+    Edited,
+    // the one without the suffix
+    Original,
+}
+
+// the suffixes are regexes at the end of the stem, e.g. ["-edited", "_v\\d+", " \\(\\d+\\)"], and may be stacked,
+// e.g. "IMG_1234-edited (1)"
+pub fn variant_suffix_regex(variant_suffixes: &[String]) -> Result<Regex> {
+    let alternatives: Vec<String> = variant_suffixes.iter().map(|variant_suffix| format!("(?:{})", variant_suffix)).collect();
+    Ok(Regex::new(&format!("(?:{})+$", alternatives.join("|")))?)
+}
+
+// the images without variants are kept as they are
+pub fn pick_variants(image_infos: Vec<ImageInfo>, variant_suffix_regex: &Regex, prefer_variant: PreferVariant) -> Vec<ImageInfo> {
+    // by the dir, the stem without the suffixes and the extension
+    let mut groups: BTreeMap<(PathBuf, String, String), Vec<(bool, ImageInfo)>> = BTreeMap::new();
+    for image_info in image_infos {
+        let dir = image_info.path.parent().map(PathBuf::from).unwrap_or_default();
+        let stem = image_info.path.file_stem().map(|file_stem| file_stem.to_string_lossy().to_string()).unwrap_or_default();
+        let extension = image_info.path.extension().map(|extension| extension.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+        let original_stem = variant_suffix_regex.replace(&stem, "").to_string();
+        // a stem of only the suffix, e.g. "(1).jpg", is its own
+        let (original_stem, is_variant) = if original_stem.is_empty() || original_stem == stem { (stem, false) } else { (original_stem, true) };
+        groups.entry((dir, original_stem, extension)).or_default().push((is_variant, image_info));
+    }
+    groups.into_values().filter_map(|mut group| {
+        group.sort_by(|(_, a), (_, b)| a.path.cmp(&b.path));
+        let has_variant = group.iter().any(|(is_variant, _)| *is_variant);
+        let has_original = group.iter().any(|(is_variant, _)| !*is_variant);
+        let picked = match prefer_variant {
+            PreferVariant::Edited if has_variant => group.into_iter()
+                .filter(|(is_variant, _)| *is_variant)
+                .max_by_key(|(_, image_info)| image_info.source_modified),
+            PreferVariant::Original if has_original => group.into_iter().find(|(is_variant, _)| !*is_variant),
+            _ => group.into_iter().next(),
+        };
+        picked.map(|(_, image_info)| image_info)
+    }).collect()
+}
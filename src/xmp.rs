@@ -0,0 +1,127 @@
+use std::{path::{Path, PathBuf}, time::SystemTime};
+use chrono::{NaiveDate, NaiveDateTime};
+use tokio::io::AsyncReadExt;
+use anyhow::Result;
+
+// embedded xmp is near the start of the file, so that the whole file isn't read
+const EMBEDDED_XMP_SEARCH_BYTES: u64 = 1024 * 1024;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct XmpMetadata {
+    pub rating: Option<i32>,
+    pub keywords: Vec<String>,
+    pub description: Option<String>,
+    // xmp:Label, the color label by name, e.g. "Red"
+    pub label: Option<String>,
+    // the first of exif:DateTimeOriginal, photoshop:DateCreated and xmp:CreateDate
+    pub date_time: Option<NaiveDateTime>,
+}
+
+// both "IMG_0001.xmp" (lightroom) and "IMG_0001.jpg.xmp" (xnview, darktable) are used
+pub fn sidecar_paths(path: &Path) -> [PathBuf; 2] {
+    let mut with_appended_extension = path.as_os_str().to_owned();
+    with_appended_extension.push(".xmp");
+    [path.with_extension("xmp"), PathBuf::from(with_appended_extension)]
+}
+
+// the modified time is for checking the cache, as the sidecar changes without the image
+pub async fn find_sidecar(path: &Path) -> Option<(PathBuf, SystemTime)> {
+    for sidecar_path in sidecar_paths(path) {
+        if let Ok(metadata) = tokio::fs::metadata(&sidecar_path).await {
+            if let Ok(modified) = metadata.modified() {
+                return Some((sidecar_path, modified));
+            }
+        }
+    }
+    None
+}
+
+// the sidecar wins over the embedded one, as that's where the editors write
+pub async fn read_xmp(path: &Path, sidecar_path: Option<&Path>) -> Result<Option<XmpMetadata>> {
+    if let Some(sidecar_path) = sidecar_path {
+        let xml = tokio::fs::read_to_string(sidecar_path).await?;
+        return Ok(Some(parse_xmp(&xml)));
+    }
+    let mut head = Vec::new();
+    tokio::fs::File::open(path).await?.take(EMBEDDED_XMP_SEARCH_BYTES).read_to_end(&mut head).await?;
+    let head = String::from_utf8_lossy(&head);
+    let Some(start) = head.find("<x:xmpmeta") else {
+        return Ok(None);
+    };
+    let end = head[start..].find("</x:xmpmeta>").map_or(head.len(), |end| start + end);
+    Ok(Some(parse_xmp(&head[start..end])))
+}
+
+// not a full xml parser, just enough for the rating, the keywords, the description, the label and the date either as attributes or as elements
+pub fn parse_xmp(xml: &str) -> XmpMetadata {
+    let rating = find_attribute(xml, "xmp:Rating")
+        .or_else(|| find_element_text(xml, "xmp:Rating"))
+        .and_then(|rating| rating.trim().parse().ok());
+    let keywords = find_element_text(xml, "dc:subject")
+        .map(|subject| {
+            subject.split("<rdf:li").skip(1)
+                .filter_map(|item| {
+                    let text_start = item.find('>')? + 1;
+                    let text_end = item.find("</rdf:li>")?;
+                    let keyword = unescape_xml(item.get(text_start..text_end)?.trim());
+                    (!keyword.is_empty()).then_some(keyword)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let description = find_element_text(xml, "dc:description")
+        .map(|description| match description.find("<rdf:li") {
+            // the x-default one of the languages comes first
+            Some(item_start) => {
+                let item = &description[item_start..];
+                let text_start = item.find('>').map_or(item.len(), |text_start| text_start + 1);
+                let text_end = item.find("</rdf:li>").unwrap_or(item.len());
+                item.get(text_start..text_end).unwrap_or_default()
+            }
+            None => description,
+        })
+        .or_else(|| find_attribute(xml, "dc:description"))
+        .map(|description| unescape_xml(description.trim()))
+        .filter(|description| !description.is_empty());
+    let label = find_attribute(xml, "xmp:Label")
+        .or_else(|| find_element_text(xml, "xmp:Label"))
+        .map(|label| unescape_xml(label.trim()))
+        .filter(|label| !label.is_empty());
+    let date_time = ["exif:DateTimeOriginal", "photoshop:DateCreated", "xmp:CreateDate"].into_iter()
+        .find_map(|name| find_attribute(xml, name).or_else(|| find_element_text(xml, name)).and_then(parse_xmp_date_time));
+    XmpMetadata { rating, keywords, description, label, date_time }
+}
+
+// "2019-07-14T18:30:00+09:00" of the xmp, with the seconds, the fraction and the offset optional, or a date only
+fn parse_xmp_date_time(text: &str) -> Option<NaiveDateTime> {
+    let text = text.trim();
+    let date_time = text.get(..19).and_then(|text| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S").ok())
+        .or_else(|| text.get(..16).and_then(|text| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M").ok()));
+    date_time.or_else(|| text.get(..10).and_then(|text| NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()).and_then(|date| date.and_hms_opt(0, 0, 0)))
+}
+
+fn find_attribute<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let pattern = format!("{}=", name);
+    let value_start = xml.find(&pattern)? + pattern.len();
+    let quote = xml[value_start..].chars().next()?;
+    if quote != '"' && quote != '\'' {
+        return None;
+    }
+    let value = &xml[value_start + 1..];
+    Some(&value[..value.find(quote)?])
+}
+
+fn find_element_text<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let open_start = xml.find(&format!("<{}", name))?;
+    let text_start = open_start + xml[open_start..].find('>')? + 1;
+    let text_end = text_start + xml[text_start..].find(&format!("</{}>", name))?;
+    Some(&xml[text_start..text_end])
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
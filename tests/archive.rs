@@ -0,0 +1,70 @@
+mod common;
+
+use std::{io::Write, path::Path, sync::Arc};
+use chrono::NaiveDate;
+use futures::StreamExt;
+use make_xnview_slideshow::{
+    archive::{archive_entry_path, extract_archive_heads, unpack_archive_entry},
+    cache::{CacheKey, CacheOptions},
+    config::SlideshowConfig,
+    image_info::{AnalysisOptions, ImageInfo},
+    scan::{ScanMemo, ScanStats, WalkOptions, image_path_stream},
+};
+use serde_json::json;
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+fn write_zip(path: &Path, entries: &[(&str, &[u8])]) {
+    let mut zip_writer = ZipWriter::new(std::fs::File::create(path).expect("temp dir is writable"));
+    for (name, bytes) in entries {
+        zip_writer.start_file(*name, SimpleFileOptions::default()).expect("writable");
+        zip_writer.write_all(bytes).expect("writable");
+    }
+    zip_writer.finish().expect("writable");
+}
+
+#[tokio::test]
+async fn images_in_the_archive_are_listed_and_unpacked() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let jpeg = common::jpeg(8, 6, Some("2012:08:03 10:15:00"));
+    let archive_path = dir.path().join("2012-trip.zip");
+    write_zip(&archive_path, &[("day1/a.jpg", &jpeg), ("notes.txt", b"not an image"), ("../escaped.jpg", &jpeg)]);
+
+    let head_paths = extract_archive_heads(&archive_path, false).await.expect("readable");
+    assert_eq!(head_paths.len(), 1);
+    let (head_path, size) = &head_paths[0];
+    assert_eq!(*size, jpeg.len() as u64);
+    let entry_path = archive_entry_path(head_path).expect("of the archive");
+    assert_eq!(entry_path, archive_path.join("day1").join("a.jpg"));
+
+    let cache_options = CacheOptions::new(false, false, None, CacheKey::Path);
+    let image_info = ImageInfo::from_path(head_path, &cache_options, AnalysisOptions::default()).await.expect("readable");
+    assert_eq!(image_info.creation_date_time.date(), NaiveDate::from_ymd_opt(2012, 8, 3).expect("valid date"));
+    assert_eq!((image_info.width, image_info.height), (8, 6));
+
+    let unpacked_path = unpack_archive_entry(&entry_path).await.expect("unpackable").expect("of the archive");
+    assert_eq!(std::fs::read(&unpacked_path).expect("unpacked"), jpeg);
+    let plain_path = common::write_fixture(dir.path(), "b.jpg", &jpeg);
+    assert_eq!(unpack_archive_entry(&plain_path).await.expect("not an archive"), None);
+}
+
+#[tokio::test]
+async fn include_globs_are_of_the_paths_in_the_archive() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let jpeg = common::jpeg(8, 6, None);
+    let archive_path = dir.path().join("trips.zip");
+    write_zip(&archive_path, &[("2012/a.jpg", &jpeg), ("2013/b.jpg", &jpeg), ("2012/c.txt", b"not an image")]);
+    let slideshow: SlideshowConfig = serde_json::from_value(json!({
+        "path": dir.path().join("a.sld"),
+        "image_dirs": [dir.path()],
+        "include_archives": true,
+        "include_globs": ["**/2012/**"],
+    })).expect("valid config");
+    let walk_options = WalkOptions::from_slideshow(&slideshow).expect("valid walk options");
+    let image_paths: Vec<_> = image_path_stream(vec![dir.path().to_path_buf()], walk_options, Arc::new(ScanMemo::default()), Arc::new(ScanStats::default()), false)
+        .map(|image_path| archive_entry_path(&image_path.expect("readable").0).expect("of the archive"))
+        .collect()
+        .await;
+    assert_eq!(image_paths, vec![archive_path.join("2012").join("a.jpg")]);
+}
@@ -0,0 +1,15 @@
+use std::time::Duration;
+use make_xnview_slideshow::scan::Latencies;
+
+#[test]
+fn latencies_are_percentiles_of_the_reads() {
+    assert_eq!(Latencies::new(&[]), None);
+    let durations: Vec<Duration> = (1..=100).rev().map(Duration::from_millis).collect();
+    let latencies = Latencies::new(&durations).expect("not empty");
+    assert_eq!(latencies.n, 100);
+    assert!((latencies.p50_ms - 51.0).abs() < 1e-6);
+    assert!((latencies.p90_ms - 90.0).abs() < 1e-6);
+    assert!((latencies.p99_ms - 99.0).abs() < 1e-6);
+    assert!((latencies.max_ms - 100.0).abs() < 1e-6);
+    assert!((latencies.total_secs - 5.05).abs() < 1e-9);
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use make_xnview_slideshow::{
+    cache::{CacheKey, CacheOptions, cache_image_info, cached_image_info, flush_cache},
+    image_info::{AnalysisOptions, ImageInfo},
+};
+use serde_json::json;
+
+#[tokio::test]
+async fn parsed_image_info_is_read_back() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let path = common::write_fixture(dir.path(), "a.jpg", &common::jpeg(8, 6, Some("2019:07:14 18:30:00")));
+    let cache_options = CacheOptions::new(false, false, None, CacheKey::Path);
+    let parsed = ImageInfo::from_path(&path, &cache_options, AnalysisOptions::default()).await.expect("readable");
+    assert!(!parsed.from_cache);
+    flush_cache().await.expect("writable");
+    let cached = ImageInfo::from_path(&path, &cache_options, AnalysisOptions::default()).await.expect("readable");
+    assert!(cached.from_cache);
+    assert_eq!(cached.path, parsed.path);
+    assert_eq!(cached.creation_date_time, parsed.creation_date_time);
+    assert_eq!((cached.width, cached.height), (parsed.width, parsed.height));
+}
+
+#[tokio::test]
+async fn entry_is_keyed_by_the_content() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let bytes = common::jpeg(8, 6, None);
+    let path = common::write_fixture(dir.path(), "a.jpg", &bytes);
+    let copy_path = common::write_fixture(dir.path(), "copy.jpg", &bytes);
+    let cache_options = CacheOptions::new(false, false, None, CacheKey::Content);
+    let image_info = common::image_info(json!({ "path": path, "keywords": ["cached"] }));
+    cache_image_info(&image_info, &cache_options).await.expect("writable");
+    flush_cache().await.expect("writable");
+    let cached = cached_image_info(&copy_path, &cache_options).await.expect("the same content");
+    assert_eq!(cached.keywords, vec!["cached".to_string()]);
+}
+
+#[tokio::test]
+async fn nothing_is_read_without_the_cache() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let path = common::write_fixture(dir.path(), "a.jpg", &common::jpeg(8, 6, None));
+    let cache_options = CacheOptions::new(true, false, None, CacheKey::Path);
+    ImageInfo::from_path(&path, &cache_options, AnalysisOptions::default()).await.expect("readable");
+    let image_info = ImageInfo::from_path(&path, &cache_options, AnalysisOptions::default()).await.expect("readable");
+    assert!(!image_info.from_cache);
+}
+
+#[tokio::test]
+async fn bounded_memory_queries_the_entry() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let path = common::write_fixture(dir.path(), "a.jpg", &common::jpeg(8, 6, Some("2019:07:14 18:30:00")));
+    let cache_options = CacheOptions::new(false, false, None, CacheKey::Path).with_bounded_memory(true);
+    let parsed = ImageInfo::from_path(&path, &cache_options, AnalysisOptions::default()).await.expect("readable");
+    assert!(!parsed.from_cache);
+    // the pending write is found before the flush too
+    let cached = ImageInfo::from_path(&path, &cache_options, AnalysisOptions::default()).await.expect("readable");
+    assert!(cached.from_cache);
+    flush_cache().await.expect("writable");
+    let cached = ImageInfo::from_path(&path, &cache_options, AnalysisOptions::default()).await.expect("readable");
+    assert!(cached.from_cache);
+    assert_eq!(cached.creation_date_time, parsed.creation_date_time);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn concurrent_writers_leave_a_readable_entry() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let path = common::write_fixture(dir.path(), "a.jpg", &common::jpeg(8, 6, Some("2019:07:14 18:30:00")));
+    let cache_options = CacheOptions::new(false, false, None, CacheKey::Path);
+    let parsed = ImageInfo::from_path(&path, &cache_options, AnalysisOptions::default()).await.expect("readable");
+    let writers: Vec<_> = (0..64).map(|i| {
+        let image_info = parsed.clone();
+        let cache_options = cache_options.clone();
+        tokio::spawn(async move {
+            cache_image_info(&image_info, &cache_options).await.expect("writable");
+            if i % 4 == 0 {
+                flush_cache().await.expect("writable");
+            }
+        })
+    }).collect();
+    for writer in writers {
+        writer.await.expect("not panicked");
+    }
+    flush_cache().await.expect("writable");
+    let cached = cached_image_info(&path, &cache_options.clone().with_bounded_memory(true)).await.expect("an entry which parses");
+    assert_eq!(cached.creation_date_time, parsed.creation_date_time);
+    assert_eq!((cached.width, cached.height), (parsed.width, parsed.height));
+}
@@ -0,0 +1,31 @@
+mod common;
+
+use chrono::NaiveDate;
+use make_xnview_slideshow::{catalog::Catalog, image_info::{DateSource, ImageInfo}};
+use rusqlite::Connection;
+
+#[tokio::test]
+async fn capture_dates_of_the_catalog_are_read_without_opening_the_images() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    // not a jpeg at all, so that parsing it would fail
+    let image_path = common::write_fixture(dir.path(), "a.jpg", b"not a jpeg");
+    let catalog_path = dir.path().join("XnView.db");
+    let db = Connection::open(&catalog_path).expect("writable");
+    db.execute_batch("
+        CREATE TABLE Folders (FolderID INTEGER PRIMARY KEY, Pathname TEXT);
+        CREATE TABLE Images (ImageID INTEGER PRIMARY KEY, FolderID INTEGER, Filename TEXT, Rating INTEGER, Label INTEGER, TakenDate TEXT, Width INTEGER, Height INTEGER);
+    ").expect("valid schema");
+    db.execute("INSERT INTO Folders VALUES (1, ?1)", [dir.path().to_string_lossy()]).expect("writable");
+    db.execute("INSERT INTO Images VALUES (1, 1, 'a.jpg', 4, 0, '2019-07-14 18:30:00', 4000, 3000)", []).expect("writable");
+    drop(db);
+
+    let catalog = Catalog::open(&catalog_path, vec![dir.path().to_path_buf()]).await.expect("readable");
+    let catalog_entry = &catalog.entries[&image_path];
+    assert_eq!(catalog_entry.rating, Some(4));
+    let image_info = ImageInfo::from_catalog_entry(&image_path, catalog_entry).await.expect("readable").expect("dated and sized");
+    let taken = NaiveDate::from_ymd_opt(2019, 7, 14).and_then(|date| date.and_hms_opt(18, 30, 0)).expect("valid date");
+    assert_eq!((image_info.width, image_info.height), (4000, 3000));
+    assert_eq!(image_info.creation_date_time, taken);
+    assert!(image_info.date_time_candidates.iter().any(|candidate| candidate.source == DateSource::Catalog && candidate.date_time == taken));
+}
@@ -0,0 +1,90 @@
+// shared by the test binaries, each of which uses only some of it
+#![allow(dead_code)]
+
+use std::{path::{Path, PathBuf}, sync::{Arc, Once}};
+use chrono::{Local, NaiveDate, TimeZone};
+use image::{ImageBuffer, Rgb, codecs::jpeg::JpegEncoder};
+use make_xnview_slideshow::{cache::set_cache_parent_dir, clock::{FixedClock, set_clock}, image_info::ImageInfo};
+
+// the day the tests run on, a saturday
+pub fn today() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 6, 15).expect("valid date")
+}
+
+// a cache dir of the process and the fixed clock, before anything reads them
+pub fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        let cache_parent_dir = std::env::temp_dir().join(format!("make-xnview-slideshow-test-{}", std::process::id()));
+        std::fs::create_dir_all(&cache_parent_dir).expect("temp dir is writable");
+        set_cache_parent_dir(cache_parent_dir);
+        let now = Local.from_local_datetime(&today().and_hms_opt(12, 0, 0).expect("valid time")).single().expect("not ambiguous");
+        set_clock(Arc::new(FixedClock(now)));
+    });
+}
+
+// a tiny jpeg, with DateTimeOriginal like "2019:07:14 18:30:00" when given
+pub fn jpeg(width: u32, height: u32, date_time_original: Option<&str>) -> Vec<u8> {
+    let pixels: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::from_fn(width, height, |x, y| Rgb([(x * 32) as u8, (y * 32) as u8, 128]));
+    let mut bytes = Vec::new();
+    JpegEncoder::new(&mut bytes).encode_image(&pixels).expect("encodable");
+    match date_time_original {
+        // right after SOI
+        Some(date_time_original) => [&bytes[..2], &exif_segment(date_time_original), &bytes[2..]].concat(),
+        None => bytes,
+    }
+}
+
+// APP1 of a little endian tiff, with IFD0 pointing to the exif IFD of the date only
+fn exif_segment(date_time_original: &str) -> Vec<u8> {
+    const IFD0_OFFSET: u32 = 8;
+    const EXIF_IFD_OFFSET: u32 = IFD0_OFFSET + 2 + 12 + 4;
+    const DATE_OFFSET: u32 = EXIF_IFD_OFFSET + 2 + 12 + 4;
+    let mut date = date_time_original.as_bytes().to_vec();
+    date.push(0);
+    let mut tiff = Vec::new();
+    tiff.extend_from_slice(b"II");
+    tiff.extend_from_slice(&42u16.to_le_bytes());
+    tiff.extend_from_slice(&IFD0_OFFSET.to_le_bytes());
+    // ExifIFDPointer, LONG
+    tiff.extend_from_slice(&1u16.to_le_bytes());
+    tiff.extend_from_slice(&0x8769u16.to_le_bytes());
+    tiff.extend_from_slice(&4u16.to_le_bytes());
+    tiff.extend_from_slice(&1u32.to_le_bytes());
+    tiff.extend_from_slice(&EXIF_IFD_OFFSET.to_le_bytes());
+    tiff.extend_from_slice(&0u32.to_le_bytes());
+    // DateTimeOriginal, ASCII
+    tiff.extend_from_slice(&1u16.to_le_bytes());
+    tiff.extend_from_slice(&0x9003u16.to_le_bytes());
+    tiff.extend_from_slice(&2u16.to_le_bytes());
+    tiff.extend_from_slice(&(date.len() as u32).to_le_bytes());
+    tiff.extend_from_slice(&DATE_OFFSET.to_le_bytes());
+    tiff.extend_from_slice(&0u32.to_le_bytes());
+    tiff.extend_from_slice(&date);
+    let mut payload = b"Exif\0\0".to_vec();
+    payload.extend_from_slice(&tiff);
+    let mut segment = vec![0xff, 0xe1];
+    segment.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+    segment.extend_from_slice(&payload);
+    segment
+}
+
+pub fn write_fixture(dir: &Path, name: &str, bytes: &[u8]) -> PathBuf {
+    let path = dir.join(name);
+    std::fs::write(&path, bytes).expect("temp dir is writable");
+    path
+}
+
+// of the fields given, the others are the defaults of the cache entries
+pub fn image_info(fields: serde_json::Value) -> ImageInfo {
+    let mut image_info = serde_json::json!({
+        "path": "/photos/a.jpg",
+        "width": 4000,
+        "height": 3000,
+        "creation_date_time": "2024-06-10T19:30:00",
+    });
+    for (key, value) in fields.as_object().expect("an object") {
+        image_info[key] = value.clone();
+    }
+    serde_json::from_value(image_info).expect("valid image info")
+}
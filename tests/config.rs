@@ -0,0 +1,14 @@
+use std::path::{Path, PathBuf};
+use make_xnview_slideshow::{Error, config::expand_path};
+
+#[test]
+fn double_dollars_are_literal_dollars() {
+    let path = expand_path(Path::new("/photos/$$RECYCLE.BIN/a$$$${b}.jpg"), None).expect("no env vars");
+    assert_eq!(path, PathBuf::from("/photos/$RECYCLE.BIN/a$${b}.jpg"));
+}
+
+#[test]
+fn unknown_env_vars_are_refused() {
+    let e = expand_path(Path::new("/photos/$MAKE_XNVIEW_SLIDESHOW_UNSET/a.jpg"), None).expect_err("unset");
+    assert_eq!(e.to_string(), Error::EnvVarError("MAKE_XNVIEW_SLIDESHOW_UNSET".to_string()).to_string());
+}
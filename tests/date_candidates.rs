@@ -0,0 +1,54 @@
+mod common;
+
+use chrono::NaiveDate;
+use make_xnview_slideshow::{
+    cache::{CacheKey, CacheOptions},
+    config::SlideshowConfig,
+    date::{DateOptions, DatePick, apply_date_options},
+    image_info::{AnalysisOptions, DateSource, ImageInfo},
+};
+
+fn no_cache() -> CacheOptions {
+    CacheOptions::new(true, false, None, CacheKey::Path)
+}
+
+fn date_time(year: i32, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> chrono::NaiveDateTime {
+    NaiveDate::from_ymd_opt(year, month, day).expect("valid date").and_hms_opt(hour, minute, second).expect("valid time")
+}
+
+#[tokio::test]
+async fn exif_date_is_the_oldest_candidate() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let path = common::write_fixture(dir.path(), "a.jpg", &common::jpeg(8, 6, Some("2019:07:14 18:30:00")));
+    let mut image_info = ImageInfo::from_path(&path, &no_cache(), AnalysisOptions::default()).await.expect("readable");
+    apply_date_options(&mut image_info, &DateOptions::default());
+    assert_eq!(image_info.creation_date_time, date_time(2019, 7, 14, 18, 30, 0));
+    assert!(image_info.has_exif_date());
+    assert_eq!((image_info.width, image_info.height), (8, 6));
+}
+
+#[tokio::test]
+async fn path_date_is_used_without_exif() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let path = common::write_fixture(dir.path(), "IMG_20150301_101500.jpg", &common::jpeg(8, 6, None));
+    let mut slideshow = SlideshowConfig::from_image_dirs(&[dir.path().to_path_buf()]).expect("valid dirs");
+    slideshow.path_dates = true;
+    let mut image_info = ImageInfo::from_path(&path, &no_cache(), AnalysisOptions::default()).await.expect("readable");
+    apply_date_options(&mut image_info, &DateOptions::from_slideshow(&slideshow).expect("valid patterns"));
+    assert_eq!(image_info.creation_date_time, date_time(2015, 3, 1, 10, 15, 0));
+    assert!(!image_info.has_exif_date());
+}
+
+#[tokio::test]
+async fn listed_sources_win_over_older_ones() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let path = common::write_fixture(dir.path(), "a.jpg", &common::jpeg(8, 6, Some("2019:07:14 18:30:00")));
+    let mut image_info = ImageInfo::from_path(&path, &no_cache(), AnalysisOptions::default()).await.expect("readable");
+    let date_options = DateOptions { date_sources: Some(vec![DateSource::Mtime]), date_pick: DatePick::First, ..Default::default() };
+    apply_date_options(&mut image_info, &date_options);
+    let mtime = image_info.date_time_candidates.iter().find(|candidate| candidate.source == DateSource::Mtime).expect("always a candidate").date_time;
+    assert_eq!(image_info.creation_date_time, mtime);
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use make_xnview_slideshow::filter::{FilterReason, ImageFilter};
+use serde_json::json;
+
+fn image_filter(fields: serde_json::Value) -> ImageFilter {
+    serde_json::from_value(fields).expect("valid filter")
+}
+
+#[test]
+fn last_n_days_is_of_the_clock() {
+    common::init();
+    let filter = image_filter(json!({ "last_n_days": 7 }));
+    assert_eq!(filter.rejection(&common::image_info(json!({ "creation_date_time": "2024-06-10T19:30:00" }))), None);
+    assert_eq!(filter.rejection(&common::image_info(json!({ "creation_date_time": "2024-06-01T19:30:00" }))), Some(FilterReason::CreationDate));
+}
+
+#[test]
+fn aspect_ratio_and_orientation() {
+    common::init();
+    let filter = image_filter(json!({ "min_aspect_ratio": 1.2 }));
+    assert_eq!(filter.rejection(&common::image_info(json!({ "width": 4000, "height": 3000 }))), None);
+    assert_eq!(filter.rejection(&common::image_info(json!({ "width": 3000, "height": 3000 }))), Some(FilterReason::AspectRatio));
+    // rotated by the exif orientation
+    let filter = image_filter(json!({ "orientation": "portrait" }));
+    assert_eq!(filter.rejection(&common::image_info(json!({ "orientation": 6 }))), None);
+    assert_eq!(filter.rejection(&common::image_info(json!({}))), Some(FilterReason::Orientation));
+}
+
+#[test]
+fn time_of_day_and_weekdays() {
+    common::init();
+    // 2024-06-10 is a monday
+    let evening = common::image_info(json!({ "creation_date_time": "2024-06-10T19:30:00" }));
+    let night = common::image_info(json!({ "creation_date_time": "2024-06-10T01:30:00" }));
+    assert_eq!(image_filter(json!({ "time_of_day": "18:00-23:00" })).rejection(&evening), None);
+    assert_eq!(image_filter(json!({ "time_of_day": "18:00-23:00" })).rejection(&night), Some(FilterReason::TimeOfDay));
+    assert_eq!(image_filter(json!({ "time_of_day": "22:00-02:00" })).rejection(&night), None);
+    assert_eq!(image_filter(json!({ "weekdays": ["Sat", "Sun"] })).rejection(&evening), Some(FilterReason::Weekday));
+    assert_eq!(image_filter(json!({ "weekdays": ["Mon"] })).rejection(&evening), None);
+}
+
+#[test]
+fn keywords_and_rating() {
+    common::init();
+    let filter = image_filter(json!({ "min_rating": 4, "required_keywords": ["family"], "excluded_keywords": ["private"] }));
+    assert_eq!(filter.rejection(&common::image_info(json!({ "rating": 5, "keywords": ["Family"] }))), None);
+    assert_eq!(filter.rejection(&common::image_info(json!({ "keywords": ["family"] }))), Some(FilterReason::Rating));
+    assert_eq!(filter.rejection(&common::image_info(json!({ "rating": 4, "keywords": ["family", "private"] }))), Some(FilterReason::Keywords));
+}
+
+#[test]
+fn expression() {
+    common::init();
+    let filter = image_filter(json!({ "filter": r#"rating >= 4 && (keyword("family") || camera == "X100V") && aspect in 1.2..1.9"# }));
+    assert_eq!(filter.rejection(&common::image_info(json!({ "rating": 4, "camera_model": "x100v" }))), None);
+    assert_eq!(filter.rejection(&common::image_info(json!({ "rating": 4, "keywords": ["family"], "width": 1000, "height": 1000 }))), Some(FilterReason::Expression));
+    // without the rating, the comparison is false
+    assert_eq!(filter.rejection(&common::image_info(json!({ "keywords": ["family"] }))), Some(FilterReason::Expression));
+    assert!(serde_json::from_value::<ImageFilter>(json!({ "filter": "rating >= \"4\"" })).is_err());
+    assert!(serde_json::from_value::<ImageFilter>(json!({ "filter": "rating >= 4 &&" })).is_err());
+    assert!(serde_json::from_value::<ImageFilter>(json!({ "filter": "iso > 100" })).is_err());
+}
+
+#[test]
+fn monochrome() {
+    common::init();
+    let gray = common::image_info(json!({ "saturation": 0.01 }));
+    let color = common::image_info(json!({ "saturation": 0.2 }));
+    assert_eq!(image_filter(json!({ "is_monochrome": true })).rejection(&gray), None);
+    assert_eq!(image_filter(json!({ "is_monochrome": true })).rejection(&color), Some(FilterReason::Monochrome));
+    assert_eq!(image_filter(json!({ "is_monochrome": false })).rejection(&gray), Some(FilterReason::Monochrome));
+    assert_eq!(image_filter(json!({ "is_monochrome": true, "max_monochrome_saturation": 0.25 })).rejection(&color), None);
+    // not analyzed, e.g. videos, pass
+    assert_eq!(image_filter(json!({ "is_monochrome": true })).rejection(&common::image_info(json!({}))), None);
+}
@@ -0,0 +1,32 @@
+mod common;
+
+use image::{DynamicImage, ImageBuffer, Luma, Rgb};
+use make_xnview_slideshow::{
+    cache::{CacheKey, CacheOptions},
+    image_info::{AnalysisOptions, ImageInfo, quality},
+};
+
+#[tokio::test]
+async fn header_sizes_are_of_the_decoded_pixels() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let cache_options = CacheOptions::new(true, false, None, CacheKey::Path);
+    let pixels: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::from_fn(37, 23, |x, y| Rgb([(x * 7) as u8, (y * 11) as u8, 128]));
+    for extension in ["jpg", "png", "gif", "bmp", "tif", "webp"] {
+        let path = dir.path().join(format!("a.{}", extension));
+        pixels.save(&path).expect("encodable");
+        let probed = ImageInfo::from_path(&path, &cache_options, AnalysisOptions::default()).await.expect("readable");
+        let decoded = ImageInfo::from_path(&path, &cache_options, AnalysisOptions { verify: true, ..Default::default() }).await.expect("decodable");
+        assert_eq!((probed.width, probed.height), (decoded.width, decoded.height), "{}", extension);
+        assert_eq!((probed.width, probed.height), (37, 23), "{}", extension);
+    }
+}
+
+#[test]
+fn small_images_are_not_upscaled_for_the_sharpness() {
+    // a checkerboard, of which each laplacian inside is 4 * 255 either way
+    let checkerboard = ImageBuffer::from_fn(4, 4, |x, y| Luma([if (x + y) % 2 == 0 { 0u8 } else { 255 }]));
+    let (sharpness, brightness) = quality(&DynamicImage::ImageLuma8(checkerboard));
+    assert_eq!(sharpness, 1020.0 * 1020.0);
+    assert_eq!(brightness, 0.5);
+}
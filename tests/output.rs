@@ -0,0 +1,71 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+use make_xnview_slideshow::{config::SlideshowConfig, output::{OutputWriter, read_output}};
+use serde_json::json;
+
+fn slideshow(path: &Path) -> SlideshowConfig {
+    serde_json::from_value(json!({"path": path, "image_dirs": ["/photos"], "relative_paths": true})).expect("valid config")
+}
+
+async fn write_output(slideshow: &SlideshowConfig, paths: &[PathBuf]) -> String {
+    let mut writer = OutputWriter::from_slideshow(slideshow).await.expect("writable");
+    writer.write_header(slideshow).await.expect("writable");
+    for path in paths {
+        writer.write_image_path(path).await.expect("writable");
+    }
+    writer.finish().await.expect("writable");
+    std::fs::read_to_string(&slideshow.path).expect("readable")
+}
+
+// the separators are rewritten on windows
+#[cfg_attr(windows, ignore)]
+#[tokio::test]
+async fn paths_are_relative_to_the_dir_of_the_output() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let slides_dir = dir.path().join("slides");
+    std::fs::create_dir(&slides_dir).expect("writable");
+    let slideshow = slideshow(&slides_dir.join("a.sld"));
+    let paths = [
+        slides_dir.join("a.jpg"),
+        dir.path().join("x").join("a.jpg"),
+        // normalized lexically before
+        slides_dir.join("..").join("x").join(".").join("b.jpg"),
+        slides_dir.join("2019").join("c.jpg"),
+    ];
+    let text = write_output(&slideshow, &paths).await;
+    let entries: Vec<&str> = text.lines().filter(|line| line.starts_with('"')).collect();
+    assert_eq!(entries, vec!["\"a.jpg\"", "\"../x/a.jpg\"", "\"../x/b.jpg\"", "\"2019/c.jpg\""]);
+    let existing_slideshow = read_output(&slideshow).await.expect("readable");
+    assert_eq!(existing_slideshow.paths, vec![paths[0].clone(), paths[1].clone(), dir.path().join("x").join("b.jpg"), paths[3].clone()]);
+}
+
+// the images can't be reached from a base dir above the current dir, so the paths are kept
+#[cfg_attr(windows, ignore)]
+#[tokio::test]
+async fn paths_are_kept_as_is_without_a_relative_path() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let slides_dir = dir.path().join("slides");
+    std::fs::create_dir(&slides_dir).expect("writable");
+    std::env::set_current_dir(&slides_dir).expect("enterable");
+    let slideshow = slideshow(Path::new("../slides/a.sld"));
+    let text = write_output(&slideshow, &[PathBuf::from("/photos/a.jpg")]).await;
+    assert!(text.lines().any(|line| line == "\"/photos/a.jpg\""));
+}
+
+#[cfg(windows)]
+#[tokio::test]
+async fn paths_on_another_drive_are_kept_as_is() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let slideshow = slideshow(&dir.path().join("a.sld"));
+    // a drive other than the one of the temp dir
+    let drive = if dir.path().starts_with("Z:\\") { "Y:" } else { "Z:" };
+    let path = PathBuf::from(format!("{}\\photos\\a.jpg", drive));
+    let text = write_output(&slideshow, &[path.clone()]).await;
+    assert!(text.lines().any(|line| line == format!("\"{}\"", path.display())));
+    let existing_slideshow = read_output(&slideshow).await.expect("readable");
+    assert_eq!(existing_slideshow.paths, vec![path]);
+}
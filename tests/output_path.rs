@@ -0,0 +1,36 @@
+mod common;
+
+use std::{path::{Path, PathBuf}, time::SystemTime};
+use chrono::{Local, NaiveDate, TimeZone};
+use make_xnview_slideshow::output_path::{has_placeholders, remove_expired_outputs, resolve_path_template};
+
+fn write_output(dir: &Path, name: &str, modified: NaiveDate) -> PathBuf {
+    let path = common::write_fixture(dir, name, b"# Slide Show Sequence v2\n");
+    let modified = Local.from_local_datetime(&modified.and_hms_opt(12, 0, 0).expect("valid time")).single().expect("not ambiguous");
+    std::fs::File::options().write(true).open(&path).expect("writable").set_modified(SystemTime::from(modified)).expect("settable");
+    path
+}
+
+#[test]
+fn placeholders_are_of_the_day() {
+    let path_template = Path::new("/slides/{name}-{today}.sld");
+    assert!(has_placeholders(path_template));
+    assert!(!has_placeholders(Path::new("/slides/family.sld")));
+    assert_eq!(resolve_path_template(path_template, "family", common::today()), PathBuf::from("/slides/family-2024-06-15.sld"));
+    assert_eq!(resolve_path_template(Path::new("/slides/{year}-{month}.sld"), "", common::today()), PathBuf::from("/slides/2024-06.sld"));
+}
+
+#[tokio::test]
+async fn expired_outputs_of_the_template_are_removed() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let expired_path = write_output(dir.path(), "family-2024-06-01.sld", NaiveDate::from_ymd_opt(2024, 6, 1).expect("valid date"));
+    let kept_path = write_output(dir.path(), "family-2024-06-14.sld", NaiveDate::from_ymd_opt(2024, 6, 14).expect("valid date"));
+    let other_path = write_output(dir.path(), "family.sld", NaiveDate::from_ymd_opt(2024, 6, 1).expect("valid date"));
+    let current_path = write_output(dir.path(), "family-2024-06-15.sld", common::today());
+    let path_template = dir.path().join("{name}-{today}.sld");
+    let n_removed = remove_expired_outputs(&path_template, "family", &current_path, 7).await.expect("removable");
+    assert_eq!(n_removed, 1);
+    assert!(!expired_path.exists());
+    assert!(kept_path.exists() && other_path.exists() && current_path.exists());
+}
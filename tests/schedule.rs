@@ -0,0 +1,16 @@
+use chrono::{Local, TimeZone};
+use make_xnview_slideshow::schedule::Schedule;
+
+#[test]
+fn zero_intervals_are_refused() {
+    for schedule in ["0s", "0m", "0h", "0d"] {
+        assert!(Schedule::parse(schedule).is_err(), "{}", schedule);
+    }
+}
+
+#[test]
+fn intervals_are_after_the_time() {
+    let now = Local.with_ymd_and_hms(2024, 6, 15, 12, 0, 0).single().expect("not ambiguous");
+    let schedule = Schedule::parse("90s").expect("valid schedule");
+    assert_eq!(schedule.next_after(now), Some(now + chrono::TimeDelta::seconds(90)));
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use rand::{SeedableRng, rngs::StdRng};
+use serde_json::json;
+use std::path::PathBuf;
+use make_xnview_slideshow::selection::{Freshness, SampleStrategy, mix_freshness, mix_image_infos};
+
+#[test]
+fn freshness_keeps_the_ratio_of_the_recent_ones() {
+    common::init();
+    let freshness = Freshness { recent_days: 7, recent_ratio: 0.3 };
+    let recent_image_infos = (0..3).map(|i| common::image_info(json!({"path": format!("/photos/recent-{}.jpg", i), "creation_date_time": format!("2024-06-1{}T09:00:00", i + 2)})));
+    let archive_image_infos = (0..20).map(|i| common::image_info(json!({"path": format!("/photos/archive-{}.jpg", i), "creation_date_time": format!("2019-07-{:02}T09:00:00", i + 1)})));
+    let image_infos: Vec<_> = recent_image_infos.chain(archive_image_infos).collect();
+    let mut rng = StdRng::seed_from_u64(1);
+
+    // the 3 recent ones don't shrink the show, the archive makes up for the rest
+    let mixed_image_infos = mix_freshness(image_infos.clone(), &freshness, None, SampleStrategy::Uniform, &mut rng);
+    assert_eq!(mixed_image_infos.len(), 23);
+    assert_eq!(mixed_image_infos.iter().filter(|image_info| freshness.is_recent(image_info, common::today())).count(), 3);
+    let mixed_image_infos = mix_freshness(image_infos.clone(), &freshness, Some(20), SampleStrategy::Uniform, &mut rng);
+    assert_eq!(mixed_image_infos.len(), 20);
+    assert_eq!(mixed_image_infos.iter().filter(|image_info| freshness.is_recent(image_info, common::today())).count(), 3);
+
+    let mixed_image_infos = mix_freshness(image_infos.clone(), &freshness, Some(4), SampleStrategy::Uniform, &mut rng);
+    assert_eq!(mixed_image_infos.len(), 4);
+    assert_eq!(mixed_image_infos.iter().filter(|image_info| freshness.is_recent(image_info, common::today())).count(), 1);
+
+    // without the recent ones, the archive alone
+    let archive_only: Vec<_> = image_infos.into_iter().skip(3).collect();
+    assert_eq!(mix_freshness(archive_only, &freshness, Some(5), SampleStrategy::Uniform, &mut rng).len(), 5);
+}
+
+#[test]
+fn dir_weights_sum_up_to_the_sample() {
+    let image_infos: Vec<_> = ["a", "b"].iter()
+        .flat_map(|dir| (0..10).map(move |i| common::image_info(json!({"path": format!("/photos/{}/{}.jpg", dir, i)}))))
+        .collect();
+    let dir_weights = vec![(PathBuf::from("/photos/a"), 0.5), (PathBuf::from("/photos/b"), 0.5)];
+    let mut rng = StdRng::seed_from_u64(1);
+    for sample in [1, 3, 7] {
+        assert_eq!(mix_image_infos(image_infos.clone(), &dir_weights, Some(sample), SampleStrategy::Uniform, &mut rng).len(), sample);
+    }
+    // the three of a third each
+    let dir_weights = vec![(PathBuf::from("/photos/a"), 1.0), (PathBuf::from("/photos/b"), 1.0), (PathBuf::from("/photos/c"), 1.0)];
+    let image_infos: Vec<_> = ["a", "b", "c"].iter()
+        .flat_map(|dir| (0..10).map(move |i| common::image_info(json!({"path": format!("/photos/{}/{}.jpg", dir, i)}))))
+        .collect();
+    assert_eq!(mix_image_infos(image_infos, &dir_weights, Some(5), SampleStrategy::Uniform, &mut rng).len(), 5);
+}
+
+#[test]
+fn freshness_sums_up_to_the_sample() {
+    common::init();
+    let freshness = Freshness { recent_days: 7, recent_ratio: 0.5 };
+    let recent_image_infos = (0..5).map(|i| common::image_info(json!({"path": format!("/photos/recent-{}.jpg", i), "creation_date_time": "2024-06-14T09:00:00"})));
+    let archive_image_infos = (0..50).map(|i| common::image_info(json!({"path": format!("/photos/archive-{}.jpg", i), "creation_date_time": "2019-07-01T09:00:00"})));
+    let image_infos: Vec<_> = recent_image_infos.chain(archive_image_infos).collect();
+    let mut rng = StdRng::seed_from_u64(1);
+    for sample in [1, 3, 7] {
+        let mixed_image_infos = mix_freshness(image_infos.clone(), &freshness, Some(sample), SampleStrategy::Uniform, &mut rng);
+        assert_eq!(mixed_image_infos.len(), sample);
+        let n_recent = mixed_image_infos.iter().filter(|image_info| freshness.is_recent(image_info, common::today())).count();
+        assert!(n_recent.abs_diff(sample - n_recent) <= 1);
+    }
+    // without sample, as many as the archive allows, of which the recent ones are half
+    let mixed_image_infos = mix_freshness(image_infos, &freshness, None, SampleStrategy::Uniform, &mut rng);
+    assert_eq!(mixed_image_infos.len(), 55);
+}
@@ -0,0 +1,174 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+use make_xnview_slideshow::{
+    Error,
+    format::{FormatHeader, SlideshowFormat},
+    slideshow::{Color, EntryOptions, OutputEncoding, SlideshowEntry, SlideshowHeader, SlideshowWriter, UnencodablePaths, parse_slideshow, read_parsed_slideshow, read_slideshow},
+};
+
+// the separators are rewritten on windows
+#[cfg_attr(windows, ignore)]
+#[tokio::test]
+async fn quotes_and_backslashes_are_escaped() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let sld_path = dir.path().join("a.sld");
+    let mut writer = SlideshowWriter::from_path(&sld_path, OutputEncoding::Utf8).await.expect("writable");
+    writer.write_image_path(r#"/photos/say "cheese"/a\b.jpg"#, Some(2.5), Some(r#"a "quoted" info"#)).await.expect("writable");
+    writer.flush().await.expect("writable");
+    let text = std::fs::read_to_string(&sld_path).expect("readable");
+    assert_eq!(text, "\"/photos/say \\\"cheese\\\"/a\\\\b.jpg\" Timer=2.5 Info=\"a \\\"quoted\\\" info\"\n");
+}
+
+// the separators are rewritten on windows
+#[cfg_attr(windows, ignore)]
+#[tokio::test]
+async fn entries_round_trip() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let sld_path = dir.path().join("a.sld");
+    let paths = [PathBuf::from(r#"/photos/say "cheese".jpg"#), PathBuf::from("/photos/写真.jpg"), PathBuf::from(r"/photos/a\b.jpg")];
+    let mut writer = SlideshowWriter::from_path(&sld_path, OutputEncoding::ShiftJis).await.expect("writable");
+    writer.write_raw_header("# Slide Show Sequence v2\n").await.expect("writable");
+    for (i, path) in paths.iter().enumerate() {
+        writer.write_entry(path, &EntryOptions { effect: Some(i as u32), stretch: Some(0), ..Default::default() }).await.expect("writable");
+    }
+    writer.flush().await.expect("writable");
+    let existing_slideshow = read_slideshow(&sld_path, OutputEncoding::ShiftJis).await.expect("readable");
+    assert_eq!(existing_slideshow.header, "# Slide Show Sequence v2\n");
+    assert_eq!(existing_slideshow.paths, paths);
+}
+
+// the separators are rewritten on windows
+#[cfg_attr(windows, ignore)]
+#[tokio::test]
+async fn header_and_entry_options_round_trip() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let sld_path = dir.path().join("a.sld");
+    let slideshow_header = SlideshowHeader { timer: 5, ..Default::default() };
+    let mut writer = SlideshowWriter::from_path(&sld_path, OutputEncoding::Utf8Bom).await.expect("writable");
+    writer.write_header(&FormatHeader { title: "a", width: 1920, height: 1080, slideshow_header: &slideshow_header }).await.expect("writable");
+    let entry_options = EntryOptions { duration_secs: Some(2.5), info: Some(r#"say "cheese" \o/"#), effect: Some(3), stretch: Some(1) };
+    writer.write_entry(Path::new("/photos/a.jpg"), &entry_options).await.expect("writable");
+    writer.write_entry(Path::new("/photos/b.jpg"), &EntryOptions::default()).await.expect("writable");
+    writer.finish().await.expect("writable");
+    let parsed_slideshow = read_parsed_slideshow(&sld_path, OutputEncoding::Utf8Bom).await.expect("valid");
+    assert!(parsed_slideshow.header.starts_with("# Slide Show Sequence v2\n"));
+    assert_eq!(parsed_slideshow.header_value("Timer"), Some("5"));
+    assert_eq!(parsed_slideshow.header_value("WinWidth"), Some("1920"));
+    assert_eq!(parsed_slideshow.entries, vec![
+        SlideshowEntry {
+            path: PathBuf::from("/photos/a.jpg"),
+            duration_secs: Some(2.5),
+            info: Some(r#"say "cheese" \o/"#.to_string()),
+            effect: Some(3),
+            stretch: Some(1),
+        },
+        SlideshowEntry { path: PathBuf::from("/photos/b.jpg"), ..Default::default() },
+    ]);
+}
+
+#[test]
+fn broken_lines_are_refused() {
+    let path = Path::new("a.sld");
+    assert!(parse_slideshow(path, "# Slide Show Sequence v2\nTimer = 2\n\n\"/a.jpg\" Timer=1 Info=\"x y\"\n").is_ok());
+    // unterminated quote
+    assert!(parse_slideshow(path, "\"/a.jpg\n").is_err());
+    // neither a header line nor an entry
+    assert!(parse_slideshow(path, "/a.jpg\n").is_err());
+    assert!(parse_slideshow(path, "\"/a.jpg\" Timer=x\n").is_err());
+    assert!(parse_slideshow(path, "\"/a.jpg\"Timer=1\n").is_err());
+    assert!(parse_slideshow(path, "\"/a.jpg\" Info=\"x\n").is_err());
+}
+
+// the separators are rewritten on windows
+#[cfg_attr(windows, ignore)]
+#[tokio::test]
+async fn unencodable_paths() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let sld_path = dir.path().join("a.sld");
+    let write = |unencodable_paths| {
+        let sld_path = sld_path.clone();
+        async move {
+            let mut writer = SlideshowWriter::from_path(&sld_path, OutputEncoding::ShiftJis).await?.with_unencodable_paths(unencodable_paths);
+            writer.write_entry(Path::new("/photos/café😀.jpg"), &EntryOptions::default()).await?;
+            writer.write_entry(Path::new("/photos/写真.jpg"), &EntryOptions::default()).await?;
+            writer.finish().await?;
+            read_slideshow(&sld_path, OutputEncoding::ShiftJis).await
+        }
+    };
+    let skipped = write(UnencodablePaths::Skip).await.expect("skipped");
+    assert_eq!(skipped.paths, vec![PathBuf::from("/photos/写真.jpg")]);
+    let transliterated = write(UnencodablePaths::Transliterate).await.expect("transliterated");
+    assert_eq!(transliterated.paths, vec![PathBuf::from("/photos/cafe_.jpg"), PathBuf::from("/photos/写真.jpg")]);
+    assert!(write(UnencodablePaths::Error).await.is_err());
+}
+
+// the separators are rewritten on windows
+#[cfg_attr(windows, ignore)]
+#[tokio::test]
+async fn encodings_are_of_the_written_bytes() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let path = Path::new("/photos/写真.jpg");
+    let expected_bytes: [(OutputEncoding, &[u8]); 3] = [
+        (OutputEncoding::Utf8, "# Slide Show Sequence v2\n\"/photos/写真.jpg\"\n".as_bytes()),
+        (OutputEncoding::Utf8Bom, "\u{feff}# Slide Show Sequence v2\n\"/photos/写真.jpg\"\n".as_bytes()),
+        (OutputEncoding::ShiftJis, b"# Slide Show Sequence v2\n\"/photos/\x8e\xca\x90\x5e.jpg\"\n"),
+    ];
+    for (encoding, expected_bytes) in expected_bytes {
+        let sld_path = dir.path().join(format!("{}.sld", encoding.name()));
+        let mut writer = SlideshowWriter::from_path(&sld_path, encoding).await.expect("writable");
+        writer.write_raw_header("# Slide Show Sequence v2\n").await.expect("writable");
+        writer.write_entry(path, &EntryOptions::default()).await.expect("writable");
+        writer.finish().await.expect("writable");
+        assert_eq!(std::fs::read(&sld_path).expect("readable"), expected_bytes, "{}", encoding.name());
+        let existing_slideshow = read_slideshow(&sld_path, encoding).await.expect("readable");
+        assert_eq!(existing_slideshow.paths, vec![path.to_path_buf()], "{}", encoding.name());
+    }
+}
+
+// a name of an old disk, which is shift_jis rather than utf-8
+#[cfg(unix)]
+#[tokio::test]
+async fn shift_jis_bytes_are_written_as_is() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let sld_path = dir.path().join("a.sld");
+    let path = Path::new(OsStr::from_bytes(b"/photos/\x95\x5c.jpg"));
+    let mut writer = SlideshowWriter::from_path(&sld_path, OutputEncoding::ShiftJis).await.expect("writable");
+    writer.write_entry(path, &EntryOptions::default()).await.expect("writable");
+    writer.finish().await.expect("writable");
+    // 0x5c of the trailing byte is not a backslash to escape
+    assert_eq!(std::fs::read(&sld_path).expect("readable"), b"\"/photos/\x95\x5c.jpg\"\n");
+}
+
+#[tokio::test]
+async fn colors_of_the_config_are_written_in_the_header() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let sld_path = dir.path().join("a.sld");
+    let slideshow_header: SlideshowHeader = serde_json::from_value(serde_json::json!({
+        "background_color": "#102030",
+        "text_color": [255, 255, 0, 128],
+    })).expect("valid header");
+    let mut writer = SlideshowWriter::from_path(&sld_path, OutputEncoding::Utf8).await.expect("writable");
+    writer.write_header(&FormatHeader { title: "a", width: 1920, height: 1080, slideshow_header: &slideshow_header }).await.expect("writable");
+    writer.finish().await.expect("writable");
+    let parsed_slideshow = read_parsed_slideshow(&sld_path, OutputEncoding::Utf8).await.expect("valid");
+    assert_eq!(parsed_slideshow.header_value("BackgroundColor"), Some("16 32 48 255"));
+    assert_eq!(parsed_slideshow.header_value("TextColor"), Some("255 255 0 128"));
+}
+
+#[test]
+fn invalid_colors_are_refused() {
+    assert_eq!(serde_json::from_value::<Color>(serde_json::json!("#10203040")).expect("valid color"), Color([16, 32, 48, 64]));
+    for invalid in ["#GGHHII", "102030", "#1020", "#１０２０３０"] {
+        let e = serde_json::from_value::<Color>(serde_json::json!(invalid)).expect_err("invalid color");
+        assert_eq!(e.to_string(), Error::ColorError(invalid.to_string()).to_string());
+    }
+}
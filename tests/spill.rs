@@ -0,0 +1,53 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+use make_xnview_slideshow::{
+    cache::cache_parent_dir,
+    collation::{PathCollation, PathComparator},
+    image_info::ImageInfo,
+    selection::{SortOrder, sort_image_infos},
+    spill::SpillSorter,
+};
+use serde_json::json;
+
+fn spilled_dirs(spill_parent_dir: &Path) -> usize {
+    std::fs::read_dir(spill_parent_dir).map_or(0, |entries| entries.count())
+}
+
+// both in one test, as the spill dirs of the process are counted
+#[tokio::test]
+async fn spilled_runs_are_merged_in_the_sort_order_and_removed() {
+    common::init();
+    let spill_parent_dir = cache_parent_dir().await.expect("cache dir").join("spill");
+    // unsorted, with the same dates for the ties broken by path
+    let image_infos: Vec<ImageInfo> = (0..50)
+        .map(|i| common::image_info(json!({
+            "path": format!("/photos/{:02}.jpg", (i * 31) % 50),
+            "creation_date_time": format!("2024-06-{:02}T12:00:00", 1 + (i * 7) % 5),
+        })))
+        .collect();
+    for sort_order in [SortOrder::CreationDateAsc, SortOrder::CreationDateDesc, SortOrder::Path] {
+        let path_comparator = PathComparator::new(PathCollation::Natural, None).expect("valid collation");
+        // a few images a run
+        let mut spill_sorter = SpillSorter::new(sort_order, path_comparator, 4 * std::mem::size_of::<ImageInfo>());
+        for image_info in &image_infos {
+            spill_sorter.push(image_info.clone()).await.expect("writable");
+        }
+        assert_eq!(spilled_dirs(&spill_parent_dir), 1);
+        let merged: Vec<PathBuf> = spill_sorter.finish().expect("readable")
+            .map(|image_info| image_info.expect("readable").path)
+            .collect();
+        let mut sorted = image_infos.clone();
+        sort_image_infos(&mut sorted, sort_order, &PathComparator::new(PathCollation::Natural, None).expect("valid collation"), &mut rand::thread_rng());
+        assert_eq!(merged, sorted.into_iter().map(|image_info| image_info.path).collect::<Vec<_>>(), "{:?}", sort_order);
+        assert_eq!(spilled_dirs(&spill_parent_dir), 0);
+    }
+
+    // failed before finishing
+    let path_comparator = PathComparator::new(PathCollation::Bytewise, None).expect("valid collation");
+    let mut spill_sorter = SpillSorter::new(SortOrder::Path, path_comparator, 1);
+    spill_sorter.push(image_infos[0].clone()).await.expect("writable");
+    assert_eq!(spilled_dirs(&spill_parent_dir), 1);
+    drop(spill_sorter);
+    assert_eq!(spilled_dirs(&spill_parent_dir), 0);
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use std::{path::Path, sync::Arc};
+use futures::StreamExt;
+use make_xnview_slideshow::{
+    config::SlideshowConfig,
+    scan::{ScanMemo, ScanStats, WalkOptions, image_path_stream},
+};
+use serde_json::json;
+
+fn walk_options(dir: &Path) -> WalkOptions {
+    let slideshow: SlideshowConfig = serde_json::from_value(json!({"path": dir.join("a.sld"), "image_dirs": [dir]})).expect("valid config");
+    WalkOptions::from_slideshow(&slideshow).expect("valid walk options")
+}
+
+#[tokio::test]
+async fn file_gone_since_the_listing_is_skipped_unless_strict() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let jpeg = common::jpeg(8, 6, None);
+    common::write_fixture(dir.path(), "a.jpg", &jpeg);
+    let gone_path = common::write_fixture(dir.path(), "b.jpg", &jpeg);
+    // the listing is memoized, so the second walk sees the file deleted after it
+    let memo = Arc::new(ScanMemo::default());
+    let image_paths: Vec<_> = image_path_stream(vec![dir.path().to_path_buf()], walk_options(dir.path()), memo.clone(), Arc::new(ScanStats::default()), false).collect().await;
+    assert_eq!(image_paths.len(), 2);
+    std::fs::remove_file(&gone_path).expect("removable");
+
+    let stats = Arc::new(ScanStats::default());
+    let image_paths: Vec<_> = image_path_stream(vec![dir.path().to_path_buf()], walk_options(dir.path()), memo.clone(), stats.clone(), false).collect().await;
+    let image_paths: Vec<_> = image_paths.into_iter().map(|image_path| image_path.expect("skipped unless strict").0).collect();
+    assert_eq!(image_paths, vec![dir.path().join("a.jpg")]);
+    let skipped_files = stats.skipped_files();
+    assert_eq!(skipped_files.len(), 1);
+    assert_eq!(skipped_files[0].path, gone_path);
+
+    let image_paths: Vec<_> = image_path_stream(vec![dir.path().to_path_buf()], walk_options(dir.path()), memo, Arc::new(ScanStats::default()), true).collect().await;
+    assert!(image_paths.iter().any(|image_path| image_path.is_err()));
+}
+
+#[tokio::test]
+async fn missing_dir_is_skipped_unless_strict() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    common::write_fixture(dir.path(), "a.jpg", &common::jpeg(8, 6, None));
+    let missing_dir = dir.path().join("missing");
+    let stats = Arc::new(ScanStats::default());
+    let image_paths: Vec<_> = image_path_stream(vec![dir.path().to_path_buf(), missing_dir.clone()], walk_options(dir.path()), Arc::new(ScanMemo::default()), stats.clone(), false).collect().await;
+    assert_eq!(image_paths.len(), 1);
+    assert!(image_paths[0].is_ok());
+    assert_eq!(stats.skipped_files()[0].path, missing_dir);
+
+    let image_paths: Vec<_> = image_path_stream(vec![missing_dir], walk_options(dir.path()), Arc::new(ScanMemo::default()), Arc::new(ScanStats::default()), true).collect().await;
+    assert!(image_paths[0].is_err());
+}
+
+// the modified time of a dir is set back by opening it, which windows doesn't allow
+#[cfg(unix)]
+#[tokio::test]
+async fn resumed_dirs_are_listed_again_when_changed() {
+    common::init();
+    let dir = tempfile::tempdir().expect("temp dir");
+    let jpeg = common::jpeg(8, 6, None);
+    let kept_dir = dir.path().join("kept");
+    let changed_dir = dir.path().join("changed");
+    for sub_dir in [&kept_dir, &changed_dir] {
+        std::fs::create_dir(sub_dir).expect("writable");
+        common::write_fixture(sub_dir, "a.jpg", &jpeg);
+    }
+    let memo = Arc::new(ScanMemo::default());
+    let image_paths: Vec<_> = image_path_stream(vec![dir.path().to_path_buf()], walk_options(dir.path()), memo.clone(), Arc::new(ScanStats::default()), true).collect().await;
+    assert_eq!(image_paths.len(), 2);
+    memo.write_checkpoint().await.expect("writable");
+
+    // added to both, while the kept one looks unchanged as of its modified time
+    let kept_modified = std::fs::metadata(&kept_dir).and_then(|metadata| metadata.modified()).expect("readable");
+    common::write_fixture(&kept_dir, "b.jpg", &jpeg);
+    common::write_fixture(&changed_dir, "b.jpg", &jpeg);
+    std::fs::File::open(&kept_dir).and_then(|kept_dir| kept_dir.set_modified(kept_modified)).expect("writable");
+    let changed_modified = std::fs::metadata(&changed_dir).and_then(|metadata| metadata.modified()).expect("readable");
+    std::fs::File::open(&changed_dir).and_then(|changed_dir| changed_dir.set_modified(changed_modified + std::time::Duration::from_secs(1))).expect("writable");
+
+    let memo = Arc::new(ScanMemo::read_checkpoint().await.expect("readable"));
+    ScanMemo::remove_checkpoint().await.expect("removable");
+    let image_paths: Vec<_> = image_path_stream(vec![dir.path().to_path_buf()], walk_options(dir.path()), memo, Arc::new(ScanStats::default()), true).collect().await;
+    let mut image_paths: Vec<_> = image_paths.into_iter().map(|image_path| image_path.expect("readable").0).collect();
+    image_paths.sort();
+    assert_eq!(image_paths, vec![changed_dir.join("a.jpg"), changed_dir.join("b.jpg"), kept_dir.join("a.jpg")]);
+}